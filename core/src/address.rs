@@ -0,0 +1,229 @@
+// src/address.rs
+//
+// Canonical account addresses.
+// --------------------------------------------------------------
+// • An `Address` is a version byte plus a 20-byte BLAKE3 hash: of the
+//   Dilithium3 public key for key accounts, of the policy for multisig
+//   accounts.  Its 21 raw bytes are the account key in chain state
+// • The text form is Base58Check of `network prefix ‖ version ‖ hash`
+//   with a 4-byte double-BLAKE3 checksum, so an address pasted into the
+//   wrong network or with a typo is refused
+// • Transactions spend from the hash of the signer's key in `tx.from`;
+//   only multisig spends and rewards name their account by address
+//   (`Address::from_sender`)
+//
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{MAINNET_CHAIN_ID, TESTNET_CHAIN_ID},
+    crypto::blake3_hash,
+    error::BlockchainError,
+    Result,
+};
+
+pub const ADDRESS_HASH_LEN: usize = 20;
+/// Raw length: version byte and hash.
+pub const ADDRESS_LEN: usize = 1 + ADDRESS_HASH_LEN;
+/// Account controlled by a single Dilithium3 key.
+pub const ADDRESS_VERSION_KEY: u8 = 0;
+/// Account controlled by a `MultisigPolicy`.
+pub const ADDRESS_VERSION_MULTISIG: u8 = 1;
+
+const CHECKSUM_LEN: usize = 4;
+
+/// Network an address is written for.  Only the text form carries it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Development,
+}
+
+impl Network {
+    /// Network of a chain; chains other than mainnet and testnet use the
+    /// development prefix.
+    pub fn from_chain_id(chain_id: &str) -> Self {
+        match chain_id {
+            MAINNET_CHAIN_ID => Network::Mainnet,
+            TESTNET_CHAIN_ID => Network::Testnet,
+            _ => Network::Development,
+        }
+    }
+
+    pub fn prefix(self) -> u8 {
+        match self {
+            Network::Mainnet => 0x35,
+            Network::Testnet => 0x6f,
+            Network::Development => 0x5a,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "RawAddress")]
+pub struct Address {
+    version: u8,
+    hash: [u8; ADDRESS_HASH_LEN],
+}
+
+/// Wire layout of `Address`, checked before it becomes one.
+#[derive(Deserialize)]
+struct RawAddress {
+    version: u8,
+    hash: [u8; ADDRESS_HASH_LEN],
+}
+
+impl TryFrom<RawAddress> for Address {
+    type Error = BlockchainError;
+
+    fn try_from(raw: RawAddress) -> Result<Self> {
+        let address = Self { version: raw.version, hash: raw.hash };
+        address.validate().map(|_| address)
+    }
+}
+
+impl Address {
+    pub fn from_public_key(public_key: &[u8]) -> Self {
+        Self::with_hash(ADDRESS_VERSION_KEY, &blake3_hash(public_key))
+    }
+
+    /// Multisig account whose policy encodes to `policy_bytes`.
+    pub fn multisig(policy_bytes: &[u8]) -> Self {
+        Self::with_hash(ADDRESS_VERSION_MULTISIG, &blake3_hash(policy_bytes))
+    }
+
+    fn with_hash(version: u8, digest: &[u8; 32]) -> Self {
+        let mut hash = [0u8; ADDRESS_HASH_LEN];
+        hash.copy_from_slice(&digest[..ADDRESS_HASH_LEN]);
+        Self { version, hash }
+    }
+
+    /// Account named by `from`: the address itself when it is one, else the
+    /// hash of the public key.  Public keys are far longer than an address,
+    /// so the two never clash.
+    pub fn from_sender(from: &[u8]) -> Self {
+        Self::from_bytes(from).unwrap_or_else(|| Self::from_public_key(from))
+    }
+
+    /// Parse raw account-key bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ADDRESS_LEN {
+            return None;
+        }
+        let mut hash = [0u8; ADDRESS_HASH_LEN];
+        hash.copy_from_slice(&bytes[1..]);
+        let address = Self { version: bytes[0], hash };
+        address.validate().ok().map(|_| address)
+    }
+
+    /// Raw bytes, as used for account keys.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(ADDRESS_LEN);
+        bytes.push(self.version);
+        bytes.extend_from_slice(&self.hash);
+        bytes
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn is_multisig(&self) -> bool {
+        self.version == ADDRESS_VERSION_MULTISIG
+    }
+
+    /// Is the version byte one this node knows?  Decoding already checks it.
+    pub fn validate(&self) -> Result<()> {
+        match self.version {
+            ADDRESS_VERSION_KEY | ADDRESS_VERSION_MULTISIG => Ok(()),
+            v => Err(BlockchainError::InvalidAddress(format!("unknown address version {v}"))),
+        }
+    }
+
+    /// Base58Check text form for `network`.
+    pub fn encode(&self, network: Network) -> String {
+        let mut payload = vec![network.prefix()];
+        payload.extend_from_slice(&self.to_bytes());
+        let checksum = checksum(&payload);
+        payload.extend_from_slice(&checksum);
+        bs58::encode(payload).into_string()
+    }
+
+    /// Parse the text form, refusing addresses of other networks.
+    pub fn parse(text: &str, network: Network) -> Result<Self> {
+        let decoded = bs58::decode(text.trim())
+            .into_vec()
+            .map_err(|_| BlockchainError::InvalidAddress(format!("'{text}' is not Base58")))?;
+        if decoded.len() != 1 + ADDRESS_LEN + CHECKSUM_LEN {
+            return Err(BlockchainError::InvalidAddress(format!("'{text}' has the wrong length")));
+        }
+        let (payload, check) = decoded.split_at(1 + ADDRESS_LEN);
+        if checksum(payload) != check {
+            return Err(BlockchainError::InvalidAddress(format!("'{text}' has a bad checksum")));
+        }
+        if payload[0] != network.prefix() {
+            return Err(BlockchainError::InvalidAddress(format!("'{text}' is not a {network:?} address")));
+        }
+        Self::from_bytes(&payload[1..])
+            .ok_or_else(|| BlockchainError::InvalidAddress(format!("'{text}' has an unknown version")))
+    }
+}
+
+impl fmt::Display for Address {
+    /// Hex of the raw bytes; use `encode` for the user-facing form.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.to_bytes()))
+    }
+}
+
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut check = [0u8; CHECKSUM_LEN];
+    check.copy_from_slice(&blake3_hash(&blake3_hash(payload))[..CHECKSUM_LEN]);
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_round_trip_is_network_bound() {
+        let address = Address::from_public_key(&[7u8; 1952]);
+        let text = address.encode(Network::Testnet);
+        assert_eq!(Address::parse(&text, Network::Testnet).unwrap(), address);
+        assert!(Address::parse(&text, Network::Mainnet).is_err());
+        assert_ne!(text, address.encode(Network::Mainnet));
+
+        // A changed character breaks the checksum.
+        let mut typo = text.into_bytes();
+        let last = typo.len() - 1;
+        typo[last] = if typo[last] == b'2' { b'3' } else { b'2' };
+        assert!(Address::parse(std::str::from_utf8(&typo).unwrap(), Network::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_sender_resolution() {
+        let key = [9u8; 1952];
+        let address = Address::from_public_key(&key);
+        assert_eq!(Address::from_sender(&key), address);
+        assert_eq!(Address::from_sender(&address.to_bytes()), address);
+        assert!(Address::from_bytes(&[5u8; ADDRESS_LEN]).is_none());
+        assert_eq!(Network::from_chain_id(MAINNET_CHAIN_ID), Network::Mainnet);
+        assert_eq!(Network::from_chain_id("numi-dev"), Network::Development);
+    }
+
+    #[test]
+    fn test_decoding_checks_the_version() {
+        let address = Address::multisig(b"policy");
+        let bytes = bincode::serialize(&address).unwrap();
+        assert_eq!(bytes, address.to_bytes());
+        assert_eq!(bincode::deserialize::<Address>(&bytes).unwrap(), address);
+
+        let mut unknown = bytes;
+        unknown[0] = 9;
+        assert!(bincode::deserialize::<Address>(&unknown).is_err());
+    }
+}
@@ -15,13 +15,20 @@ lazy_static::lazy_static! {
 
 pub type BlockHash = [u8; 32];
 
+/// First header version that commits to the post-block account state.
+pub const BLOCK_VERSION_STATE_ROOT: u32 = 2;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockHeader {
     pub version: u32,
+    /// Network the block belongs to (`ConsensusConfig::chain_id`)
+    pub chain_id: String,
     pub height: u64,
     pub timestamp: DateTime<Utc>,
     pub previous_hash: BlockHash,
     pub merkle_root: Hash,
+    /// Account-state root after applying the block (zero before version 2)
+    pub state_root: Hash,
     pub difficulty: u32,
     pub nonce: u64,
     pub miner_public_key: Vec<u8>,
@@ -36,6 +43,7 @@ pub struct Block {
 
 impl Block {
     pub fn new(
+        chain_id: &str,
         height: u64,
         previous_hash: BlockHash,
         transactions: Vec<Transaction>,
@@ -45,11 +53,13 @@ impl Block {
         let merkle_root = Self::calculate_merkle_root(&transactions);
         
         let header = BlockHeader {
-            version: 1,
+            version: BLOCK_VERSION_STATE_ROOT,
+            chain_id: chain_id.to_string(),
             height,
             timestamp: Utc::now(),
             previous_hash,
             merkle_root,
+            state_root: [0u8; 32],
             difficulty,
             nonce: 0,
             miner_public_key,
@@ -63,13 +73,9 @@ impl Block {
     }
     
     pub fn calculate_hash(&self, consensus: Option<&ConsensusConfig>) -> Result<BlockHash> {
-        let header_data = self.serialize_header_for_hashing()?;
-        if let Some(cfg) = consensus {
-            let salt = &blake3_hash(&header_data)[..16];
-            let pow_hash = argon2d_pow(&header_data, salt, &cfg.argon2_config)?;
-            Ok(blake3_hash_block(&pow_hash))
-        } else {
-            Ok(blake3_hash_block(&header_data))
+        match consensus {
+            Some(cfg) => self.header.pow_hash(cfg),
+            None => self.header.calculate_hash(),
         }
     }
     
@@ -134,6 +140,22 @@ impl Block {
     }
     
     pub fn validate(&self, previous_block: Option<&Block>, consensus: &crate::config::ConsensusConfig) -> Result<()> {
+        if self.header.version == 0 || self.header.version > BLOCK_VERSION_STATE_ROOT {
+            return Err(InvalidBlockError::UnsupportedVersion(self.header.version).into());
+        }
+        // Only genesis is trusted without a state root; every later block
+        // must commit to the accounts it produces.
+        if !self.is_genesis() && !self.header.commits_state_root() {
+            return Err(InvalidBlockError::UnsupportedVersion(self.header.version).into());
+        }
+        if self.header.chain_id != consensus.chain_id {
+            return Err(InvalidBlockError::WrongChain(self.header.chain_id.clone()).into());
+        }
+        // The state root itself is checked when the block is applied.
+        if !self.header.commits_state_root() && self.header.state_root != [0u8; 32] {
+            return Err(InvalidBlockError::StateRootMismatch.into());
+        }
+
         // Skip PoW check for genesis
         if !self.is_genesis() {
             let target = generate_difficulty_target(self.header.difficulty);
@@ -142,8 +164,9 @@ impl Block {
             }
         }
 
-        // Verify block signature
-        if !self.verify_signature()? {
+        // Verify block signature.  Genesis comes from the chain's genesis
+        // spec and is trusted by its hash, so it need not be signed.
+        if !self.is_genesis() && !self.verify_signature()? {
             return Err(InvalidBlockError::SignatureVerificationFailed.into());
         }
         
@@ -175,11 +198,11 @@ impl Block {
             if self.header.previous_hash != [0u8; 32] {
                 return Err(InvalidBlockError::GenesisBlockHashNotZero.into());
             }
-            // Additional genesis block validation
-            if self.transactions.len() != 1 {
+            // Additional genesis block validation: only the initial allocations
+            if self.transactions.is_empty() {
                 return Err(InvalidBlockError::GenesisBlockInvalidTransactionCount.into());
             }
-            if !matches!(self.transactions[0].kind, crate::transaction::TransactionType::MiningReward { .. }) {
+            if !self.transactions.iter().all(|tx| matches!(tx.kind, crate::transaction::TransactionType::MiningReward { .. })) {
                 return Err(InvalidBlockError::GenesisBlockTransactionNotReward.into());
             }
         }
@@ -189,10 +212,26 @@ impl Block {
             return Err(InvalidBlockError::InvalidMerkleRoot.into());
         }
         
-        // Verify transactions
+        // Transactions signed for another network are replays
+        if self.transactions.iter().any(|tx| tx.chain_id != self.header.chain_id) {
+            return Err(InvalidBlockError::InvalidTransaction("Transaction belongs to another network".to_string()).into());
+        }
+
+        // Time- and height-locked transactions must have unlocked
         for tx in &self.transactions {
-            if !tx.verify_signature()? {
-                return Err(InvalidBlockError::InvalidTransaction("Transaction signature verification failed".to_string()).into());
+            if let Some(lock) = tx.lock_until.filter(|_| !tx.is_unlocked_at(self.header.height, self.header.timestamp)) {
+                return Err(InvalidBlockError::InvalidTransaction(format!("Transaction locked until {lock}")).into());
+            }
+        }
+
+        // Verify transactions (genesis allocations are unsigned)
+        if !self.is_genesis() {
+            for tx in &self.transactions {
+                tx.validate_consensus()
+                    .map_err(|e| InvalidBlockError::InvalidTransaction(e.to_string()))?;
+                if !tx.verify_signature()? {
+                    return Err(InvalidBlockError::InvalidTransaction("Transaction signature verification failed".to_string()).into());
+                }
             }
         }
 
@@ -207,14 +246,16 @@ impl Block {
             .collect();
 
         if self.is_genesis() {
-            // Already ensured there is exactly one tx and it is a MiningReward.
-            let reward_tx = reward_txs[0];
-            if let TransactionType::MiningReward { block_height, amount } = reward_tx.kind {
-                if block_height != 0 {
-                    return Err(InvalidBlockError::InvalidBlockHeight.into());
-                }
-                if amount != consensus.initial_mining_reward {
-                    return Err(InvalidBlockError::InvalidRewardAmount.into());
+            // Already ensured every tx is a MiningReward; amounts are set by
+            // the genesis spec.
+            for reward_tx in &reward_txs {
+                if let TransactionType::MiningReward { block_height, amount } = reward_tx.kind {
+                    if block_height != 0 {
+                        return Err(InvalidBlockError::InvalidBlockHeight.into());
+                    }
+                    if amount == 0 {
+                        return Err(InvalidBlockError::InvalidRewardAmount.into());
+                    }
                 }
             }
         } else {
@@ -274,18 +315,7 @@ impl Block {
     }
     
     pub fn serialize_header_for_hashing(&self) -> Result<Vec<u8>> {
-        // Create header data without signature for hashing
-        let header_data = HeaderForHashing {
-            version: self.header.version,
-            height: self.header.height,
-            timestamp: self.header.timestamp,
-            previous_hash: self.header.previous_hash,
-            merkle_root: self.header.merkle_root,
-            difficulty: self.header.difficulty,
-            nonce: self.header.nonce,
-            miner_public_key: self.header.miner_public_key.clone(),
-        };
-        bincode::serialize(&header_data).map_err(|e| BlockchainError::SerializationError(e.to_string()))
+        self.header.serialize_for_hashing()
     }
 
     pub fn mine(&mut self, keypair: &crate::crypto::Dilithium3Keypair, consensus: &ConsensusConfig) -> Result<()> {
@@ -306,18 +336,7 @@ impl Block {
                 let mut block_header = self.header.clone();
                 block_header.nonce = nonce;
     
-                let header_data = HeaderForHashing {
-                    version: block_header.version,
-                    height: block_header.height,
-                    timestamp: block_header.timestamp,
-                    previous_hash: block_header.previous_hash,
-                    merkle_root: block_header.merkle_root,
-                    difficulty: block_header.difficulty,
-                    nonce: block_header.nonce,
-                    miner_public_key: block_header.miner_public_key.clone(),
-                };
-    
-                if let Ok(serialized_header) = bincode::serialize(&header_data) {
+                if let Ok(serialized_header) = block_header.serialize_for_hashing() {
                     if let Ok(hash) = self.calculate_hash_with_header(&serialized_header, consensus) {
                         if crate::blockchain::meets_target(&hash, &target) {
                             stop_flag.store(true, Ordering::Relaxed);
@@ -347,6 +366,7 @@ impl Block {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct HeaderForHashing {
     version: u32,
+    chain_id: String,
     height: u64,
     timestamp: DateTime<Utc>,
     previous_hash: BlockHash,
@@ -364,9 +384,29 @@ impl BlockHeader {
     }
 
     pub fn calculate_hash(&self) -> Result<BlockHash> {
-        // Create header data without signature for hashing
+        Ok(crate::crypto::blake3_hash_block(&self.serialize_for_hashing()?))
+    }
+
+    /// Argon2d proof-of-work hash of this header.  This is the value a child
+    /// block commits to in `previous_hash`.
+    pub fn pow_hash(&self, consensus: &ConsensusConfig) -> Result<BlockHash> {
+        let header_data = self.serialize_for_hashing()?;
+        let salt = &blake3_hash(&header_data)[..16];
+        let pow_hash = argon2d_pow(&header_data, salt, &consensus.argon2_config)?;
+        Ok(blake3_hash_block(&pow_hash))
+    }
+
+    /// Whether this header carries a `state_root` commitment.
+    pub fn commits_state_root(&self) -> bool {
+        self.version >= BLOCK_VERSION_STATE_ROOT
+    }
+
+    /// Header bytes covered by the PoW and the miner signature (no signature).
+    /// Version 1 headers hash exactly as before `state_root` existed.
+    pub fn serialize_for_hashing(&self) -> Result<Vec<u8>> {
         let header_data = HeaderForHashing {
             version: self.version,
+            chain_id: self.chain_id.clone(),
             height: self.height,
             timestamp: self.timestamp,
             previous_hash: self.previous_hash,
@@ -375,15 +415,19 @@ impl BlockHeader {
             nonce: self.nonce,
             miner_public_key: self.miner_public_key.clone(),
         };
-        let serialized = bincode::serialize(&header_data)
+        let mut bytes = bincode::serialize(&header_data)
             .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
-        Ok(crate::crypto::blake3_hash_block(&serialized))
+        if self.commits_state_root() {
+            bytes.extend_from_slice(&self.state_root);
+        }
+        Ok(bytes)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::address::Address;
     use crate::crypto::Dilithium3Keypair;
     use crate::transaction::TransactionType;
     
@@ -392,9 +436,10 @@ mod tests {
         let keypair = Dilithium3Keypair::new().unwrap();
         let transactions = vec![
             Transaction::new(
+                "test",
                 keypair.public_key.clone(),
                 TransactionType::Transfer {
-                    to: vec![1, 2, 3, 4],
+                    to: Address::from_public_key(&[1, 2, 3, 4]),
                     amount: 100,
                     memo: None,
                 },
@@ -403,6 +448,7 @@ mod tests {
         ];
         
         let block = Block::new(
+            "test",
             1,
             [0u8; 32],
             transactions,
@@ -421,18 +467,20 @@ mod tests {
         let keypair = Dilithium3Keypair::new().unwrap();
         let transactions = vec![
             Transaction::new(
+                "test",
                 keypair.public_key.clone(),
                 TransactionType::Transfer {
-                    to: vec![1, 2, 3, 4],
+                    to: Address::from_public_key(&[1, 2, 3, 4]),
                     amount: 100,
                     memo: None,
                 },
                 1,
             ),
             Transaction::new(
+                "test",
                 keypair.public_key.clone(),
                 TransactionType::Transfer {
-                    to: vec![5, 6, 7, 8],
+                    to: Address::from_public_key(&[5, 6, 7, 8]),
                     amount: 200,
                     memo: None,
                 },
@@ -443,6 +491,7 @@ mod tests {
         let merkle_root = Block::calculate_merkle_root(&transactions);
         assert_ne!(merkle_root, [0u8; 32]);
         let block = Block::new(
+            "test",
             1,
             [0u8; 32],
             transactions,
@@ -456,6 +505,7 @@ mod tests {
     fn test_block_signing() {
         let keypair = Dilithium3Keypair::new().unwrap();
         let mut block = Block::new(
+            "test",
             1,
             [0u8; 32],
             vec![],
@@ -477,6 +527,7 @@ mod tests {
         let consensus = ConsensusConfig::default();
 
         let mut reward_tx = Transaction::new(
+            &consensus.chain_id,
             keypair.public_key.clone(),
             TransactionType::MiningReward {
                 block_height: 0,
@@ -487,6 +538,7 @@ mod tests {
         reward_tx.sign(&keypair).unwrap();
 
         let mut block = Block::new(
+            &consensus.chain_id,
             0,
             [0u8; 32],
             vec![reward_tx],
@@ -502,4 +554,42 @@ mod tests {
         assert!(block.validate(None, &consensus).is_ok());
         let _ = block.calculate_hash(None).unwrap();
     }
+
+    #[test]
+    fn test_state_root_is_hashed_from_version_2() {
+        let mut block = Block::new("test", 1, [0u8; 32], vec![], 2, vec![1, 2, 3]);
+        let before = block.calculate_hash(None).unwrap();
+        block.header.state_root = [7u8; 32];
+        assert_ne!(block.calculate_hash(None).unwrap(), before);
+
+        // Version 1 headers hash as they did before the field existed.
+        block.header.version = 1;
+        let v1 = block.header.serialize_for_hashing().unwrap();
+        block.header.state_root = [0u8; 32];
+        assert_eq!(block.header.serialize_for_hashing().unwrap(), v1);
+    }
+
+    #[test]
+    fn test_blocks_after_genesis_need_a_state_root() {
+        let consensus = crate::config::ConsensusConfig::default();
+        let mut block = Block::new(&consensus.chain_id, 1, [0u8; 32], vec![], 1, vec![1, 2, 3]);
+        block.header.version = 1;
+        assert!(matches!(
+            block.validate(None, &consensus),
+            Err(BlockchainError::InvalidBlock(InvalidBlockError::UnsupportedVersion(1)))
+        ));
+    }
+
+    #[test]
+    fn test_block_cannot_carry_an_unknown_address_version() {
+        let keypair = Dilithium3Keypair::new().unwrap();
+        let to = Address::from_public_key(&[1, 2, 3, 4]);
+        let tx = Transaction::new("test", keypair.public_key.clone(), TransactionType::Transfer { to, amount: 100, memo: None }, 1);
+        let block = Block::new("test", 1, [0u8; 32], vec![tx], 2, keypair.public_key.clone());
+
+        let mut bytes = bincode::serialize(&block).unwrap();
+        let at = bytes.windows(to.to_bytes().len()).position(|w| w == to.to_bytes().as_slice()).unwrap();
+        bytes[at] = 9;
+        assert!(bincode::deserialize::<Block>(&bytes).is_err());
+    }
 }
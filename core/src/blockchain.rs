@@ -6,29 +6,32 @@
 
 #![allow(clippy::result_large_err)]
 
+use std::collections::{BTreeSet, HashSet, VecDeque};
 use std::sync::Arc;
 
-use bs58;
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
+use libp2p::PeerId;
 use crate::RwLock;
 
 use serde::{Deserialize, Serialize};
-use tempfile::NamedTempFile;
-use std::io::Write;
 use std::collections::BTreeMap;
-use bincode;
-use tokio::fs;
 
 use crate::{
+    address::{Address, Network},
     block::{Block, BlockHash, BlockHeader},
     config::ConsensusConfig,
-    crypto::{blake3_hash, Dilithium3Keypair},
+    crypto::{generate_difficulty_target, Dilithium3Keypair},
     error::{BlockchainError, InvalidBlockError},
+    fee_estimator::FeeEstimate,
+    genesis::GenesisSpec,
     mempool::{MempoolStats, TransactionMempool, ValidationResult},
     miner::WalletManager,
-    storage::BlockchainStorage,
-    transaction::{Transaction, TransactionType},
+    orphan_pool::OrphanPool,
+    state_overlay::{self, StateOverlay},
+    state_tree::{StateProof, StateTree},
+    storage::{AddressHistoryEntry, BlockchainStorage, StorageTransaction, TxDirection},
+    transaction::{Transaction, TransactionId, TransactionType},
     Result,
 };
 
@@ -51,7 +54,7 @@ pub fn meets_target(hash: &[u8; 32], target: &[u8; 32]) -> bool {
 pub struct AccountState {
     pub balance: u64,
     pub nonce: u64,
-    // kept for future extensions
+    // statistics, updated by every transaction touching the account
     pub transaction_count: u64,
     pub total_received: u64,
     pub total_sent: u64,
@@ -89,12 +92,128 @@ pub struct SecurityCheckpoint {
     pub state_root: [u8; 32],
 }
 
+/// Account states touched by a main-chain block, as they were before the
+/// block was applied (`None` = account did not exist yet).
+pub type BlockUndo = Vec<(Vec<u8>, Option<AccountState>)>;
+
+/// Every account at a checkpoint, sorted by public key.
+pub type AccountSnapshot = Vec<(Vec<u8>, AccountState)>;
+
+/// Live account states, keyed like the state tree.
+type AccountMap = DashMap<Vec<u8>, AccountState>;
+
+/// Number of checkpoint snapshots kept in storage; older ones are deleted.
+const SNAPSHOTS_KEPT: u64 = 2;
+
+/// The storage side of connecting or disconnecting one main-chain block,
+/// captured right after the in-memory change so that several (a whole
+/// reorganization) can be committed in one transaction.
+enum ChainWrite {
+    Connect {
+        block: Block,
+        accounts: Vec<(Vec<u8>, Option<AccountState>)>, // touched accounts after the block
+        undo: Option<BlockUndo>,                        // none for genesis
+        expired_undo: Option<u64>,
+        checkpoint: Option<(SecurityCheckpoint, AccountSnapshot)>,
+        expired_snapshot: Option<u64>,
+        state: ChainState,
+    },
+    Disconnect {
+        block: Block,
+        undo: BlockUndo,
+        state: ChainState,
+    },
+}
+
+impl ChainWrite {
+    fn apply(&self, tx: &mut StorageTransaction) -> Result<()> {
+        match self {
+            ChainWrite::Connect { block, accounts, undo, expired_undo, checkpoint, expired_snapshot, state } => {
+                let height = block.header.height;
+                tx.save_block(block)?;
+                for t in &block.transactions {
+                    tx.save_transaction(&t.id, t)?;
+                }
+                for (address, entry) in &address_history(block) {
+                    tx.save_address_entry(address, entry)?;
+                }
+                for (key, account) in accounts {
+                    match account {
+                        Some(account) => tx.save_account(key, account)?,
+                        None => tx.delete_account(key)?,
+                    }
+                }
+                if let Some(undo) = undo {
+                    tx.save_undo(height, undo)?;
+                }
+                if let Some(expired) = expired_undo {
+                    tx.delete_undo(*expired)?;
+                }
+                if let Some((checkpoint, snapshot)) = checkpoint {
+                    tx.save_checkpoint(checkpoint)?;
+                    tx.save_snapshot(height, snapshot)?;
+                    if let Some(expired) = expired_snapshot {
+                        tx.delete_snapshot(*expired)?;
+                    }
+                }
+                tx.save_chain_state(state)
+            }
+            // Remove the block and restore the accounts it touched.
+            ChainWrite::Disconnect { block, undo, state } => {
+                let height = block.header.height;
+                tx.delete_block(height)?;
+                for t in &block.transactions {
+                    tx.delete_transaction(&t.id)?;
+                }
+                for (address, entry) in &address_history(block) {
+                    tx.delete_address_entry(address, entry)?;
+                }
+                for (key, previous) in undo {
+                    match previous {
+                        Some(account) => tx.save_account(key, account)?,
+                        None => tx.delete_account(key)?,
+                    }
+                }
+                tx.delete_undo(height)?;
+                // A checkpoint on a replaced block no longer describes our chain.
+                tx.delete_checkpoint(height)?;
+                tx.delete_snapshot(height)?;
+                tx.save_chain_state(state)
+            }
+        }
+    }
+}
+
+/// An account's state at a main-chain block, with a proof against that
+/// block's state root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountProof {
+    pub height: u64,
+    pub block_hash: BlockHash,
+    pub state_root: [u8; 32],
+    pub account: Option<AccountState>,
+    pub proof: StateProof,
+}
+
+/// A valid block that is not (or no longer) on the main chain.
+#[derive(Debug, Clone)]
+struct SideBlock {
+    block: Block,
+    cumulative_difficulty: u128,
+}
+
 /* --------------------------------------------------------------------------
                                  Blockchain
    ------------------------------------------------------------------------*/
 pub struct NumiBlockchain {
     blocks: Arc<RwLock<Vec<Block>>>,
-    accounts: DashMap<Vec<u8>, AccountState>,
+    block_index: DashMap<BlockHash, u64>, // PoW hash → height
+    tx_index: DashMap<TransactionId, u64>, // main-chain tx id → block height
+    undo_log: RwLock<VecDeque<BlockUndo>>, // one entry per recent main-chain block, tip last
+    side_blocks: DashMap<BlockHash, SideBlock>, // competing branches
+    orphans: RwLock<OrphanPool>, // blocks waiting for their parent
+    accounts: AccountMap,
+    state_tree: RwLock<StateTree>, // authenticated view of `accounts`
     mempool: Arc<TransactionMempool>,
     state: Arc<RwLock<ChainState>>,
     miner_keypair: Dilithium3Keypair,
@@ -105,135 +224,93 @@ pub struct NumiBlockchain {
 impl NumiBlockchain {
     /* ------------------- construction helpers ----------------------- */
     fn build(kp: Dilithium3Keypair, consensus: ConsensusConfig, storage: Option<Arc<BlockchainStorage>>) -> Result<Self> {
-        // Placeholder to wire mempool <-> blockchain without cycles
-        let placeholder = Self {
+        let chain = Self {
             blocks: Arc::new(RwLock::new(Vec::new())),
+            block_index: DashMap::new(),
+            tx_index: DashMap::new(),
+            undo_log: RwLock::new(VecDeque::new()),
+            side_blocks: DashMap::new(),
+            orphans: RwLock::new(OrphanPool::default()),
             accounts: DashMap::new(),
-            mempool: Arc::new(TransactionMempool::new()),
+            state_tree: RwLock::new(StateTree::new()),
+            mempool: Arc::new(TransactionMempool::with_config(consensus.clone())),
             state: Arc::new(RwLock::new(ChainState::default())),
-            miner_keypair: kp.clone(),
-            storage: storage.clone(),
-            consensus: consensus.clone(),
+            miner_keypair: kp,
+            storage,
+            consensus,
         };
-        let chain_arc = Arc::new(RwLock::new(placeholder));
-
-        // link mempool to chain
-        {
-            let mut mp = TransactionMempool::new();
-            mp.attach_chain(&chain_arc);
-            chain_arc.write().mempool = Arc::new(mp);
-        }
 
         // create & apply genesis
-        {
-            let chain_guard = chain_arc.write();
-            let genesis = chain_guard.create_genesis_block()?;
-            chain_guard.apply_block(&genesis)?;
-            chain_guard.blocks.write().push(genesis.clone());
-            
-            // CRITICAL FIX: Update chain state for genesis block
-            {
-                let mut st = chain_guard.state.write();
-                st.total_blocks = 1; // Genesis is block 1
-                st.best_block_hash = genesis.calculate_hash(Some(&chain_guard.consensus))?;
-                st.cumulative_difficulty = genesis.header.difficulty as u128;
-                
-                // Add genesis mining reward to total supply
-                if let Some(reward) = genesis.transactions.iter().find_map(|tx| {
-                    if let TransactionType::MiningReward { amount, .. } = tx.kind {
-                        Some(amount)
-                    } else { None }
-                }) {
-                    st.total_supply = reward;
-                }
-                
-                // Set initial difficulty
-                st.current_difficulty = genesis.header.difficulty;
-            }
-        }
-
-        Arc::try_unwrap(chain_arc)
-            .map(|rw| rw.into_inner())
-            .map_err(|_| BlockchainError::ConsensusError("Failed to unwrap Arc during blockchain initialization".into()))
-    }
-
-    pub fn new(consensus: ConsensusConfig) -> Result<Self> {
-        let kp = WalletManager::load_or_create_miner_wallet(&std::path::PathBuf::from("./core-data"))?;
-        Self::build(kp, consensus, None)
+        let genesis = chain.create_genesis_block()?;
+        chain.init_genesis(&genesis)?;
+        Ok(chain)
     }
 
+    /// Chain mining with `kp`; a development genesis pays that key.
     pub fn new_with_keypair(kp: Dilithium3Keypair, consensus: ConsensusConfig) -> Result<Self> {
         Self::build(kp, consensus, None)
     }
 
-    pub fn new_with_config(cfg: ConsensusConfig, kp: Option<Dilithium3Keypair>, storage: Option<Arc<BlockchainStorage>>) -> Result<Self> {
-        let keypair = kp.unwrap_or(WalletManager::load_or_create_miner_wallet(&std::path::PathBuf::from("./core-data"))?);
-        Self::build(keypair, cfg, storage)
+    pub fn new_with_config(cfg: ConsensusConfig, kp: Dilithium3Keypair, storage: Option<Arc<BlockchainStorage>>) -> Result<Self> {
+        Self::build(kp, cfg, storage)
     }
 
-    pub async fn load_from_storage(storage: &Arc<BlockchainStorage>, consensus: ConsensusConfig) -> Result<Self> {
-        let dir = storage.blocks_dir();
-
-        if !dir.exists() {
-            // No prior data – start fresh, ensuring consensus config is passed.
-            return Self::new_with_config(consensus, None, Some(storage.clone()));
+    /// Chain committed to `storage`, or a new one if there is none.  `kp` is
+    /// the node's miner key (from its wallet), which a new development
+    /// genesis pays.
+    pub async fn load_from_storage(storage: &Arc<BlockchainStorage>, consensus: ConsensusConfig, kp: Dilithium3Keypair) -> Result<Self> {
+        if let Some(state) = storage.load_chain_state()? {
+            return Self::restore(storage, consensus, state, kp).await;
         }
 
-        // Build a map height → path so we replay in numeric order
-        let mut file_map: BTreeMap<u64, std::path::PathBuf> = BTreeMap::new();
-        let mut read_dir = fs::read_dir(&dir).await?;
-        while let Some(entry) = read_dir.next_entry().await? {
-            if !entry.file_type().await?.is_file() { continue; }
-            let fname = entry.file_name().into_string().unwrap_or_default();
-            if !fname.starts_with("block_") { continue; }
-            if let Ok(height) = fname.trim_start_matches("block_").trim_end_matches(".bin").parse::<u64>() {
-                file_map.insert(height, entry.path());
-            }
-        }
-        
-        if file_map.is_empty() || !file_map.contains_key(&0) {
+        // Nodes from before sled persistence kept one file per block; import
+        // those once by replaying them, after which sled is authoritative.
+        let dir = storage.blocks_dir();
+        let mut file_map = crate::legacy::block_files(&dir)?;
+        if !file_map.contains_key(&0) {
             // If no blocks or no genesis block, start fresh.
-            return Self::new_with_config(consensus, None, Some(storage.clone()));
+            return Self::new_with_config(consensus, kp, Some(storage.clone()));
         }
 
+        log::info!("Importing {} legacy block files from {}", file_map.len(), dir.display());
+
+        // Files in the original layout (no chain id or state root) cannot be
+        // replayed; their balances move to a new genesis instead.
+        let read_block = |path: &std::path::Path| -> Result<Block> {
+            let data = std::fs::read(path)?;
+            crate::legacy::decode_exact::<Block>(&data).map_err(|e| {
+                if crate::legacy::decode_exact::<crate::legacy::LegacyBlock>(&data).is_ok() {
+                    BlockchainError::StorageError(format!(
+                        "{} holds blocks in the pre-chain-id layout; run `numi-core migrate-legacy --blocks {}` \
+                         to carry their balances into a genesis file, then start from that genesis",
+                        dir.display(),
+                        dir.display()
+                    ))
+                } else {
+                    e
+                }
+            })
+        };
 
         // Load genesis block first to correctly initialize the chain state
-        let genesis_path = file_map.remove(&0).unwrap(); // Already checked with contains_key
-        let genesis_data = fs::read(genesis_path).await?;
-        let genesis_block: Block = bincode::deserialize(&genesis_data)
-            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
-
-        // Manually build the chain with the loaded genesis block
-        let keypair = WalletManager::load_or_create_miner_wallet(&std::path::PathBuf::from("./core-data"))?;
-        let chain = Self::build(keypair, consensus, Some(storage.clone()))?;
-        
-        // This is a bit of a hack. `build` creates its own genesis. We need to replace it.
+        let genesis_block = read_block(&file_map.remove(&0).unwrap())?; // Already checked with contains_key
+
+        // Build without storage so the placeholder genesis never reaches disk,
+        // then swap in the imported one.
+        let mut chain = Self::build(kp, consensus, None)?;
+        chain.check_stored_genesis(&genesis_block.calculate_hash(Some(&chain.consensus))?)?;
         chain.blocks.write().clear();
+        chain.block_index.clear();
+        chain.tx_index.clear();
         chain.accounts.clear();
-        chain.state.write().total_blocks = 0;
-
-        chain.apply_block(&genesis_block)?;
-        chain.blocks.write().push(genesis_block.clone());
-        
-        {
-            let mut st = chain.state.write();
-            st.total_blocks = 1;
-            st.best_block_hash = genesis_block.calculate_hash(Some(&chain.consensus))?;
-            st.cumulative_difficulty = genesis_block.header.difficulty as u128;
-            if let Some(reward) = genesis_block.transactions.iter().find_map(|tx| {
-                if let TransactionType::MiningReward { amount, .. } = tx.kind { Some(amount) } else { None }
-            }) {
-                st.total_supply = reward;
-            }
-            st.current_difficulty = genesis_block.header.difficulty;
-        }
-
+        *chain.state_tree.write() = StateTree::new();
+        *chain.state.write() = ChainState::default();
+        chain.storage = Some(storage.clone());
+        chain.init_genesis(&genesis_block)?;
 
         for (_height, path) in file_map {
-            let data = fs::read(path).await?;
-            let block: Block = bincode::deserialize(&data)
-                .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
-            
+            let block = read_block(&path)?;
+
             // Perform a full validation of the block against the current chain state
             let consensus = &chain.consensus;
             block.validate(chain.blocks.read().last(), consensus)?;
@@ -243,6 +320,159 @@ impl NumiBlockchain {
         Ok(chain)
     }
 
+    /// Rebuild the accounts from the newest checkpoint snapshot that
+    /// belongs to this chain and matches the state root committed by the
+    /// checkpoint block's header, replaying the blocks above it into a fresh
+    /// map.  Returns the checkpoint height and the accounts, or `None` if no
+    /// usable snapshot exists.
+    fn restore_accounts_from_snapshot(
+        storage: &BlockchainStorage,
+        blocks: &[Block],
+        hashes: &[BlockHash],
+    ) -> Result<Option<(u64, AccountMap)>> {
+        for checkpoint in storage.load_checkpoints()?.into_iter().rev() {
+            let height = checkpoint.block_height;
+            if hashes.get(height as usize) != Some(&checkpoint.block_hash) {
+                continue;
+            }
+            let Some(snapshot) = storage.load_snapshot(height)? else { continue };
+            let header = &blocks[height as usize].header;
+            if header.commits_state_root() && state_root(&snapshot) != header.state_root {
+                log::warn!("Snapshot at checkpoint #{} does not match its block's state root; ignoring it", height);
+                continue;
+            }
+
+            let accounts: AccountMap = snapshot.into_iter().collect();
+            // Replayed blocks were fully validated when first connected.
+            for block in &blocks[height as usize + 1..] {
+                apply_transactions(&accounts, &block.transactions)?;
+            }
+            return Ok(Some((height, accounts)));
+        }
+        Ok(None)
+    }
+
+    /// Refuse stored data that starts from a different genesis than the one
+    /// this node is configured for.  A development genesis minted from the
+    /// local key is not enforced.
+    fn check_stored_genesis(&self, stored: &BlockHash) -> Result<()> {
+        if GenesisSpec::configured(&self.consensus)?.is_none() && self.consensus.genesis_hash.is_none() {
+            return Ok(());
+        }
+        let expected = self.genesis_hash();
+        if *stored != expected {
+            return Err(BlockchainError::ConsensusError(format!(
+                "Stored chain has genesis {} but this node expects {}",
+                hex::encode(stored),
+                hex::encode(expected)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rebuild the in-memory chain from the blocks, accounts and chain state
+    /// committed to `storage`, without re-validating history.
+    async fn restore(storage: &Arc<BlockchainStorage>, consensus: ConsensusConfig, mut state: ChainState, kp: Dilithium3Keypair) -> Result<Self> {
+        let blocks = storage.load_blocks()?;
+        if blocks.is_empty()
+            || blocks.len() as u64 != state.total_blocks
+            || blocks.iter().enumerate().any(|(i, b)| b.header.height != i as u64)
+        {
+            return Err(BlockchainError::StorageError(format!(
+                "Stored chain state expects {} blocks but storage holds {} (or has gaps)",
+                state.total_blocks,
+                blocks.len()
+            )));
+        }
+
+        // The placeholder genesis built here is replaced below.
+        let mut chain = Self::build(kp, consensus, None)?;
+        chain.block_index.clear();
+        chain.tx_index.clear();
+        chain.accounts.clear();
+
+        // A block's hash is stored as its child's `previous_hash`; the tip's
+        // is in the chain state.  No PoW needs to be recomputed.
+        let hashes: Vec<BlockHash> = (0..blocks.len())
+            .map(|i| blocks.get(i + 1).map_or(state.best_block_hash, |child| child.header.previous_hash))
+            .collect();
+        chain.check_stored_genesis(&hashes[0])?;
+        for (height, hash) in hashes.iter().enumerate() {
+            chain.block_index.insert(*hash, height as u64);
+        }
+        for block in &blocks {
+            chain.index_transactions(block);
+        }
+
+        // Prefer the verified checkpoint snapshot; chains younger than the
+        // first checkpoint fall back to the per-block account records.
+        let accounts = match Self::restore_accounts_from_snapshot(storage, &blocks, &hashes)? {
+            Some((height, accounts)) => {
+                log::info!(
+                    "Restored accounts from checkpoint #{} and replayed {} block(s)",
+                    height,
+                    blocks.len() as u64 - height - 1
+                );
+                accounts
+            }
+            None => storage.load_accounts()?.into_iter().collect(),
+        };
+        // Whichever source was used must reproduce the tip's state root.
+        let snapshot = account_snapshot(&accounts);
+        let tip_header = &blocks[blocks.len() - 1].header;
+        if tip_header.commits_state_root() && state_root(&snapshot) != tip_header.state_root {
+            return Err(BlockchainError::StorageError(format!(
+                "Stored accounts do not match the state root of block #{}",
+                tip_header.height
+            )));
+        }
+        chain.accounts = accounts;
+        *chain.state_tree.write() =
+            StateTree::from_accounts(snapshot.iter().map(|(k, a)| (k.as_slice(), a)));
+
+        // Reload undo records for the reorg window (never genesis), keeping
+        // only the run that ends at the tip.
+        let tip = state.total_blocks.saturating_sub(1);
+        let from = tip.saturating_sub(chain.reorg_limit()).saturating_add(1).max(1);
+        let mut undo_log = VecDeque::new();
+        let mut expected = tip;
+        for (height, undo) in storage.load_undo_since(from)?.into_iter().rev() {
+            if height != expected {
+                break;
+            }
+            undo_log.push_front(undo);
+            expected = expected.saturating_sub(1);
+        }
+        *chain.undo_log.write() = undo_log;
+
+        // An index turned on (or back on) after blocks were committed
+        // without it is rebuilt from the whole chain.
+        if storage.address_index_enabled() && !storage.address_index_built()? {
+            log::info!("Building address index for {} blocks", blocks.len());
+            let entries: Vec<_> = blocks.iter().flat_map(address_history).collect();
+            storage.rebuild_address_index(&entries)?;
+        }
+
+        // Recomputed rather than trusted: older nodes stored the linear sum of
+        // difficulties as the chain's work.
+        state.cumulative_difficulty =
+            blocks.iter().fold(0u128, |work, b| work.saturating_add(block_work(b.header.difficulty)));
+        state.current_difficulty = chain_difficulty(&blocks, &chain.consensus);
+
+        *chain.blocks.write() = blocks;
+        *chain.state.write() = state;
+        chain.storage = Some(storage.clone());
+        let keys: Vec<Vec<u8>> = chain.accounts.iter().map(|e| e.key().clone()).collect();
+        chain.mempool.sync_accounts_from_chain(&chain.accounts, &keys, tip);
+
+        log::info!(
+            "Loaded chain at height {} with {} accounts from storage",
+            tip,
+            chain.accounts.len()
+        );
+        Ok(chain)
+    }
+
     /* ------------------- public accessor API ------------------------ */
     pub fn get_current_height(&self) -> u64 {
         self.state.read().total_blocks.saturating_sub(1)
@@ -250,6 +480,14 @@ impl NumiBlockchain {
     pub fn get_latest_block_hash(&self) -> BlockHash {
         self.state.read().best_block_hash
     }
+    /// Hash of the block this chain starts from.
+    pub fn genesis_hash(&self) -> BlockHash {
+        let blocks = self.blocks.read();
+        match blocks.get(1) {
+            Some(child) => child.header.previous_hash,
+            None => self.get_latest_block_hash(),
+        }
+    }
     pub fn get_current_difficulty(&self) -> u32 {
         self.state.read().current_difficulty
     }
@@ -259,8 +497,12 @@ impl NumiBlockchain {
     pub fn get_mempool_stats(&self) -> MempoolStats {
         self.mempool.stats()
     }
+
+    pub fn estimate_fee(&self, target_blocks: u32) -> FeeEstimate {
+        self.mempool.estimate_fee(target_blocks)
+    }
     pub fn get_transactions_for_block(&self, max_size: usize, max_count: usize) -> Vec<Transaction> {
-        self.mempool.select_for_block(max_size, max_count)
+        self.mempool.select_for_block(&self.accounts, max_size, max_count)
     }
     pub fn get_pending_transaction_count(&self) -> usize {
         self.mempool.stats().total_transactions
@@ -274,13 +516,44 @@ impl NumiBlockchain {
         self.blocks.read().get(height as usize).cloned()
     }
     pub fn get_block_by_hash(&self, hash: &BlockHash) -> Option<Block> {
-        self.blocks
-            .read()
-            .iter()
-            .find(|b| b.calculate_hash(Some(&self.consensus)).ok().map_or(false, |h| &h == hash))
-            .cloned()
+        let height = *self.block_index.get(hash)?;
+        self.get_block_by_height(height)
+    }
+    /// Height of the main-chain block with the given PoW hash, if any.
+    pub fn get_block_height(&self, hash: &BlockHash) -> Option<u64> {
+        self.block_index.get(hash).map(|h| *h)
+    }
+    /// PoW hash of the main-chain block at `height`.
+    pub fn get_block_hash(&self, height: u64) -> Option<BlockHash> {
+        let blocks = self.blocks.read();
+        if height as usize + 1 < blocks.len() {
+            Some(blocks[height as usize + 1].header.previous_hash)
+        } else if height as usize + 1 == blocks.len() {
+            Some(self.get_latest_block_hash())
+        } else {
+            None
+        }
+    }
+    /// Page of an account's transaction history, newest first.  Needs
+    /// storage with the address index enabled.
+    pub fn address_history(&self, address: &Address, offset: usize, limit: usize) -> Result<Vec<AddressHistoryEntry>> {
+        match &self.storage {
+            Some(storage) if storage.address_index_enabled() => {
+                storage.address_history(&address.to_bytes(), offset, limit)
+            }
+            _ => Err(BlockchainError::InvalidArgument("Address index is not enabled on this node".into())),
+        }
     }
-    /// Return up to `count` headers starting after `start_hash` (empty = genesis)
+    /// A main-chain transaction and the height of the block holding it.
+    pub fn find_transaction(&self, id: &TransactionId) -> Option<(Transaction, u64)> {
+        let height = *self.tx_index.get(id)?;
+        let block = self.get_block_by_height(height)?;
+        let tx = block.transactions.into_iter().find(|tx| tx.id == *id)?;
+        Some((tx, height))
+    }
+    /// Return up to `count` main-chain headers following the block with PoW
+    /// hash `start_hash`, which is itself excluded.  An empty `start_hash`
+    /// starts at genesis (included); an unknown one yields no headers.
     pub fn get_block_headers(&self, start_hash: Vec<u8>, count: u32) -> Vec<BlockHeader> {
         let blocks = self.blocks.read();
         let mut headers = Vec::new();
@@ -290,9 +563,8 @@ impl NumiBlockchain {
         } else if start_hash.len() == 32 {
             let mut arr = [0u8; 32];
             arr.copy_from_slice(&start_hash);
-            blocks
-                .iter()
-                .position(|b| b.calculate_hash(None).ok().filter(|h| h == &arr).is_some())
+            self.get_block_height(&arr)
+                .map(|h| h as usize + 1)
                 .unwrap_or(blocks.len())
         } else {
             blocks.len()
@@ -301,26 +573,72 @@ impl NumiBlockchain {
         blocks.iter().skip(start_index).take(count as usize).for_each(|b| headers.push(b.header.clone()));
         headers
     }
-    pub fn get_balance_by_pubkey(&self, pk: &[u8]) -> u64 {
-        self.accounts.get(pk).map(|a| a.balance).unwrap_or(0)
+    /// Main-chain headers the difficulty retarget after the block `hash`
+    /// looks at, oldest first and ending with that block's header.  Empty if
+    /// `hash` is not on the main chain.
+    pub fn retarget_headers(&self, hash: &BlockHash) -> Vec<BlockHeader> {
+        let Some(height) = self.get_block_height(hash) else {
+            return Vec::new();
+        };
+        let blocks = self.blocks.read();
+        let from = (height as usize + 1).saturating_sub(retarget_window(&self.consensus) + 1);
+        blocks[from..=height as usize].iter().map(|b| b.header.clone()).collect()
     }
-    pub fn get_account_state_by_address(&self, address: &str) -> Option<AccountState> {
-        if !Self::is_valid_address(address) {
-            return None;
-        }
-        for entry in self.accounts.iter() {
-            if self.derive_address(entry.key()) == address {
-                return Some(entry.value().clone());
+    /// Main-chain hashes from the tip back to genesis, dense near the tip and
+    /// exponentially sparser further down.  Lets a peer on another branch
+    /// find the most recent block we have in common.
+    pub fn block_locator(&self) -> Vec<BlockHash> {
+        let blocks = self.blocks.read();
+        let tip = blocks.len().saturating_sub(1);
+        let best = self.get_latest_block_hash();
+        // A block's hash is stored as its child's `previous_hash`.
+        let hash_at = |h: usize| if h == tip { best } else { blocks[h + 1].header.previous_hash };
+
+        let mut locator = Vec::new();
+        let mut height = tip;
+        let mut step = 1;
+        loop {
+            locator.push(hash_at(height));
+            if height == 0 {
+                break;
             }
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            height = height.saturating_sub(step);
         }
-        None
+        locator
+    }
+    /// Network this chain's addresses are written for.
+    pub fn network(&self) -> Network {
+        Network::from_chain_id(&self.consensus.chain_id)
+    }
+    /// Account named by `text`: an address of this network or a hex public
+    /// key.
+    pub fn parse_account(&self, text: &str) -> Option<Address> {
+        Address::parse(text, self.network())
+            .ok()
+            .or_else(|| hex::decode(text).ok().filter(|pk| !pk.is_empty()).map(|pk| Address::from_public_key(&pk)))
+    }
+    pub fn get_balance(&self, address: &Address) -> u64 {
+        self.get_account_state(address).map(|a| a.balance).unwrap_or(0)
+    }
+    /// Balance of the account a transaction from `pk` would spend.
+    pub fn get_balance_by_pubkey(&self, pk: &[u8]) -> u64 {
+        self.get_balance(&Address::from_sender(pk))
+    }
+    pub fn get_account_state(&self, address: &Address) -> Option<AccountState> {
+        self.accounts.get(&address.to_bytes()).map(|a| a.clone())
+    }
+    pub fn get_account_state_by_address(&self, address: &str) -> Option<AccountState> {
+        self.get_account_state(&self.parse_account(address)?)
     }
     pub fn get_address_from_public_key(&self, pk: &[u8]) -> String {
-        self.derive_address(pk)
+        Address::from_public_key(pk).encode(self.network())
     }
     pub fn get_account_state_or_default(&self, pk: &[u8]) -> AccountState {
         self.accounts
-            .get(pk)
+            .get(&Address::from_sender(pk).to_bytes())
             .map(|r| r.value().clone())
             .unwrap_or_else(|| AccountState {
                 balance: 0,
@@ -332,6 +650,113 @@ impl NumiBlockchain {
                 last_activity: Utc::now(),
             })
     }
+    /// State root the accounts would have after applying `transactions` on
+    /// top of the current tip.  Block producers put this in the header.
+    pub fn state_root_after(&self, transactions: &[Transaction]) -> Result<[u8; 32]> {
+        let mut overlay = StateOverlay::new(&self.accounts);
+        overlay.apply_all(transactions)?;
+
+        // Swap the new states into the tree just long enough to hash it.
+        let mut tree = self.state_tree.write();
+        let root = root_with_overlay(&mut tree, &overlay);
+        for (key, _) in overlay.changes() {
+            tree.update(key, self.accounts.get(key).as_deref());
+        }
+        Ok(root)
+    }
+
+    /// Unmined block on the tip paying `kp` the subsidy and fees, carrying up
+    /// to `max_count` mempool transactions in `max_size` bytes and committing
+    /// to the state they produce.  Should the chain refuse the selection the
+    /// template carries the reward alone, so miners never spin on a template
+    /// that cannot be applied.
+    pub fn block_template(&self, kp: &Dilithium3Keypair, max_size: usize, max_count: usize) -> Result<Block> {
+        let transactions = self.get_transactions_for_block(max_size, max_count);
+        self.template_with(kp, transactions).or_else(|e| {
+            log::warn!("Block template rejected ({e}); building one without mempool transactions");
+            self.template_with(kp, Vec::new())
+        })
+    }
+
+    fn template_with(&self, kp: &Dilithium3Keypair, transactions: Vec<Transaction>) -> Result<Block> {
+        let height = self.get_current_height() + 1;
+        let fees = transactions.iter().map(|tx| tx.fee).fold(0, u64::saturating_add);
+        let amount = WalletManager::calculate_mining_reward_with_config(height, &self.consensus).saturating_add(fees);
+        let mut reward = Transaction::new(
+            &self.consensus.chain_id,
+            kp.public_key.clone(),
+            TransactionType::MiningReward { block_height: height, amount },
+            0,
+        );
+        reward.sign(kp)?;
+
+        // The reward must come first.
+        let mut all = Vec::with_capacity(1 + transactions.len());
+        all.push(reward);
+        all.extend(transactions);
+        let mut block = Block::new(
+            &self.consensus.chain_id,
+            height,
+            self.get_latest_block_hash(),
+            all,
+            self.get_current_difficulty(),
+            kp.public_key.clone(),
+        );
+        block.header.state_root = self.state_root_after(&block.transactions)?;
+        Ok(block)
+    }
+
+    /// State of `address`'s account at main-chain `height` (default: the
+    /// tip) with a proof against that block's state root.  Heights are
+    /// available as far back as undo records are kept.
+    pub fn account_proof(&self, address: &Address, height: Option<u64>) -> Result<AccountProof> {
+        let key = address.to_bytes();
+        let blocks = self.blocks.read();
+        let tip = blocks.len().saturating_sub(1) as u64;
+        let height = height.unwrap_or(tip);
+        if height > tip {
+            return Err(BlockchainError::BlockNotFound(format!("height {height}")));
+        }
+        let block_hash = if height == tip {
+            self.get_latest_block_hash()
+        } else {
+            blocks[height as usize + 1].header.previous_hash
+        };
+
+        let undo_log = self.undo_log.read();
+        let depth = (tip - height) as usize;
+        if depth > undo_log.len() {
+            return Err(BlockchainError::InvalidArgument(format!(
+                "State at height {height} is no longer available; oldest is {}",
+                tip - undo_log.len() as u64
+            )));
+        }
+        // Undo records go from the tip down, so the last write per account
+        // is its state at `height`.
+        let mut rolled_back: BTreeMap<Vec<u8>, Option<AccountState>> = BTreeMap::new();
+        for undo in undo_log.iter().rev().take(depth) {
+            for (key, previous) in undo {
+                rolled_back.insert(key.clone(), previous.clone());
+            }
+        }
+
+        let mut tree = self.state_tree.write();
+        for (key, account) in &rolled_back {
+            tree.update(key, account.as_ref());
+        }
+        let state_root = tree.root();
+        let proof = tree.prove(&key);
+        for key in rolled_back.keys() {
+            tree.update(key, self.accounts.get(key).as_deref());
+        }
+
+        let account = match rolled_back.get(&key) {
+            Some(account) => account.clone(),
+            None => self.accounts.get(&key).map(|a| a.clone()),
+        };
+        Ok(AccountProof { height, block_hash, state_root, account, proof })
+    }
+
     pub fn mempool_handle(&self) -> Arc<TransactionMempool> {
         Arc::clone(&self.mempool)
     }
@@ -345,106 +770,502 @@ impl NumiBlockchain {
     }
 
     /* ----------------------- block handling ------------------------- */
+    /// Add a block to the block tree.
+    ///
+    /// Blocks extending the tip are connected directly.  Blocks on other
+    /// branches are validated against their parent and kept aside; if such a
+    /// branch ends up with more cumulative work than the main chain the node
+    /// reorganizes onto it.  Returns `Ok(false)` if the block is already known.
+    ///
+    /// A block whose parent is unknown is kept in the orphan pool and
+    /// reported as `InvalidBlockError::OrphanBlock` so the caller can fetch
+    /// the parent; pooled descendants are applied once it connects.
     pub async fn add_block(&self, block: Block) -> Result<bool> {
-        if self.get_block_by_hash(&block.calculate_hash(Some(&self.consensus))?).is_some() {
-            return Ok(false);
+        match self.import_block(block, None)? {
+            Some(returned) => {
+                self.mempool.readmit(returned).await;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// `add_block` for a chain shared behind a lock.  The write lock is held
+    /// while the block is imported and released before reorganized
+    /// transactions go back to the mempool.  `source` is the peer that
+    /// relayed the block, if any; it bounds that peer's share of the orphan
+    /// pool.
+    pub async fn add_block_to(chain: &RwLock<Self>, block: Block, source: Option<PeerId>) -> Result<bool> {
+        let (imported, mempool) = {
+            let chain = chain.write();
+            (chain.import_block(block, source)?, chain.mempool_handle())
+        };
+        match imported {
+            Some(returned) => {
+                mempool.readmit(returned).await;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The synchronous part of `add_block`: everything but handing
+    /// transactions back to the mempool.  Returns `None` if the block is
+    /// already known, otherwise the transactions a reorganization took off
+    /// the main chain; pass them to `TransactionMempool::readmit`.
+    pub fn import_block(&self, block: Block, source: Option<PeerId>) -> Result<Option<Vec<Transaction>>> {
+        let block_hash = block.calculate_hash(Some(&self.consensus))?;
+        if self.block_index.contains_key(&block_hash)
+            || self.side_blocks.contains_key(&block_hash)
+            || self.orphans.read().contains(&block_hash)
+        {
+            return Ok(None);
         }
 
+        let parent = block.header.previous_hash;
+        if !self.block_index.contains_key(&parent) && !self.side_blocks.contains_key(&parent) {
+            self.add_orphan(block, block_hash, source)?;
+            return Err(InvalidBlockError::OrphanBlock.into());
+        }
+
+        let mut returned = Vec::new();
+        self.accept_block(block, block_hash, &mut returned)?;
+        self.connect_orphans(block_hash, &mut returned);
+        Ok(Some(returned))
+    }
+
+    fn accept_block(&self, block: Block, block_hash: BlockHash, returned: &mut Vec<Transaction>) -> Result<()> {
         if block.header.previous_hash != self.get_latest_block_hash() {
+            return self.add_side_block(block, block_hash, returned);
+        }
+
+        let touched = self.connect_block(&block, block_hash)?;
+
+        // remove mined txs
+        self.mempool.record_mined(block.header.height, &block.transactions);
+        let ids: Vec<_> = block.transactions.iter().map(|t| t.id).collect();
+        self.mempool.remove_transactions(&ids);
+
+        // ------------------------------------------------------------------
+        // Sync the touched accounts' nonces and balances in the mempool with
+        // on-chain state so future submissions from those accounts are
+        // validated against them, and queued spends they no longer cover go.
+        // ------------------------------------------------------------------
+        self.mempool.sync_accounts_from_chain(&self.accounts, &touched, self.get_current_height());
+
+        self.prune_side_blocks();
+        Ok(())
+    }
+
+    /// Pool a block whose parent we have not seen.  Only the checks that do
+    /// not need the parent are done here; the rest happen when it connects.
+    /// The work is checked against our own difficulty rather than the one
+    /// the header claims, so orphans cannot be mined for free; orphans on a
+    /// branch whose difficulty fell below ours are left to the sync manager.
+    fn add_orphan(&self, block: Block, block_hash: BlockHash, source: Option<PeerId>) -> Result<()> {
+        if block.header.height + self.reorg_limit() <= self.get_current_height() {
             return Err(InvalidBlockError::StaleChain.into());
         }
+        if block.header.chain_id != self.consensus.chain_id {
+            return Err(InvalidBlockError::WrongChain(block.header.chain_id).into());
+        }
+        let minimum = self.get_current_difficulty();
+        if block.header.difficulty < minimum {
+            return Err(InvalidBlockError::DifficultyTooLow { got: block.header.difficulty, minimum }.into());
+        }
+        // `block_hash` is the PoW hash, so this is the full PoW check.
+        if !meets_target(&block_hash, &generate_difficulty_target(block.header.difficulty)) {
+            return Err(InvalidBlockError::InvalidPoW.into());
+        }
+        if !block.verify_signature()? {
+            return Err(InvalidBlockError::SignatureVerificationFailed.into());
+        }
+        if !block.verify_merkle_root() {
+            return Err(InvalidBlockError::InvalidMerkleRoot.into());
+        }
 
-        self.apply_block(&block)?;
+        let height = block.header.height;
+        if self.orphans.write().insert(block_hash, block, source) {
+            log::debug!("Holding orphan block #{height} ({}) until its parent arrives", hex::encode(block_hash));
+        }
+        Ok(())
+    }
+
+    /// Apply pooled orphans that were waiting for `parent`, parents first.
+    fn connect_orphans(&self, parent: BlockHash, returned: &mut Vec<Transaction>) {
+        let mut ready = VecDeque::from([parent]);
+        while let Some(hash) = ready.pop_front() {
+            let children = self.orphans.write().take_children(&hash);
+            for (child_hash, child) in children {
+                let height = child.header.height;
+                match self.accept_block(child, child_hash, returned) {
+                    Ok(()) => {
+                        log::debug!("Connected orphan block #{height}");
+                        ready.push_back(child_hash);
+                    }
+                    Err(e) => log::debug!("Dropping orphan block #{height}: {e}"),
+                }
+            }
+        }
+    }
+
+    /// Apply `block` on top of the current tip and advance the chain state.
+    /// The block, its transactions, the touched accounts and the new chain
+    /// state are committed to storage in a single transaction.  Returns the
+    /// keys of the accounts the block touched.
+    fn connect_block(&self, block: &Block, block_hash: BlockHash) -> Result<Vec<Vec<u8>>> {
+        let touched = self.connect_in_memory(block, block_hash)?;
+        let write = self.connect_write(block);
+        if let Err(e) = self.persist(&[write]) {
+            log::error!("CRITICAL: Failed to persist block #{}: {}", block.header.height, e);
+            self.disconnect_in_memory()?;
+            return Err(e);
+        }
+        Ok(touched)
+    }
+
+    /// The in-memory half of `connect_block`; storage is left untouched.
+    fn connect_in_memory(&self, block: &Block, block_hash: BlockHash) -> Result<Vec<Vec<u8>>> {
+        let undo = self.apply_block(block)?;
+        let touched = undo.iter().map(|(key, _)| key.clone()).collect();
         self.blocks.write().push(block.clone());
+        self.block_index.insert(block_hash, block.header.height);
+        self.index_transactions(block);
 
         // update chain state
         {
             let mut st = self.state.write();
             st.total_blocks += 1;
-            st.best_block_hash = block.calculate_hash(Some(&self.consensus))?;
-            st.cumulative_difficulty += block.header.difficulty as u128;
-            // mint
-            if let Some(reward) = block.transactions.iter().find_map(|tx| {
-                if let TransactionType::MiningReward { amount, .. } = tx.kind {
-                    Some(amount)
-                } else { None }
-            }) {
-                st.total_supply += reward;
-            }
+            st.best_block_hash = block_hash;
+            st.cumulative_difficulty = st.cumulative_difficulty.saturating_add(block_work(block.header.difficulty));
+            st.total_supply += minted_amount(block);
             // next difficulty based on recent block solvetime statistics
-            st.current_difficulty = next_difficulty(&self.blocks.read(), &self.consensus);
+            st.current_difficulty = chain_difficulty(&self.blocks.read(), &self.consensus);
         }
 
-        // remove mined txs
-        let ids: Vec<_> = block.transactions.iter().map(|t| t.id).collect();
-        self.mempool.remove_transactions(&ids).await;
+        let mut log = self.undo_log.write();
+        log.push_back(undo);
+        while log.len() as u64 > self.reorg_limit() {
+            log.pop_front();
+        }
+        Ok(touched)
+    }
 
-        // ------------------------------------------------------------------
-        // Sync sender nonces in mempool with on-chain state so future
-        // submissions from those accounts are validated against the correct
-        // expected nonce.
-        // ------------------------------------------------------------------
-        self.mempool.sync_nonces_from_chain(&self.accounts).await;
+    /// Remove the tip block from memory, restoring the account states it
+    /// modified.  Storage is left untouched.
+    fn disconnect_in_memory(&self) -> Result<(BlockHash, Block, BlockUndo)> {
+        let undo = self.undo_log.write().pop_back().ok_or_else(|| {
+            BlockchainError::ConsensusError("No undo data left for the chain tip".into())
+        })?;
+        let block = self.blocks.write().pop().ok_or(BlockchainError::MissingGenesisBlock)?;
+        let block_hash = self.get_latest_block_hash();
+        self.revert_accounts(&undo);
+        self.block_index.remove(&block_hash);
+        for tx in &block.transactions {
+            self.tx_index.remove(&tx.id);
+        }
 
-        // ------------------------------------------------------------------
-        // Persistence: write block file & periodic checkpoint (async)
-        // ------------------------------------------------------------------
-        if let Some(storage) = self.storage.clone() {
-            let dir = storage.blocks_dir();
-            let block_clone = block.clone();
-            tokio::task::spawn_blocking(move || {
-                // Write block file crash-safely
-                if let Err(e) = std::fs::create_dir_all(&dir) {
-                    log::error!("CRITICAL: Failed to create blocks directory '{}': {}", dir.display(), e);
-                    return;
-                }
-                let path = dir.join(format!("block_{:08}.bin", block_clone.header.height));
-                if !path.exists() {
-                    match bincode::serialize(&block_clone) {
-                        Ok(bytes) => {
-                            if let Err(e) = (|| -> std::io::Result<()> {
-                                let mut tmp = NamedTempFile::new_in(&dir)?;
-                                tmp.write_all(&bytes)?;
-                                match tmp.persist(&path) {
-                                    Ok(_) => Ok(()),
-                                    Err(pe) => Err(pe.error),
-                                }
-                            })() {
-                                log::error!("CRITICAL: Failed to persist block file '{}': {}", path.display(), e);
-                            }
-                        }
-                        Err(e) => log::error!("CRITICAL: Failed to serialize block {}: {}", block_clone.header.height, e),
+        let mut st = self.state.write();
+        st.total_blocks -= 1;
+        st.best_block_hash = block.header.previous_hash;
+        st.cumulative_difficulty = st.cumulative_difficulty.saturating_sub(block_work(block.header.difficulty));
+        st.total_supply -= minted_amount(&block);
+        st.current_difficulty = chain_difficulty(&self.blocks.read(), &self.consensus);
+        Ok((block_hash, block, undo))
+    }
+
+    /// Connect the genesis block of an empty chain.  Genesis has no undo
+    /// record: it can never be disconnected.
+    fn init_genesis(&self, genesis: &Block) -> Result<()> {
+        let genesis_hash = genesis.calculate_hash(Some(&self.consensus))?;
+        if let Some(pinned) = self.consensus.pinned_genesis_hash().map_err(BlockchainError::ConsensusError)? {
+            if pinned != genesis_hash {
+                return Err(BlockchainError::ConsensusError(format!(
+                    "Genesis block {} does not match the pinned genesis {}",
+                    hex::encode(genesis_hash),
+                    hex::encode(pinned)
+                )));
+            }
+        }
+        self.apply_block(genesis)?;
+        self.blocks.write().push(genesis.clone());
+        self.block_index.insert(genesis_hash, 0);
+        self.index_transactions(genesis);
+        {
+            let mut st = self.state.write();
+            st.total_blocks = 1;
+            st.best_block_hash = genesis_hash;
+            st.cumulative_difficulty = block_work(genesis.header.difficulty);
+            st.total_supply = minted_amount(genesis);
+            st.current_difficulty = genesis.header.difficulty;
+        }
+        let write = self.connect_write(genesis);
+        self.persist(&[write])?;
+        let touched: Vec<Vec<u8>> = self.accounts.iter().map(|e| e.key().clone()).collect();
+        self.mempool.sync_accounts_from_chain(&self.accounts, &touched, self.get_current_height());
+        Ok(())
+    }
+
+    /// Validate a block that does not extend our tip against its own parent
+    /// and keep it as part of a side branch.  Switches to that branch if it
+    /// now has more cumulative work than the main chain.
+    fn add_side_block(&self, block: Block, block_hash: BlockHash, returned: &mut Vec<Transaction>) -> Result<()> {
+        let tip_height = self.get_current_height();
+        if block.header.height + self.reorg_limit() <= tip_height {
+            return Err(InvalidBlockError::ReorgTooDeep {
+                depth: tip_height + 1 - block.header.height,
+                limit: self.reorg_limit(),
+            }
+            .into());
+        }
+
+        let parent_hash = block.header.previous_hash;
+        let (parent, parent_work) = if let Some(height) = self.get_block_height(&parent_hash) {
+            let parent = self.get_block_by_height(height).ok_or(InvalidBlockError::StaleChain)?;
+            (parent, self.cumulative_difficulty_at(height))
+        } else if let Some(side) = self.side_blocks.get(&parent_hash) {
+            (side.block.clone(), side.cumulative_difficulty)
+        } else {
+            // Parent unknown – nothing to attach the block to.
+            return Err(InvalidBlockError::StaleChain.into());
+        };
+
+        block.validate(Some(&parent), &self.consensus)?;
+        let expected = self.difficulty_after(&parent_hash)?;
+        if block.header.difficulty != expected {
+            return Err(InvalidBlockError::WrongDifficulty { got: block.header.difficulty, expected }.into());
+        }
+        let cumulative_difficulty = parent_work.saturating_add(block_work(block.header.difficulty));
+        let height = block.header.height;
+        self.side_blocks.insert(block_hash, SideBlock { block, cumulative_difficulty });
+        log::debug!("Stored side-branch block #{height} ({})", hex::encode(block_hash));
+
+        if cumulative_difficulty > self.state.read().cumulative_difficulty {
+            self.reorganize(block_hash, returned)?;
+        }
+        Ok(())
+    }
+
+    /// Switch the main chain to the side branch ending in `new_tip`.
+    ///
+    /// Blocks above the fork point are disconnected (restoring account
+    /// state), the branch is connected block by block, and transactions that
+    /// only existed on the abandoned blocks are collected in `returned` for
+    /// the mempool.  If any branch block fails to apply, the original chain
+    /// is restored.
+    fn reorganize(&self, new_tip: BlockHash, returned: &mut Vec<Transaction>) -> Result<()> {
+        // Walk back from the new tip until we hit the main chain.
+        let mut branch = Vec::new();
+        let mut cursor = new_tip;
+        let fork_height = loop {
+            if let Some(height) = self.get_block_height(&cursor) {
+                break height;
+            }
+            let block = self.side_blocks.get(&cursor).map(|s| s.block.clone()).ok_or_else(|| {
+                BlockchainError::ConsensusError("Side branch is not connected to the main chain".into())
+            })?;
+            let parent = block.header.previous_hash;
+            branch.push((cursor, block));
+            cursor = parent;
+        };
+        branch.reverse();
+
+        let old_height = self.get_current_height();
+        let depth = old_height - fork_height;
+        if depth > self.reorg_limit() || depth > self.undo_log.read().len() as u64 {
+            return Err(InvalidBlockError::ReorgTooDeep { depth, limit: self.reorg_limit() }.into());
+        }
+        log::warn!(
+            "🔀 Reorganizing: replacing {} block(s) above #{} with {} block(s)",
+            depth,
+            fork_height,
+            branch.len()
+        );
+
+        // Storage keeps the old chain until the whole switch is written in
+        // one transaction below.
+        let mut writes = Vec::new();
+        let mut disconnected = Vec::new();
+        // Accounts either side of the fork changed, for the mempool.
+        let mut touched = BTreeSet::new();
+        for _ in 0..depth {
+            let work = self.state.read().cumulative_difficulty;
+            let (hash, block, undo) = self.disconnect_in_memory()?;
+            touched.extend(undo.iter().map(|(key, _)| key.clone()));
+            writes.push(ChainWrite::Disconnect { block: block.clone(), undo, state: self.get_chain_state() });
+            self.side_blocks.insert(hash, SideBlock { block: block.clone(), cumulative_difficulty: work });
+            disconnected.push((hash, block));
+        }
+        disconnected.reverse();
+
+        for (i, (hash, block)) in branch.iter().enumerate() {
+            match self.connect_in_memory(block, *hash) {
+                Ok(keys) => touched.extend(keys),
+                Err(e) => {
+                    log::warn!("Reorg aborted, block #{} on the new branch is invalid: {}", block.header.height, e);
+                    self.restore_main_chain(i, &disconnected)?;
+                    // The failing block and everything built on it can never connect.
+                    for (bad_hash, _) in &branch[i..] {
+                        self.side_blocks.remove(bad_hash);
                     }
+                    return Err(e);
                 }
-            });
-        }
-
-        // Checkpoint logic
-        if block.header.height % self.consensus.checkpoint_interval == 0 {
-            if let Some(storage) = self.storage.clone() {
-                let chain_state = self.get_chain_state();
-                let state_root = self.calculate_state_root();
-                let block_clone = block.clone();
-
-                let checkpoint = SecurityCheckpoint {
-                    block_height: block_clone.header.height,
-                    block_hash: block_clone.calculate_hash(None).unwrap_or_default(),
-                    cumulative_difficulty: chain_state.cumulative_difficulty,
-                    timestamp: Utc::now(),
-                    total_supply: chain_state.total_supply,
-                    state_root,
-                };
-
-                let result = storage.transaction(|tx| {
-                    tx.save_checkpoint(&checkpoint)
-                });
-
-                if let Err(e) = result {
-                    log::error!("Failed to save checkpoint: {}", e);
-                }
             }
+            writes.push(self.connect_write(block));
+        }
+        if let Err(e) = self.persist(&writes) {
+            log::error!("CRITICAL: Failed to persist reorg onto {}: {}", hex::encode(new_tip), e);
+            self.restore_main_chain(branch.len(), &disconnected)?;
+            return Err(e);
+        }
+        for (hash, _) in &branch {
+            self.side_blocks.remove(hash);
+        }
+
+        // Mempool: drop what the new branch mined, return what it orphaned.
+        let included: HashSet<TransactionId> = branch
+            .iter()
+            .flat_map(|(_, b)| b.transactions.iter().map(|t| t.id))
+            .collect();
+        for (_, b) in &branch {
+            self.mempool.record_mined(b.header.height, &b.transactions);
+        }
+        let included_ids: Vec<_> = included.iter().copied().collect();
+        self.mempool.remove_transactions(&included_ids);
+        self.mempool.sync_accounts_from_chain(&self.accounts, &touched, self.get_current_height());
+        returned.extend(
+            disconnected
+                .iter()
+                .flat_map(|(_, b)| &b.transactions)
+                .filter(|tx| !matches!(tx.kind, TransactionType::MiningReward { .. }) && !included.contains(&tx.id))
+                .cloned(),
+        );
+
+        log::info!("🔀 Reorg complete: new tip #{} ({})", self.get_current_height(), hex::encode(new_tip));
+        self.prune_side_blocks();
+        Ok(())
+    }
+
+    /// Undo an unfinished reorganization in memory: drop the `connected`
+    /// branch blocks and put the `disconnected` main-chain blocks back.
+    fn restore_main_chain(&self, connected: usize, disconnected: &[(BlockHash, Block)]) -> Result<()> {
+        for _ in 0..connected {
+            self.disconnect_in_memory()?;
+        }
+        for (hash, block) in disconnected {
+            self.connect_in_memory(block, *hash)?;
+            self.side_blocks.remove(hash);
+        }
+        Ok(())
+    }
+
+    /// Difficulty a child of `parent` must carry: the retarget rule applied
+    /// to the branch ending in `parent`, which need not be the main chain.
+    fn difficulty_after(&self, parent: &BlockHash) -> Result<u32> {
+        if *parent == self.get_latest_block_hash() {
+            return Ok(self.get_current_difficulty());
+        }
+        // Side branches are pruned to the reorg window, so this walk is short.
+        let mut side = Vec::new();
+        let mut cursor = *parent;
+        let fork_height = loop {
+            if let Some(height) = self.get_block_height(&cursor) {
+                break height as usize;
+            }
+            let header = self
+                .side_blocks
+                .get(&cursor)
+                .map(|s| s.block.header.clone())
+                .ok_or(InvalidBlockError::StaleChain)?;
+            cursor = header.previous_hash;
+            side.push(header);
+        };
+        side.reverse();
+
+        let blocks = self.blocks.read();
+        let from = (fork_height + 1).saturating_sub(retarget_window(&self.consensus) + 1);
+        let headers: Vec<&BlockHeader> =
+            blocks[from..=fork_height].iter().map(|b| &b.header).chain(side.iter()).collect();
+        Ok(next_difficulty(&headers, &self.consensus))
+    }
+
+    /// Maximum number of main-chain blocks a reorganization may replace.
+    fn reorg_limit(&self) -> u64 {
+        self.consensus.max_reorg_depth.min(self.consensus.finality_depth)
+    }
+
+    /// Cumulative work of the main chain up to and including `height`.
+    fn cumulative_difficulty_at(&self, height: u64) -> u128 {
+        let above: u128 = self
+            .blocks
+            .read()
+            .iter()
+            .skip(height as usize + 1)
+            .fold(0u128, |work, b| work.saturating_add(block_work(b.header.difficulty)));
+        self.state.read().cumulative_difficulty.saturating_sub(above)
+    }
+
+    /// Forget side and orphan blocks that are too far below the tip to ever
+    /// be adopted.
+    fn prune_side_blocks(&self) {
+        let min_height = self.get_current_height().saturating_sub(self.reorg_limit());
+        self.side_blocks.retain(|_, side| side.block.header.height > min_height);
+        self.orphans.write().prune_below(min_height);
+    }
+
+    /// Capture what connecting the tip block changed, for `persist`: the
+    /// block, its transactions and address history entries, the accounts it
+    /// touched, its undo record and the chain state (plus a checkpoint and
+    /// account snapshot on interval heights).
+    fn connect_write(&self, block: &Block) -> ChainWrite {
+        let height = block.header.height;
+
+        // Genesis has no undo record, so collect its accounts from the block.
+        let undo = if height == 0 { None } else { self.undo_log.read().back().cloned() };
+        let touched: Vec<Vec<u8>> = match &undo {
+            Some(undo) => undo.iter().map(|(key, _)| key.clone()).collect(),
+            None => self.accounts.iter().map(|e| e.key().clone()).collect(),
+        };
+        let accounts = touched
+            .into_iter()
+            .map(|key| {
+                let account = self.accounts.get(&key).map(|a| a.clone());
+                (key, account)
+            })
+            .collect();
+        let state = self.get_chain_state();
+
+        // Checkpoints carry a full account snapshot so restarts can skip
+        // replaying everything below them.
+        let interval = self.consensus.checkpoint_interval;
+        let checkpoint = (height > 0 && height.is_multiple_of(interval)).then(|| {
+            let checkpoint = SecurityCheckpoint {
+                block_height: height,
+                block_hash: state.best_block_hash,
+                cumulative_difficulty: state.cumulative_difficulty,
+                timestamp: Utc::now(),
+                total_supply: state.total_supply,
+                state_root: self.state_tree.write().root(),
+            };
+            (checkpoint, account_snapshot(&self.accounts))
+        });
+
+        ChainWrite::Connect {
+            block: block.clone(),
+            accounts,
+            undo,
+            expired_undo: height.checked_sub(self.reorg_limit()).filter(|h| *h > 0),
+            checkpoint,
+            expired_snapshot: height.checked_sub(SNAPSHOTS_KEPT * interval).filter(|h| *h > 0),
+            state,
         }
-        Ok(true)
+    }
+
+    /// Commit `writes` to storage, in order, in one transaction.  Does
+    /// nothing for in-memory chains.
+    fn persist(&self, writes: &[ChainWrite]) -> Result<()> {
+        let Some(storage) = &self.storage else { return Ok(()) };
+        storage.transaction(|tx| writes.iter().try_for_each(|write| write.apply(tx)))
     }
 
     /* ------------------- state-recalc & maintenance ----------------- */
@@ -462,26 +1283,31 @@ impl NumiBlockchain {
         self.state.write().total_supply = supply;
         Ok(supply)
     }
-    /// Persist all blocks to plain files so that the chain can be reloaded on
-    /// restart even if the embedded sled database is wiped.
-    ///
-    /// Each file is named `block_{height}.bin` and contains a bincode‐encoded
-    /// `Block`.  This is *not* the primary long-term storage format, but is
-    /// sufficient for single-node operation and CI tests.
+    /// Write the whole in-memory chain – blocks, transactions, accounts and
+    /// chain state – to `storage` in one transaction.  Normal operation
+    /// commits block by block; this is for exporting a chain into a fresh
+    /// database.
     pub fn save_to_storage(&self, storage: &BlockchainStorage) -> Result<()> {
-        let dir = storage.blocks_dir();
-        std::fs::create_dir_all(&dir)?;
-
-        for block in self.blocks.read().iter() {
-            let path = dir.join(format!("block_{:08}.bin", block.header.height));
-            if path.exists() {
-                continue; // already persisted
+        let blocks = self.blocks.read().clone();
+        let accounts: Vec<(Vec<u8>, AccountState)> =
+            self.accounts.iter().map(|e| (e.key().clone(), e.value().clone())).collect();
+        let state = self.get_chain_state();
+
+        storage.transaction(|tx| {
+            for block in &blocks {
+                tx.save_block(block)?;
+                for t in &block.transactions {
+                    tx.save_transaction(&t.id, t)?;
+                }
+                for (address, entry) in address_history(block) {
+                    tx.save_address_entry(&address, &entry)?;
+                }
             }
-            let data = bincode::serialize(block)
-                .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
-            std::fs::write(path, data)?;
-        }
-        Ok(())
+            for (key, account) in &accounts {
+                tx.save_account(key, account)?;
+            }
+            tx.save_chain_state(&state)
+        })
     }
     pub async fn perform_maintenance(&self) -> Result<()> {
         self.mempool.house_keep().await;
@@ -490,7 +1316,13 @@ impl NumiBlockchain {
 
     /* --------------------- internal helpers ------------------------- */
     fn create_genesis_block(&self) -> Result<Block> {
+        if let Some(spec) = GenesisSpec::configured(&self.consensus)? {
+            return spec.block();
+        }
+
+        // Development chain: genesis pays the local miner.
         let mut tx = Transaction::new(
+            &self.consensus.chain_id,
             self.miner_keypair.public_key.clone(),
             TransactionType::MiningReward {
                 block_height: 0,
@@ -500,134 +1332,163 @@ impl NumiBlockchain {
         );
         tx.sign(&self.miner_keypair)?;
         let mut block = Block::new(
+            &self.consensus.chain_id,
             0,
             [0u8; 32],
             vec![tx],
             1,
             self.miner_keypair.public_key.clone(),
         );
+        // Genesis is applied to an empty account set.
+        let accounts = DashMap::new();
+        apply_transactions(&accounts, &block.transactions)?;
+        block.header.state_root = state_root(&account_snapshot(&accounts));
         block.sign(&self.miner_keypair, None)?;
         Ok(block)
     }
 
-    fn apply_block(&self, block: &Block) -> Result<()> {
+    fn apply_block(&self, block: &Block) -> Result<BlockUndo> {
         // structural validation
         block.validate(self.blocks.read().last(), &self.consensus)?;
+        // Genesis sets the starting difficulty; every later block must carry
+        // the retarget of the chain below it.
+        let expected = self.get_current_difficulty();
+        if !block.is_genesis() && block.header.difficulty != expected {
+            return Err(InvalidBlockError::WrongDifficulty { got: block.header.difficulty, expected }.into());
+        }
+
+        // Execute against an overlay; the live accounts only change once the
+        // whole block has applied and matches its state root.
+        let mut overlay = StateOverlay::new(&self.accounts);
+        overlay.apply_all(&block.transactions)?;
 
-        // state transition
+        let mut tree = self.state_tree.write();
+        let root = root_with_overlay(&mut tree, &overlay);
+        if block.header.commits_state_root() && root != block.header.state_root {
+            for (key, _) in overlay.changes() {
+                tree.update(key, self.accounts.get(key).as_deref());
+            }
+            return Err(InvalidBlockError::StateRootMismatch.into());
+        }
+        // The pre-block states let the block be disconnected again during a
+        // reorganization.
+        Ok(overlay.commit())
+    }
+
+    fn index_transactions(&self, block: &Block) {
         for tx in &block.transactions {
-            match &tx.kind {
-                TransactionType::Transfer { to, amount, .. } => {
-                    // Avoid nested mutable locks on the same DashMap shard.
-                    // Holding two entry guards for keys that hash to the same
-                    // shard can deadlock.  Handle sender and recipient in
-                    // separate scopes so the first guard is dropped before the
-                    // second is acquired.
-
-                    // Self-transfer: only the fee is deducted while the nonce
-                    // is incremented.
-                    if tx.from == *to {
-                        let mut acc = self.accounts.entry(tx.from.clone()).or_default();
-                        if acc.balance < tx.fee {
-                            return Err(BlockchainError::InvalidTransaction("Insufficient balance".into()));
-                        }
-                        acc.balance -= tx.fee;
-                        acc.nonce += 1;
-                        // No net amount change, nothing else to do.
-                    } else {
-                        // 1. Debit sender
-                        // NOTE: This logic relies on DashMap's shard locking. Operations
-                        // on different keys that hash to the same shard will block.
-                        // For high-contention accounts, this could become a bottleneck.
-                        {
-                            let mut sender = self.accounts.entry(tx.from.clone()).or_default();
-                            if sender.balance < amount + tx.fee {
-                                return Err(BlockchainError::InvalidTransaction("Insufficient balance".into()));
-                            }
-                            sender.balance -= amount + tx.fee;
-                            sender.nonce += 1;
-                        }
-
-                        // 2. Credit recipient (sender guard dropped)
-                        {
-                            let mut recipient = self.accounts.entry(to.clone()).or_default();
-                            recipient.balance += amount;
-                        }
-                    }
+            self.tx_index.insert(tx.id, block.header.height);
+        }
+    }
+
+    fn revert_accounts(&self, undo: &BlockUndo) {
+        let mut tree = self.state_tree.write();
+        for (key, previous) in undo.iter().rev() {
+            match previous {
+                Some(state) => {
+                    self.accounts.insert(key.clone(), state.clone());
                 }
-                TransactionType::MiningReward { amount, .. } => {
-                    let mut miner = self.accounts.entry(tx.from.clone()).or_default();
-                    miner.balance += amount;
+                None => {
+                    self.accounts.remove(key);
                 }
             }
+            tree.update(key, previous.as_ref());
         }
-        Ok(())
     }
+}
 
-    fn derive_address(&self, pk: &[u8]) -> String {
-        let h1 = blake3_hash(pk);
-        let mut payload = vec![0u8; 21];
-        payload[0] = 0x00; // version byte
-        payload[1..].copy_from_slice(&h1[..20]); // Using BLAKE3-160 for the address
+/// Execute `transactions` against `accounts` in order.  Nothing is written
+/// unless every transaction applies.
+fn apply_transactions(accounts: &AccountMap, transactions: &[Transaction]) -> Result<BlockUndo> {
+    let mut overlay = StateOverlay::new(accounts);
+    overlay.apply_all(transactions)?;
+    Ok(overlay.commit())
+}
 
-        let checksum = &blake3_hash(&blake3_hash(&payload))[..4];
-        let mut full = vec![0u8; 25];
-        full[..21].copy_from_slice(&payload);
-        full[21..].copy_from_slice(checksum);
-        bs58::encode(full).into_string()
-    }
+/// Copy of every account, sorted by public key.
+fn account_snapshot(accounts: &AccountMap) -> AccountSnapshot {
+    let mut snapshot: AccountSnapshot = accounts
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+    snapshot
+}
+
+/// Root of the state tree holding exactly the accounts in `snapshot`.
+fn state_root(snapshot: &AccountSnapshot) -> [u8; 32] {
+    StateTree::from_accounts(snapshot.iter().map(|(key, account)| (key.as_slice(), account))).root()
+}
 
-    pub fn is_valid_address(address: &str) -> bool {
-        if let Ok(decoded) = bs58::decode(address).into_vec() {
-            if decoded.len() != 25 { return false; }
-            let checksum = &decoded[21..];
-            let data     = &decoded[..21];
-            let hash     = &blake3_hash(&blake3_hash(data))[..4];
-            checksum == hash
-        } else { false }
+/// Root of `tree` with the overlay's accounts written into it.
+fn root_with_overlay(tree: &mut StateTree, overlay: &StateOverlay) -> [u8; 32] {
+    for (key, account) in overlay.changes() {
+        tree.update(key, Some(account));
     }
+    tree.root()
+}
 
-    fn calculate_state_root(&self) -> [u8; 32] {
-        if self.accounts.is_empty() {
-            return [0u8; 32];
+/// Address history entries for every transaction in `block`: one `Sent`
+/// for the spender (amount plus fee) and one `Received` per credited account.
+fn address_history(block: &Block) -> Vec<(Vec<u8>, AddressHistoryEntry)> {
+    let height = block.header.height;
+    let mut entries = Vec::new();
+    for tx in &block.transactions {
+        let credits = state_overlay::credits(tx);
+        if !matches!(tx.kind, TransactionType::MiningReward { .. }) {
+            let amount = credits.values().fold(tx.fee, |sum, amount| sum.saturating_add(*amount));
+            let entry = AddressHistoryEntry { height, tx_id: tx.id, direction: TxDirection::Sent, amount };
+            entries.push((tx.sender().to_bytes(), entry));
         }
-
-        let mut hashes: Vec<[u8; 32]> = self.accounts.iter()
-            .map(|entry| {
-                let mut hasher = blake3::Hasher::new();
-                hasher.update(entry.key());
-                if let Ok(value_bytes) = bincode::serialize(entry.value()) {
-                    hasher.update(&value_bytes);
-                }
-                *hasher.finalize().as_bytes()
-            })
-            .collect();
-
-        while hashes.len() > 1 {
-            if hashes.len() % 2 != 0 {
-                hashes.push(hashes.last().unwrap().clone());
-            }
-
-            hashes = hashes.chunks(2)
-                .map(|chunk| {
-                    let mut hasher = blake3::Hasher::new();
-                    hasher.update(&chunk[0]);
-                    hasher.update(&chunk[1]);
-                    *hasher.finalize().as_bytes()
-                })
-                .collect();
+        for (to, amount) in credits {
+            let entry = AddressHistoryEntry { height, tx_id: tx.id, direction: TxDirection::Received, amount };
+            entries.push((to.to_bytes(), entry));
         }
-
-        hashes.get(0).cloned().unwrap_or([0u8; 32])
     }
+    entries
+}
+
+/// Coins created by the block's mining reward transaction(s); only
+/// genesis has more than one.
+fn minted_amount(block: &Block) -> u64 {
+    block
+        .transactions
+        .iter()
+        .filter_map(|tx| match tx.kind {
+            TransactionType::MiningReward { amount, .. } => Some(amount),
+            _ => None,
+        })
+        .sum()
 }
 
 /* --------------------------------------------------------------------------
    Pragmatic difficulty adjustment
    ------------------------------------------------------------------------*/
 
+/// Work represented by a block at `difficulty` leading zero bits: the
+/// expected number of hashes needed to find it.  Fork choice compares the
+/// sum of this, so one block at difficulty `d + 1` outweighs one at `d`.
+pub fn block_work(difficulty: u32) -> u128 {
+    1u128.checked_shl(difficulty).unwrap_or(u128::MAX)
+}
+
+/// Difficulty required of the block after the last of `blocks`.
+fn chain_difficulty(blocks: &[Block], consensus: &ConsensusConfig) -> u32 {
+    let from = blocks.len().saturating_sub(retarget_window(consensus) + 1);
+    let headers: Vec<&BlockHeader> = blocks[from..].iter().map(|b| &b.header).collect();
+    next_difficulty(&headers, consensus)
+}
+
+/// Number of solve-times `next_difficulty` averages over.
+fn retarget_window(consensus: &ConsensusConfig) -> usize {
+    consensus.difficulty_adjustment_interval.max(1) as usize
+}
+
 /// Calculate the next difficulty using a bounded moving-average algorithm.
 ///
+/// `headers` are the most recent headers of a branch, oldest first, ending
+/// with the parent of the block being mined.
+///
 /// Rationale & rules:
 /// 1.  Use the timestamps of the most recent `window` blocks (default taken from
 ///     `consensus.difficulty_adjustment_interval`).
@@ -639,12 +1500,15 @@ impl NumiBlockchain {
 ///     (`clamp` defaults to 4) so the difficulty can at most quadruple or
 ///     quarter in one step.
 ///
+/// The child of genesis inherits the genesis difficulty, as there is no
+/// solve-time to measure yet.
+///
 /// The algorithm is intentionally simple, transparent and resistant to common
 /// timestamp attacks while being easy to tune via the `ConsensusConfig`.
-fn next_difficulty(blocks: &[Block], consensus: &ConsensusConfig) -> u32 {
+pub fn next_difficulty(headers: &[&BlockHeader], consensus: &ConsensusConfig) -> u32 {
     // Need at least 2 blocks to measure a solve-time.
-    if blocks.len() < 2 {
-        return 1;
+    if headers.len() < 2 {
+        return headers.last().map_or(1, |h| h.difficulty);
     }
 
     let target = consensus.target_block_time.as_secs();
@@ -653,19 +1517,16 @@ fn next_difficulty(blocks: &[Block], consensus: &ConsensusConfig) -> u32 {
     }
 
     // ---- collect truncated solve-times ----------------------------------
-    let window   = consensus
-        .difficulty_adjustment_interval
-        .max(1)                                   // avoid 0
-        .min((blocks.len() - 1) as u64) as usize; // cannot exceed available
+    let window = retarget_window(consensus).min(headers.len() - 1); // cannot exceed available
 
     let lower = (target / 4).max(1) as i64;
     let upper = (target * 4) as i64;
 
     let mut sum: i64 = 0;
     for i in 0..window {
-        let b_i    = &blocks[blocks.len() - 1 - i];
-        let b_prev = &blocks[blocks.len() - 2 - i];
-        let st = (b_i.header.timestamp.timestamp() - b_prev.header.timestamp.timestamp())
+        let h_i    = headers[headers.len() - 1 - i];
+        let h_prev = headers[headers.len() - 2 - i];
+        let st = (h_i.timestamp.timestamp() - h_prev.timestamp.timestamp())
             .max(1); // positive, non-zero
         let st_clamped = st.clamp(lower, upper);
         sum += st_clamped;
@@ -674,13 +1535,13 @@ fn next_difficulty(blocks: &[Block], consensus: &ConsensusConfig) -> u32 {
     let avg = sum as f64 / window as f64;
 
     // ---- difficulty factor ----------------------------------------------
-    let last_diff = blocks.last().unwrap().header.difficulty as f64;
+    let last_diff = headers[headers.len() - 1].difficulty as f64;
     let mut new_diff = last_diff * target as f64 / avg;
 
     // ---- anti-oscillation clamp -----------------------------------------
     let clamp_factor = 4.0; // can be made configurable
     let min_diff = (last_diff / clamp_factor).max(1.0);
-    let max_diff = (last_diff * clamp_factor).min(std::u32::MAX as f64);
+    let max_diff = (last_diff * clamp_factor).min(u32::MAX as f64);
     new_diff = new_diff.clamp(min_diff, max_diff);
 
     new_diff.round().max(1.0) as u32
@@ -563,6 +563,9 @@ pub struct StorageConfig {
     pub backup_interval_hours: u64,
     pub retention_days: u64,
     pub sync_mode: SyncMode,
+    /// Keep an address → transaction history index (for `/address/{addr}/history`)
+    #[serde(default)]
+    pub address_index: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -585,6 +588,7 @@ impl Default for StorageConfig {
             backup_interval_hours: 24,
             retention_days: 30,
             sync_mode: SyncMode::Normal,
+            address_index: false,
         }
     }
 }
@@ -602,6 +606,7 @@ impl StorageConfig {
             backup_interval_hours: 6,
             retention_days: 90,
             sync_mode: SyncMode::Full,
+            address_index: false,
         }
     }
 
@@ -615,6 +620,7 @@ impl StorageConfig {
             enable_encryption: false,
             auto_backup: false,
             sync_mode: SyncMode::Fast,
+            address_index: true,
             ..Default::default()
         }
     }
@@ -631,6 +637,7 @@ impl StorageConfig {
             backup_interval_hours: 12,
             retention_days: 7,
             sync_mode: SyncMode::Normal,
+            address_index: true,
         }
     }
 
@@ -653,6 +660,10 @@ impl StorageConfig {
 /// Consensus configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConsensusConfig {
+    /// Network identifier committed to by every transaction signature and
+    /// block header, and part of the gossip topic names
+    #[serde(default = "default_chain_id")]
+    pub chain_id: String,
     pub target_block_time: Duration,
     pub difficulty_adjustment_interval: u64,
     pub max_block_size: usize,
@@ -665,11 +676,30 @@ pub struct ConsensusConfig {
     pub mining_reward_halving_interval: u64,
     pub initial_mining_reward: u64,
     pub argon2_config: Argon2Config,
+    /// Genesis spec file; the testnet's is built in.  Without one a
+    /// development genesis is minted with the local miner key
+    #[serde(default)]
+    pub genesis_file: Option<PathBuf>,
+    /// Expected genesis block hash (hex); any other genesis is refused
+    #[serde(default)]
+    pub genesis_hash: Option<String>,
+}
+
+pub const MAINNET_CHAIN_ID: &str = "numi-mainnet";
+pub const TESTNET_CHAIN_ID: &str = "testnet-2025";
+/// Hash of the block `testnet-genesis.toml` produces under the testnet
+/// proof-of-work parameters.
+pub const TESTNET_GENESIS_HASH: &str = "f2b95c54792aea4500331c8796cad008a51823f4a86ccf5f685fe4d401102bf3";
+pub const DEVELOPMENT_CHAIN_ID: &str = "numi-dev";
+
+fn default_chain_id() -> String {
+    MAINNET_CHAIN_ID.to_string()
 }
 
 impl Default for ConsensusConfig {
     fn default() -> Self {
         Self {
+            chain_id: default_chain_id(),
             target_block_time: Duration::from_secs(30),
             difficulty_adjustment_interval: 144,
             max_block_size: 2 * 1024 * 1024, // 2MB
@@ -682,6 +712,8 @@ impl Default for ConsensusConfig {
             mining_reward_halving_interval: 111_000, //  halving every 111k blocks
             initial_mining_reward: 8888, // 88.88 NUMI (8888 NANO units)
             argon2_config: Argon2Config::default(),
+            genesis_file: None,
+            genesis_hash: None,
         }
     }
 }
@@ -698,6 +730,7 @@ impl ConsensusConfig {
 
     pub fn development() -> Self {
         Self {
+            chain_id: DEVELOPMENT_CHAIN_ID.to_string(),
             target_block_time: Duration::from_secs(10), // Faster for testing
             difficulty_adjustment_interval: 20,
             max_block_size: 512 * 1024, // 512KB
@@ -712,6 +745,7 @@ impl ConsensusConfig {
 
     pub fn testnet() -> Self {
         Self {
+            chain_id: TESTNET_CHAIN_ID.to_string(), // must match testnet-genesis.toml
             target_block_time: Duration::from_secs(15), // 15 second blocks for testnet
             difficulty_adjustment_interval: 30,
             max_block_size: 1024 * 1024, // 1MB
@@ -724,6 +758,8 @@ impl ConsensusConfig {
             mining_reward_halving_interval: 1000, // 1k blocks halving for faster testing
             initial_mining_reward: 8888, // 88.88 NUMI (8888 NANO units) - same as mainnet
             argon2_config: Argon2Config::development(),
+            genesis_file: None, // built in, see `genesis::TESTNET_GENESIS`
+            genesis_hash: Some(TESTNET_GENESIS_HASH.to_string()),
         }
     }
 
@@ -740,8 +776,25 @@ impl ConsensusConfig {
         if self.max_transactions_per_block == 0 {
             return Err("Max transactions per block must be greater than 0".to_string());
         }
+        if self.chain_id.is_empty() {
+            return Err("Chain id must not be empty".to_string());
+        }
+        self.pinned_genesis_hash()?;
         Ok(())
     }
+
+    /// Decoded `genesis_hash`, if one is pinned.
+    pub fn pinned_genesis_hash(&self) -> Result<Option<[u8; 32]>, String> {
+        self.genesis_hash
+            .as_deref()
+            .map(|h| {
+                hex::decode(h)
+                    .ok()
+                    .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                    .ok_or_else(|| format!("Invalid genesis hash '{h}'"))
+            })
+            .transpose()
+    }
 }
 
 #[cfg(test)]
@@ -23,7 +23,7 @@ use rand::rngs::OsRng;
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use subtle::ConstantTimeEq;
-use zeroize::ZeroizeOnDrop;
+use zeroize::{ZeroizeOnDrop, Zeroizing};
 
 use crate::error::BlockchainError;
 use crate::Result;
@@ -39,6 +39,8 @@ pub const MAX_SIGNABLE_MESSAGE_SIZE: usize = 4 * 1024 * 1024; // Reduced to 4MB
 pub const DILITHIUM3_SIGNATURE_SIZE: usize = pqcrypto_dilithium::dilithium3::signature_bytes();
 pub const DILITHIUM3_PUBKEY_SIZE: usize    = pqcrypto_dilithium::dilithium3::public_key_bytes();
 pub const DILITHIUM3_SECKEY_SIZE: usize    = pqcrypto_dilithium::dilithium3::secret_key_bytes();
+/// Width of `tr = H(pk)` inside a backend secret key
+const DILITHIUM3_TR_SIZE: usize = 64;
 
 /// PEM-export/import record
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,6 +49,22 @@ pub struct PemKeyPair {
     pub public_key:  String,
 }
 
+impl PemKeyPair {
+    /// PEM text of both keys, as read back by `Dilithium3Keypair::from_pem`.
+    pub fn to_pem_string(&self) -> Zeroizing<String> {
+        let mut pem = Zeroizing::new(String::new());
+        for (tag, b64) in [("PRIVATE KEY", &self.private_key), ("PUBLIC KEY", &self.public_key)] {
+            pem.push_str(&format!("-----BEGIN {}-----\n", tag));
+            for line in b64.as_bytes().chunks(64) {
+                pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+                pem.push('\n');
+            }
+            pem.push_str(&format!("-----END {}-----\n", tag));
+        }
+        pem
+    }
+}
+
 /// Post-quantum Dilithium3 keypair
 #[derive(Debug, Clone, Serialize, Deserialize, ZeroizeOnDrop)]
 pub struct Dilithium3Keypair {
@@ -91,6 +109,33 @@ impl Dilithium3Keypair {
         Ok(Self { public_key, secret_key, fingerprint, created_at })
     }
 
+    /// Keypair generated deterministically from `seed`: the same seed always
+    /// yields the same keys.  Used for accounts of mnemonic wallets.
+    ///
+    /// The signing backend (PQClean Dilithium3) has no seeded key generation.
+    /// crystals-dilithium expands the seed into rho, rho' and key exactly as
+    /// PQClean does, so the public key and s1, s2, t0 are what PQClean would
+    /// generate from `seed`; only `tr = H(pk)` is narrower in its secret key,
+    /// so the secret key is repacked with PQClean's 64-byte `tr`.
+    pub fn from_seed(seed: &Hash) -> Result<Self> {
+        use crystals_dilithium::{fips202::shake256, params::SEEDBYTES};
+
+        let keys = crystals_dilithium::dilithium3::Keypair::generate(Some(&seed[..]));
+        let public_key = keys.public.to_bytes().to_vec();
+        let short = Zeroizing::new(keys.secret.to_bytes());
+
+        let mut tr = [0u8; DILITHIUM3_TR_SIZE];
+        shake256(&mut tr, DILITHIUM3_TR_SIZE, &public_key, public_key.len());
+        // (rho, key, tr, s1, s2, t0) in both layouts
+        let mut secret_key = Vec::with_capacity(DILITHIUM3_SECKEY_SIZE);
+        secret_key.extend_from_slice(&short[..2 * SEEDBYTES]);
+        secret_key.extend_from_slice(&tr);
+        secret_key.extend_from_slice(&short[3 * SEEDBYTES..]);
+        // Round-trips through the signing backend, so a key it cannot use is
+        // refused here rather than when spending.
+        Self::from_bytes(public_key, secret_key)
+    }
+
     /// Load from JSON or PEM
     pub fn load_from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
         // Enforce strict file permissions (owner read-only)
@@ -526,24 +571,3 @@ impl fmt::Display for Dilithium3Signature {
         write!(f, "Dilithium3Signature(sig: {}..., size: {} bytes)", preview, self.size())
     }
 }
-
-/// Derive address from public key using BLAKE3 + RIPEMD160 + Base58Check
-pub fn derive_address_from_public_key(pk: &[u8]) -> Result<String> {
-    use ripemd::{Ripemd160, Digest};
-    
-    let h1 = blake3_hash(pk);
-    let mut h2 = Ripemd160::new();
-    h2.update(h1);
-    let h3 = h2.finalize();
-
-    let mut payload = vec![0u8; 21];
-    payload[0] = 0x00;
-    payload[1..].copy_from_slice(&h3);
-
-    let checksum = &blake3_hash(&blake3_hash(&payload))[..4];
-    let mut full = vec![0u8; 25];
-    full[..21].copy_from_slice(&payload);
-    full[21..].copy_from_slice(checksum);
-    
-    Ok(bs58::encode(full).into_string())
-}
@@ -13,9 +13,9 @@ pub enum InvalidBlockError {
     GenesisBlockHeightNotZero,
     #[error("Genesis block previous_hash must be zero")]
     GenesisBlockHashNotZero,
-    #[error("Genesis block must have exactly one transaction")]
+    #[error("Genesis block must have at least one transaction")]
     GenesisBlockInvalidTransactionCount,
-    #[error("Genesis block's only transaction must be a mining reward")]
+    #[error("Genesis block may only contain mining rewards")]
     GenesisBlockTransactionNotReward,
     #[error("Invalid number of mining reward transactions in block")]
     InvalidRewardTransactionCount,
@@ -29,10 +29,26 @@ pub enum InvalidBlockError {
     TimestampOutOfRange(String),
     #[error("Invalid PoW")]
     InvalidPoW,
+    #[error("Block difficulty {got} is below the required {minimum}")]
+    DifficultyTooLow { got: u32, minimum: u32 },
+    #[error("Block difficulty {got} does not match the expected {expected}")]
+    WrongDifficulty { got: u32, expected: u32 },
     #[error("The block is stale and does not connect to the main chain")]
     StaleChain,
+    #[error("Parent block is unknown; block kept as orphan")]
+    OrphanBlock,
+    #[error("Reorganization of {depth} blocks exceeds the limit of {limit}")]
+    ReorgTooDeep { depth: u64, limit: u64 },
     #[error("Invalid transaction in block: {0}")]
     InvalidTransaction(String),
+    #[error("Unsupported block version {0}")]
+    UnsupportedVersion(u32),
+    #[error("State root does not match the account state after the block")]
+    StateRootMismatch,
+    #[error("Transaction {0} appears more than once in the block")]
+    DuplicateTransaction(String),
+    #[error("Block belongs to another network (chain id '{0}')")]
+    WrongChain(String),
 }
 
 #[derive(Debug, Clone, Error)]
@@ -78,6 +94,9 @@ pub enum BlockchainError {
     #[error("Invalid argument: {0}")]
     InvalidArgument(String),
 
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+
     #[error("Invalid backup: {0}")]
     InvalidBackup(String),
 
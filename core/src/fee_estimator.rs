@@ -0,0 +1,169 @@
+// src/fee_estimator.rs
+//
+// Fee estimation from recent blocks and the mempool backlog.
+// --------------------------------------------------------------
+// • For every mined transaction the mempool saw, remember its fee rate and
+//   how many blocks it waited (inclusion delay)
+// • History: the lowest fee rate at which most transactions paying at
+//   least that much were mined within the target
+// • Backlog: the fee rate needed to get ahead of the pending transactions
+//   that already fill the target's blocks
+// • Rates are nano-NUMI per MiB; fees are a few nano for several KiB of
+//   Dilithium3 keys and signatures, so coarser units round them all to 0
+//
+
+use std::{cmp::Reverse, collections::VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::transaction::MAX_FEE;
+
+/// Mined blocks remembered for estimation.
+pub const FEE_HISTORY_BLOCKS: usize = 100;
+/// Confirmation target used when none is given.
+pub const DEFAULT_TARGET_BLOCKS: u32 = 3;
+/// Largest confirmation target an estimate can be asked for.
+pub const MAX_TARGET_BLOCKS: u32 = 25;
+/// Share of transactions (in percent) that must have made the target.
+const SUCCESS_PERCENT: u64 = 85;
+/// Fewer samples than this are not trusted.
+const MIN_SAMPLES: u64 = 10;
+
+/// Fee rate (nano-NUMI per MiB) of `fee` paid for `size` bytes.
+pub fn fee_per_mib(fee: u64, size: usize) -> u64 {
+    if size == 0 {
+        return 0;
+    }
+    fee.saturating_mul(1 << 20) / size as u64
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeeSample {
+    pub fee_per_mib: u64,
+    /// Blocks between admission to the mempool and inclusion (≥ 1)
+    pub delay: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    pub target_blocks: u32,
+    pub fee_per_mib: u64,
+    /// Least fee the mempool currently admits
+    pub min_fee: u64,
+    /// Mined transactions the estimate is based on
+    pub samples: usize,
+}
+
+impl FeeEstimate {
+    /// Fee for a transaction of `size` bytes, capped at `MAX_FEE`.  Callers
+    /// still pay at least the transaction's own minimum fee.
+    pub fn fee_for_size(&self, size: usize) -> u64 {
+        let by_rate = self.fee_per_mib.saturating_mul(size as u64).div_ceil(1 << 20);
+        by_rate.max(self.min_fee).min(MAX_FEE)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FeeEstimator {
+    blocks: VecDeque<Vec<FeeSample>>,
+}
+
+impl FeeEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the samples of a newly mined block.
+    pub fn record_block(&mut self, samples: Vec<FeeSample>) {
+        self.blocks.push_back(samples);
+        while self.blocks.len() > FEE_HISTORY_BLOCKS {
+            self.blocks.pop_front();
+        }
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.blocks.iter().map(Vec::len).sum()
+    }
+
+    /// Lowest rate at which at least `SUCCESS_PERCENT` of the transactions
+    /// paying that rate or more were mined within `target` blocks.
+    pub fn history_rate(&self, target: u32) -> Option<u64> {
+        let mut samples: Vec<FeeSample> = self.blocks.iter().flatten().copied().collect();
+        samples.sort_by_key(|s| Reverse(s.fee_per_mib));
+
+        let (mut total, mut in_time, mut best) = (0u64, 0u64, None);
+        for (i, sample) in samples.iter().enumerate() {
+            total += 1;
+            if sample.delay <= target as u64 {
+                in_time += 1;
+            }
+            // Only judge a rate once every sample paying it has been counted.
+            let last_of_rate = samples.get(i + 1).is_none_or(|next| next.fee_per_mib != sample.fee_per_mib);
+            if last_of_rate && total >= MIN_SAMPLES && in_time * 100 >= total * SUCCESS_PERCENT {
+                best = Some(sample.fee_per_mib);
+            }
+        }
+        best
+    }
+
+    /// Rate that outbids the pending transactions filling the next `target`
+    /// blocks, or `None` if everything pending fits.
+    pub fn backlog_rate(pending: &mut [(u64, usize)], target: u32, block_bytes: usize) -> Option<u64> {
+        pending.sort_by_key(|&(rate, _)| Reverse(rate));
+        let capacity = block_bytes.saturating_mul(target as usize);
+        let mut used = 0usize;
+        for (rate, size) in pending.iter() {
+            used += size;
+            if used > capacity {
+                return Some(rate + 1);
+            }
+        }
+        None
+    }
+
+    /// Estimate for `target` blocks given the pending `(fee_per_mib, size)`
+    /// pairs and the mempool's current minimum fee.
+    pub fn estimate(&self, target: u32, pending: &mut [(u64, usize)], block_bytes: usize, min_fee: u64) -> FeeEstimate {
+        let target = target.clamp(1, MAX_TARGET_BLOCKS);
+        let rate = self
+            .history_rate(target)
+            .unwrap_or(0)
+            .max(Self::backlog_rate(pending, target, block_bytes).unwrap_or(0));
+        FeeEstimate { target_blocks: target, fee_per_mib: rate, min_fee, samples: self.sample_count() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(rate: u64, delay: u64, n: usize) -> Vec<FeeSample> {
+        vec![FeeSample { fee_per_mib: rate, delay }; n]
+    }
+
+    #[test]
+    fn test_history_picks_lowest_rate_meeting_target() {
+        let mut est = FeeEstimator::new();
+        est.record_block(samples(10, 1, 20));
+        est.record_block(samples(5, 3, 20));
+        est.record_block(samples(1, 10, 20));
+
+        assert_eq!(est.history_rate(1), Some(10));
+        assert_eq!(est.history_rate(3), Some(5));
+        assert_eq!(est.history_rate(10), Some(1));
+        assert_eq!(FeeEstimator::new().history_rate(1), None);
+    }
+
+    #[test]
+    fn test_backlog_outbids_full_blocks() {
+        let mut pending = vec![(3, 600), (8, 600), (5, 600)];
+        assert_eq!(FeeEstimator::backlog_rate(&mut pending, 1, 1_000), Some(6));
+        assert_eq!(FeeEstimator::backlog_rate(&mut pending, 2, 800), Some(4));
+        assert_eq!(FeeEstimator::backlog_rate(&mut pending, 2, 10_000), None);
+
+        let estimate = FeeEstimator::new().estimate(1, &mut pending, 1_000, 2);
+        assert_eq!(estimate.fee_per_mib, 6);
+        assert_eq!(estimate.fee_for_size(1 << 20), 6);
+        assert_eq!(estimate.fee_for_size(10), 2);
+    }
+}
@@ -0,0 +1,277 @@
+// src/genesis.rs
+//
+// Deterministic genesis block.
+// --------------------------------------------------------------
+// • `GenesisSpec` is the `[genesis]` table of `testnet-genesis.toml`
+// • The same spec yields the same block, hash and initial accounts on
+//   every node – no local key is involved
+// • Pre-funded accounts are credited by one height-0 reward each; the
+//   block is unsigned and trusted by its (pinned) hash instead
+// • Accounts are given as addresses of the chain's network, or as hex
+//   public keys whose address is funded
+//
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    address::{Address, Network},
+    block::Block,
+    blockchain::{AccountSnapshot, AccountState},
+    config::{ConsensusConfig, TESTNET_CHAIN_ID},
+    error::BlockchainError,
+    state_tree::StateTree,
+    transaction::{Transaction, TransactionType},
+    Result,
+};
+
+/// Genesis of the public testnet, built into the binary so a node finds it
+/// whatever directory it is started from.
+pub const TESTNET_GENESIS: &str = include_str!("../../testnet-genesis.toml");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenesisSpec {
+    pub chain_id: String,
+    #[serde(default)]
+    pub network_name: String,
+    pub timestamp: DateTime<Utc>,
+    pub difficulty: u32,
+    pub accounts: GenesisAccounts,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GenesisAccounts {
+    #[serde(default)]
+    pub accounts: Vec<GenesisAccount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenesisAccount {
+    /// Base58Check address, or a hex-encoded public key
+    pub address: String,
+    pub balance: u64,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GenesisFile {
+    genesis: GenesisSpec,
+}
+
+impl GenesisSpec {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            BlockchainError::IoError(format!("Failed to read genesis file {}: {e}", path.display()))
+        })?;
+        Self::from_toml_str(&text)
+    }
+
+    /// The spec `consensus` starts from: its `genesis_file`, else the
+    /// built-in genesis on the testnet chain.  `None` means a development
+    /// genesis minted with the local key.
+    pub fn configured(consensus: &ConsensusConfig) -> Result<Option<Self>> {
+        let spec = match &consensus.genesis_file {
+            Some(path) => Self::load(path)?,
+            None if consensus.chain_id == TESTNET_CHAIN_ID => Self::from_toml_str(TESTNET_GENESIS)?,
+            None => return Ok(None),
+        };
+        if spec.chain_id != consensus.chain_id {
+            return Err(BlockchainError::ConsensusError(format!(
+                "Genesis spec is for chain '{}' but this node runs '{}'",
+                spec.chain_id, consensus.chain_id
+            )));
+        }
+        Ok(Some(spec))
+    }
+
+    pub fn from_toml_str(text: &str) -> Result<Self> {
+        let file: GenesisFile = toml::from_str(text)?;
+        file.genesis.validate()?;
+        Ok(file.genesis)
+    }
+
+    /// The spec as a genesis file, readable by `from_toml_str`.
+    pub fn to_toml_string(&self) -> Result<String> {
+        Ok(toml::to_string(&GenesisFile { genesis: self.clone() })?)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.chain_id.is_empty() {
+            return Err(BlockchainError::InvalidArgument("Genesis chain_id must not be empty".into()));
+        }
+        if self.accounts.accounts.is_empty() {
+            return Err(BlockchainError::InvalidArgument("Genesis must fund at least one account".into()));
+        }
+        let mut seen = HashSet::new();
+        let network = Network::from_chain_id(&self.chain_id);
+        for (address, balance) in self.balances()? {
+            if balance == 0 {
+                return Err(BlockchainError::InvalidArgument(format!(
+                    "Genesis account {} has a zero balance",
+                    address.encode(network)
+                )));
+            }
+            if !seen.insert(address) {
+                return Err(BlockchainError::InvalidArgument(format!(
+                    "Genesis account {} is listed twice",
+                    address.encode(network)
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Funded addresses and their initial balances, in file order.
+    pub fn balances(&self) -> Result<Vec<(Address, u64)>> {
+        let network = Network::from_chain_id(&self.chain_id);
+        self.accounts
+            .accounts
+            .iter()
+            .map(|account| {
+                let address = Address::parse(&account.address, network)
+                    .ok()
+                    .or_else(|| {
+                        hex::decode(&account.address)
+                            .ok()
+                            .filter(|key| !key.is_empty())
+                            .map(|key| Address::from_public_key(&key))
+                    })
+                    .ok_or_else(|| {
+                        BlockchainError::InvalidArgument(format!("Invalid genesis address '{}'", account.address))
+                    })?;
+                Ok((address, account.balance))
+            })
+            .collect()
+    }
+
+    /// Account state right after genesis, sorted by key.  Each allocation
+    /// is a reward transaction, so it counts as received.
+    pub fn initial_accounts(&self) -> Result<AccountSnapshot> {
+        let mut accounts: BTreeMap<Vec<u8>, AccountState> = BTreeMap::new();
+        for (address, balance) in self.balances()? {
+            let account = accounts.entry(address.to_bytes()).or_default();
+            account.balance += balance;
+            account.total_received += balance;
+            account.transaction_count += 1;
+        }
+        Ok(accounts.into_iter().collect())
+    }
+
+    pub fn total_supply(&self) -> u64 {
+        self.accounts.accounts.iter().map(|a| a.balance).sum()
+    }
+
+    /// Build the genesis block.  Every field is taken from the spec, so the
+    /// result is identical on every node.
+    pub fn block(&self) -> Result<Block> {
+        let transactions = self
+            .balances()?
+            .into_iter()
+            .map(|(address, amount)| {
+                let reward = TransactionType::MiningReward { block_height: 0, amount };
+                let mut tx = Transaction::new(&self.chain_id, address.to_bytes(), reward, 0);
+                tx.timestamp = self.timestamp;
+                tx.valid_until = self.timestamp;
+                tx.id = tx.hash();
+                tx
+            })
+            .collect();
+
+        let mut block = Block::new(&self.chain_id, 0, [0u8; 32], transactions, self.difficulty, Vec::new());
+        block.header.timestamp = self.timestamp;
+        let accounts = self.initial_accounts()?;
+        block.header.state_root =
+            StateTree::from_accounts(accounts.iter().map(|(key, account)| (key.as_slice(), account))).root();
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    const SPEC: &str = r#"
+        [genesis]
+        chain_id = "test-1"
+        timestamp = "2025-07-04T13:33:37Z"
+        difficulty = 1
+
+        [[genesis.accounts.accounts]]
+        address = "0a0b0c"
+        balance = 500
+
+        [[genesis.accounts.accounts]]
+        address = "01"
+        balance = 100
+    "#;
+
+    #[test]
+    fn test_block_is_deterministic() {
+        let spec = GenesisSpec::from_toml_str(SPEC).unwrap();
+        let a = spec.block().unwrap();
+        let b = GenesisSpec::from_toml_str(SPEC).unwrap().block().unwrap();
+
+        assert_eq!(a.calculate_hash(None).unwrap(), b.calculate_hash(None).unwrap());
+        assert_eq!(a.transactions.len(), 2);
+        assert_eq!(spec.total_supply(), 600);
+        let mut funded = vec![Address::from_public_key(&[1]).to_bytes(), Address::from_public_key(&[10, 11, 12]).to_bytes()];
+        funded.sort();
+        let accounts: Vec<Vec<u8>> = spec.initial_accounts().unwrap().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(accounts, funded);
+    }
+
+    #[test]
+    fn test_accepts_addresses_of_its_network() {
+        let address = Address::from_public_key(&[1]);
+        let spec = SPEC.replace("\"01\"", &format!("\"{}\"", address.encode(Network::Development)));
+        let spec = GenesisSpec::from_toml_str(&spec).unwrap();
+        assert_eq!(spec.balances().unwrap()[1], (address, 100));
+
+        let wrong_network = SPEC.replace("\"01\"", &format!("\"{}\"", address.encode(Network::Mainnet)));
+        assert!(GenesisSpec::from_toml_str(&wrong_network).is_err());
+    }
+
+    #[test]
+    fn test_rejects_duplicate_accounts() {
+        let spec = SPEC.replace("\"01\"", "\"0a0b0c\"");
+        assert!(GenesisSpec::from_toml_str(&spec).is_err());
+    }
+
+    #[test]
+    fn test_parses_testnet_genesis() {
+        let spec = GenesisSpec::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../testnet-genesis.toml")).unwrap();
+        assert_eq!(spec.chain_id, "testnet-2025");
+        assert_eq!(spec.accounts.accounts.len(), 4);
+    }
+
+    #[test]
+    fn test_testnet_genesis_is_built_in() {
+        let consensus = ConsensusConfig::testnet();
+        assert!(consensus.genesis_file.is_none());
+        let spec = GenesisSpec::configured(&consensus).unwrap().unwrap();
+        assert_eq!(spec.chain_id, consensus.chain_id);
+        assert!(GenesisSpec::configured(&ConsensusConfig::development()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_testnet_config_files_pin_the_built_in_genesis() {
+        let built_in = ConsensusConfig::testnet();
+        let spec = GenesisSpec::configured(&built_in).unwrap().unwrap();
+        let hash = spec.block().unwrap().calculate_hash(Some(&built_in)).unwrap();
+        assert_eq!(built_in.pinned_genesis_hash().unwrap(), Some(hash));
+
+        for file in ["/../testnet.toml", "/../testnet/testnet.toml"] {
+            let config = Config::load_from_file(format!("{}{file}", env!("CARGO_MANIFEST_DIR"))).unwrap();
+            let spec = GenesisSpec::configured(&config.consensus).unwrap().unwrap();
+            let block = spec.block().unwrap();
+            assert_eq!(config.consensus.pinned_genesis_hash().unwrap(), Some(hash), "{file}");
+            assert_eq!(block.calculate_hash(Some(&config.consensus)).unwrap(), hash, "{file}");
+        }
+    }
+}
@@ -0,0 +1,101 @@
+// src/hd_wallet.rs
+//
+// Mnemonic (seed-phrase) wallets.
+// --------------------------------------------------------------
+// • A BIP39 phrase of 24 English words carries 256 bits of entropy; its
+//   BIP39 seed (PBKDF2 of phrase and optional passphrase) is the wallet root
+// • Account `i` is the Dilithium3 keypair generated from
+//   `derive_key(root, HD_DERIVATION_CONTEXT, i)`.  Dilithium keys cannot be
+//   tweaked like BIP32 keys, so every account is an independent key hashed
+//   off the root
+// • Backing up the phrase once recovers every account of the wallet
+//
+
+use bip39::Mnemonic;
+use zeroize::Zeroizing;
+
+use crate::{
+    crypto::{derive_key, generate_random_bytes, Dilithium3Keypair},
+    error::BlockchainError,
+    Result,
+};
+
+pub const MNEMONIC_WORDS: usize = 24;
+/// Entropy behind a `MNEMONIC_WORDS`-word phrase.
+const MNEMONIC_ENTROPY_BYTES: usize = 32;
+/// BLAKE3 key-derivation context for account seeds.
+const HD_DERIVATION_CONTEXT: &str = "numi 2025-07 hd-wallet dilithium3 account";
+
+pub struct HdWallet {
+    mnemonic: Mnemonic,
+    seed: Zeroizing<[u8; 64]>,
+}
+
+impl HdWallet {
+    /// New wallet with a fresh phrase.
+    pub fn generate() -> Result<Self> {
+        let entropy = Zeroizing::new(generate_random_bytes(MNEMONIC_ENTROPY_BYTES)?);
+        let mnemonic = Mnemonic::from_entropy(&entropy)
+            .map_err(|e| BlockchainError::CryptographyError(e.to_string()))?;
+        Ok(Self::from_mnemonic(mnemonic, ""))
+    }
+
+    /// Wallet of an existing phrase.  A different `passphrase` gives a
+    /// different, equally valid wallet, so it must match the original.
+    pub fn from_phrase(phrase: &str, passphrase: &str) -> Result<Self> {
+        let mnemonic = Mnemonic::parse(phrase)
+            .map_err(|e| BlockchainError::InvalidArgument(format!("Invalid recovery phrase: {e}")))?;
+        Ok(Self::from_mnemonic(mnemonic, passphrase))
+    }
+
+    fn from_mnemonic(mnemonic: Mnemonic, passphrase: &str) -> Self {
+        let seed = Zeroizing::new(mnemonic.to_seed(passphrase));
+        Self { mnemonic, seed }
+    }
+
+    /// The recovery phrase, words separated by single spaces.
+    pub fn phrase(&self) -> Zeroizing<String> {
+        Zeroizing::new(self.mnemonic.to_string())
+    }
+
+    /// Keypair of account `index`.
+    pub fn keypair(&self, index: u32) -> Result<Dilithium3Keypair> {
+        let seed = Zeroizing::new(derive_key(&self.seed[..], HD_DERIVATION_CONTEXT, &index.to_be_bytes())?);
+        Dilithium3Keypair::from_seed(&seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phrase_restores_every_account() {
+        let wallet = HdWallet::generate().unwrap();
+        assert_eq!(wallet.phrase().split(' ').count(), MNEMONIC_WORDS);
+
+        let restored = HdWallet::from_phrase(&wallet.phrase(), "").unwrap();
+        for index in [0, 1, 7] {
+            assert_eq!(wallet.keypair(index).unwrap().public_key, restored.keypair(index).unwrap().public_key);
+        }
+        assert_ne!(wallet.keypair(0).unwrap().public_key, wallet.keypair(1).unwrap().public_key);
+
+        let other = HdWallet::from_phrase(&wallet.phrase(), "extra words").unwrap();
+        assert_ne!(wallet.keypair(0).unwrap().public_key, other.keypair(0).unwrap().public_key);
+    }
+
+    #[test]
+    fn test_derived_key_signs() {
+        let keypair = HdWallet::generate().unwrap().keypair(3).unwrap();
+        let signature = keypair.sign(b"spend").unwrap();
+        assert!(Dilithium3Keypair::verify(b"spend", &signature, &keypair.public_key).unwrap());
+    }
+
+    #[test]
+    fn test_rejects_bad_phrases() {
+        let phrase = HdWallet::generate().unwrap().phrase();
+        let (_, truncated) = phrase.split_once(' ').unwrap();
+        assert!(HdWallet::from_phrase(truncated, "").is_err());
+        assert!(HdWallet::from_phrase("not a recovery phrase", "").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+// src/inbound.rs
+//
+// Inbound gossip pipeline.
+// --------------------------------------------------------------
+// • Consumes `InEvent`s emitted by the NetworkManager
+// • Blocks   → NumiBlockchain::add_block; missing parents of orphan
+//   blocks are requested from the peer that relayed them
+// • Txs      → TransactionMempool::add_transaction
+// • Sync requests from peers are answered from the local chain
+// • Reports a gossipsub verdict for every message so that only data this
+//   node accepted is re-broadcast to the rest of the mesh
+//
+
+use std::sync::Arc;
+
+use dashmap::DashSet;
+use futures::{channel::mpsc, StreamExt};
+use libp2p::{gossipsub::MessageAcceptance, PeerId};
+
+use crate::{
+    block::{Block, BlockHash},
+    blockchain::NumiBlockchain,
+    error::{BlockchainError, InvalidBlockError},
+    mempool::ValidationResult,
+    network::{InEvent, NetworkHandle},
+    sync::{self, SyncRequest, SyncResponse},
+    transaction::Transaction,
+    RwLock,
+};
+
+// How many missing ancestors of an orphan we fetch before leaving the gap
+// to the sync manager.
+const MAX_PARENT_FETCH_DEPTH: usize = 16;
+
+pub struct InboundProcessor {
+    chain: Arc<RwLock<NumiBlockchain>>,
+    network: NetworkHandle,
+    fetching: Arc<DashSet<BlockHash>>, // parents currently being requested
+}
+
+impl InboundProcessor {
+    pub fn new(chain: Arc<RwLock<NumiBlockchain>>, network: NetworkHandle) -> Self {
+        Self { chain, network, fetching: Arc::new(DashSet::new()) }
+    }
+
+    /// Run until the network manager drops its sender.
+    pub async fn run(self, mut in_rx: mpsc::UnboundedReceiver<InEvent>) {
+        while let Some(event) = in_rx.next().await {
+            match event {
+                InEvent::Block { block, source, message_id } => {
+                    let acceptance = self.handle_block(block, source).await;
+                    let _ = self.network.report_validation(message_id, source, acceptance);
+                }
+                InEvent::Tx { tx, source, message_id } => {
+                    let acceptance = self.handle_tx(tx).await;
+                    let _ = self.network.report_validation(message_id, source, acceptance);
+                }
+                InEvent::SyncRequest { peer, request_id, request } => {
+                    log::debug!("Serving {request:?} to {peer}");
+                    let response = sync::serve_request(&self.chain.read(), request);
+                    let _ = self.network.respond(request_id, response);
+                }
+            }
+        }
+        log::info!("Inbound gossip channel closed");
+    }
+
+    async fn handle_block(&self, block: Block, source: PeerId) -> MessageAcceptance {
+        let height = block.header.height;
+        let parent = block.header.previous_hash;
+        let result = NumiBlockchain::add_block_to(&self.chain, block, Some(source)).await;
+        match result {
+            Ok(true) => {
+                log::info!("📥 Accepted block #{height} from network");
+                MessageAcceptance::Accept
+            }
+            Ok(false) => MessageAcceptance::Ignore, // already known
+            Err(BlockchainError::InvalidBlock(InvalidBlockError::StaleChain)) => {
+                // Valid data we cannot attach to our block tree – not the peer's fault.
+                log::debug!("Ignoring gossiped block #{height}: parent unknown");
+                MessageAcceptance::Ignore
+            }
+            Err(BlockchainError::InvalidBlock(InvalidBlockError::OrphanBlock)) => {
+                // Not forwarded: it cannot be fully validated without its parent.
+                log::debug!("Gossiped block #{height} is an orphan; fetching its parent from {source}");
+                self.fetch_missing_parents(parent, source);
+                MessageAcceptance::Ignore
+            }
+            Err(BlockchainError::InvalidBlock(InvalidBlockError::ReorgTooDeep { depth, .. })) => {
+                log::debug!("Ignoring gossiped block #{height}: would reorganize {depth} blocks");
+                MessageAcceptance::Ignore
+            }
+            Err(BlockchainError::InvalidBlock(e)) => {
+                log::warn!("❌ Rejected gossiped block #{height}: {e}");
+                MessageAcceptance::Reject
+            }
+            Err(e) => {
+                log::error!("Failed to process gossiped block #{height}: {e}");
+                MessageAcceptance::Ignore
+            }
+        }
+    }
+
+    /// Request `parent` from `peer`, walking further back while the fetched
+    /// blocks are themselves orphans.
+    fn fetch_missing_parents(&self, parent: BlockHash, peer: PeerId) {
+        if !self.fetching.insert(parent) {
+            return; // already being fetched
+        }
+        let chain = self.chain.clone();
+        let network = self.network.clone();
+        let fetching = self.fetching.clone();
+        tokio::spawn(async move {
+            let mut wanted = parent;
+            for _ in 0..MAX_PARENT_FETCH_DEPTH {
+                let block = match network.request(peer, SyncRequest::GetBlock { hash: wanted }).await {
+                    Ok(SyncResponse::Blocks(mut blocks)) if blocks.len() == 1 => blocks.remove(0),
+                    Ok(_) => {
+                        log::debug!("Peer {peer} does not have block {}", hex::encode(wanted));
+                        break;
+                    }
+                    Err(e) => {
+                        log::debug!("Fetching block {} from {peer} failed: {e}", hex::encode(wanted));
+                        break;
+                    }
+                };
+                let next = block.header.previous_hash;
+                let result = NumiBlockchain::add_block_to(&chain, block, Some(peer)).await;
+                match result {
+                    Err(BlockchainError::InvalidBlock(InvalidBlockError::OrphanBlock)) => wanted = next,
+                    Ok(_) => break,
+                    Err(e) => {
+                        log::debug!("Parent block from {peer} rejected: {e}");
+                        break;
+                    }
+                }
+            }
+            fetching.remove(&parent);
+        });
+    }
+
+    async fn handle_tx(&self, tx: Transaction) -> MessageAcceptance {
+        let tx_id = hex::encode(tx.id);
+        let mempool = self.chain.read().mempool_handle();
+        match mempool.add_transaction(tx).await {
+            Ok(result) if result.is_accepted() => {
+                log::debug!("📥 Accepted transaction {tx_id} from network");
+                MessageAcceptance::Accept
+            }
+            Ok(ValidationResult::DuplicateTransaction) => MessageAcceptance::Ignore,
+            Ok(ValidationResult::InvalidSignature)
+            | Ok(ValidationResult::TransactionTooLarge)
+            | Ok(ValidationResult::WrongChain) => {
+                log::warn!("❌ Rejected gossiped transaction {tx_id}: malformed");
+                MessageAcceptance::Reject
+            }
+            // Nonce, balance, fee and rate-limit outcomes depend on our local
+            // view, so the transaction is dropped without penalising the peer.
+            Ok(other) => {
+                log::debug!("Ignoring gossiped transaction {tx_id}: {other:?}");
+                MessageAcceptance::Ignore
+            }
+            Err(e) => {
+                log::warn!("❌ Rejected gossiped transaction {tx_id}: {e}");
+                MessageAcceptance::Reject
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+// src/legacy.rs
+//
+// Block files of nodes from before the sled store.
+// --------------------------------------------------------------
+// • Such nodes kept one bincode `block_<height>.bin` per block in the
+//   original layout: no chain id, no state root, recipients given as raw
+//   public keys
+// • Those blocks hash and sign differently from current ones, so they
+//   cannot be replayed under today's consensus rules
+// • Instead their final balances are carried over as the genesis accounts
+//   of a new chain (`numi-core migrate-legacy`)
+//
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use bincode::Options;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    block::BlockHash,
+    crypto::{Dilithium3Signature, Hash},
+    error::BlockchainError,
+    genesis::{GenesisAccount, GenesisAccounts, GenesisSpec},
+    Result,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegacyBlockHeader {
+    pub version: u32,
+    pub height: u64,
+    pub timestamp: DateTime<Utc>,
+    pub previous_hash: BlockHash,
+    pub merkle_root: Hash,
+    pub difficulty: u32,
+    pub nonce: u64,
+    pub miner_public_key: Vec<u8>,
+    pub block_signature: Option<Dilithium3Signature>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegacyBlock {
+    pub header: LegacyBlockHeader,
+    pub transactions: Vec<LegacyTransaction>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum LegacyTransactionType {
+    Transfer { to: Vec<u8>, amount: u64, memo: Option<String> },
+    MiningReward { block_height: u64, amount: u64 },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegacyTransaction {
+    pub id: [u8; 32],
+    pub from: Vec<u8>,
+    pub kind: LegacyTransactionType,
+    pub nonce: u64,
+    pub fee: u64,
+    pub timestamp: DateTime<Utc>,
+    pub valid_until: DateTime<Utc>,
+    pub signature: Option<Dilithium3Signature>,
+}
+
+/// Decode `bytes` as exactly one `T`; trailing bytes are an error, so a
+/// file in one layout is never mistaken for the other.
+pub fn decode_exact<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .deserialize(bytes)
+        .map_err(|e| BlockchainError::SerializationError(e.to_string()))
+}
+
+/// The `block_<height>.bin` files in `dir`, by height.
+pub fn block_files(dir: &Path) -> Result<BTreeMap<u64, std::path::PathBuf>> {
+    let mut files = BTreeMap::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().into_string().unwrap_or_default();
+        let Some(height) = name.strip_prefix("block_").and_then(|n| n.strip_suffix(".bin")) else { continue };
+        if let Ok(height) = height.parse::<u64>() {
+            files.insert(height, entry.path());
+        }
+    }
+    Ok(files)
+}
+
+/// Read the legacy chain in `dir`.  Heights must run from 0 without gaps.
+pub fn read_blocks(dir: &Path) -> Result<Vec<LegacyBlock>> {
+    let mut blocks = Vec::new();
+    for (height, path) in block_files(dir)? {
+        if height != blocks.len() as u64 {
+            return Err(BlockchainError::StorageError(format!(
+                "Legacy block files in {} have a gap before height {}",
+                dir.display(),
+                height
+            )));
+        }
+        let block: LegacyBlock = decode_exact(&std::fs::read(&path)?).map_err(|e| {
+            BlockchainError::SerializationError(format!("{} is not a legacy block: {e}", path.display()))
+        })?;
+        if block.header.height != height {
+            return Err(BlockchainError::StorageError(format!(
+                "{} holds block #{}",
+                path.display(),
+                block.header.height
+            )));
+        }
+        blocks.push(block);
+    }
+    if blocks.is_empty() {
+        return Err(BlockchainError::StorageError(format!("No legacy block files in {}", dir.display())));
+    }
+    Ok(blocks)
+}
+
+/// Balance of every public key after `blocks`, with the legacy rules:
+/// rewards credit their `from`, transfers move `amount` and burn `fee`.
+pub fn balances(blocks: &[LegacyBlock]) -> Result<BTreeMap<Vec<u8>, u64>> {
+    let mut balances: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for tx in blocks.iter().flat_map(|b| &b.transactions) {
+        match &tx.kind {
+            LegacyTransactionType::MiningReward { amount, .. } => {
+                *balances.entry(tx.from.clone()).or_default() += amount;
+            }
+            LegacyTransactionType::Transfer { to, amount, .. } => {
+                let spent = if tx.from == *to { tx.fee } else { amount + tx.fee };
+                let sender = balances.entry(tx.from.clone()).or_default();
+                *sender = sender.checked_sub(spent).ok_or_else(|| {
+                    BlockchainError::InvalidTransaction(format!(
+                        "Legacy transaction {} overspends its sender",
+                        hex::encode(tx.id)
+                    ))
+                })?;
+                if tx.from != *to {
+                    *balances.entry(to.clone()).or_default() += amount;
+                }
+            }
+        }
+    }
+    balances.retain(|_, balance| *balance > 0);
+    Ok(balances)
+}
+
+/// Genesis for `chain_id` funding every key with its balance at the tip of
+/// the legacy chain in `dir`.  Timestamp and difficulty are taken from the
+/// legacy blocks, so every node migrating the same files gets the same
+/// genesis.
+pub fn genesis_spec(dir: &Path, chain_id: &str) -> Result<GenesisSpec> {
+    let blocks = read_blocks(dir)?;
+    let tip = &blocks[blocks.len() - 1].header;
+    let accounts = balances(&blocks)?
+        .into_iter()
+        .map(|(key, balance)| GenesisAccount {
+            address: hex::encode(key),
+            balance,
+            description: Some("Balance carried over from the legacy chain".into()),
+        })
+        .collect();
+    let spec = GenesisSpec {
+        chain_id: chain_id.to_string(),
+        network_name: chain_id.to_string(),
+        timestamp: tip.timestamp,
+        difficulty: blocks[0].header.difficulty,
+        accounts: GenesisAccounts { accounts },
+    };
+    spec.validate()?;
+    Ok(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    // Mirrors of the legacy layout, for writing test files.
+    #[derive(Serialize)]
+    struct Header {
+        version: u32,
+        height: u64,
+        timestamp: DateTime<Utc>,
+        previous_hash: BlockHash,
+        merkle_root: Hash,
+        difficulty: u32,
+        nonce: u64,
+        miner_public_key: Vec<u8>,
+        block_signature: Option<Dilithium3Signature>,
+    }
+
+    #[derive(Serialize)]
+    enum Kind {
+        Transfer { to: Vec<u8>, amount: u64, memo: Option<String> },
+        MiningReward { block_height: u64, amount: u64 },
+    }
+
+    #[derive(Serialize)]
+    struct Tx {
+        id: [u8; 32],
+        from: Vec<u8>,
+        kind: Kind,
+        nonce: u64,
+        fee: u64,
+        timestamp: DateTime<Utc>,
+        valid_until: DateTime<Utc>,
+        signature: Option<Dilithium3Signature>,
+    }
+
+    fn write_block(dir: &Path, height: u64, transactions: Vec<Tx>) {
+        #[derive(Serialize)]
+        struct Block {
+            header: Header,
+            transactions: Vec<Tx>,
+        }
+        let header = Header {
+            version: 1,
+            height,
+            timestamp: Utc::now(),
+            previous_hash: [0; 32],
+            merkle_root: [0; 32],
+            difficulty: 3,
+            nonce: 0,
+            miner_public_key: vec![1],
+            block_signature: None,
+        };
+        let bytes = bincode::serialize(&Block { header, transactions }).unwrap();
+        std::fs::write(dir.join(format!("block_{height}.bin")), bytes).unwrap();
+    }
+
+    fn tx(from: u8, kind: Kind, fee: u64) -> Tx {
+        Tx {
+            id: [from; 32],
+            from: vec![from],
+            kind,
+            nonce: 0,
+            fee,
+            timestamp: Utc::now(),
+            valid_until: Utc::now(),
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn test_carries_balances_into_genesis() {
+        let dir = tempfile::tempdir().unwrap();
+        write_block(dir.path(), 0, vec![tx(1, Kind::MiningReward { block_height: 0, amount: 1_000 }, 0)]);
+        write_block(
+            dir.path(),
+            1,
+            vec![
+                tx(1, Kind::MiningReward { block_height: 1, amount: 50 }, 0),
+                tx(1, Kind::Transfer { to: vec![2], amount: 300, memo: None }, 2),
+            ],
+        );
+
+        let spec = genesis_spec(dir.path(), "migrated-1").unwrap();
+        assert_eq!(spec.difficulty, 3);
+        let balances: Vec<(String, u64)> =
+            spec.accounts.accounts.iter().map(|a| (a.address.clone(), a.balance)).collect();
+        assert_eq!(balances, vec![("01".to_string(), 748), ("02".to_string(), 300)]);
+        let reloaded = GenesisSpec::from_toml_str(&spec.to_toml_string().unwrap()).unwrap();
+        assert_eq!(reloaded.block().unwrap().calculate_hash(None).unwrap(), spec.block().unwrap().calculate_hash(None).unwrap());
+    }
+
+    #[test]
+    fn test_rejects_gaps_and_current_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        write_block(dir.path(), 0, vec![tx(1, Kind::MiningReward { block_height: 0, amount: 1 }, 0)]);
+        write_block(dir.path(), 2, vec![]);
+        assert!(read_blocks(dir.path()).is_err());
+
+        let current = crate::block::Block::new("dev", 0, [0; 32], vec![], 1, vec![1]);
+        std::fs::write(dir.path().join("block_2.bin"), bincode::serialize(&current).unwrap()).unwrap();
+        std::fs::rename(dir.path().join("block_2.bin"), dir.path().join("block_1.bin")).unwrap();
+        assert!(read_blocks(dir.path()).is_err());
+    }
+}
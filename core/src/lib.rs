@@ -1,20 +1,33 @@
+pub mod address;
 pub mod block;
 pub mod blockchain;
 pub mod config;
 pub mod crypto;
 pub mod error;
+pub mod fee_estimator;
+pub mod genesis;
+pub mod hd_wallet;
+pub mod inbound;
+pub mod legacy;
 pub mod mempool;
 pub mod miner;
 pub mod local_miner;
 pub mod mining_service;
+pub mod multisig;
 pub mod network;
+pub mod orphan_pool;
 pub mod rpc;
 pub mod secure_storage;
+pub mod state_overlay;
+pub mod state_tree;
 pub mod storage;
 pub mod stratum_server;
+pub mod sync;
 pub mod transaction;
+pub mod wallet;
 pub mod sync_lock;
 
+pub use address::{Address, Network};
 pub use block::{Block, BlockHeader};
 pub use transaction::{Transaction, TransactionType};
 pub use crypto::{Dilithium3Keypair, Dilithium3Signature, Hash};
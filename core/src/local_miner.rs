@@ -16,6 +16,7 @@ use crate::{
     block::Block,
     miner::Miner,
     config::ConsensusConfig,
+    network::NetworkHandle,
 };
 
 pub struct LocalMiner {
@@ -35,6 +36,7 @@ impl LocalMiner {
         threads: usize,
         consensus: ConsensusConfig,
         stratum_connected_rx: Receiver<bool>, // true = at least one miner
+        network: NetworkHandle,
     ) -> Self {
         let stop = Arc::new(AtomicBool::new(false));
         let (block_tx, block_rx) = unbounded::<Block>();
@@ -47,7 +49,7 @@ impl LocalMiner {
         let chain_clone = chain.clone();
         let stats_clone = stats.clone();
         tokio::spawn(async move {
-            Self::block_processor_task(chain_clone, block_rx, stats_clone).await;
+            Self::block_processor_task(chain_clone, network, block_rx, stats_clone).await;
         });
 
         // Spawn mining threads
@@ -81,6 +83,7 @@ impl LocalMiner {
     
     async fn block_processor_task(
         chain: Arc<RwLock<NumiBlockchain>>,
+        network: NetworkHandle,
         block_rx: Receiver<Block>,
         stats: Arc<MiningStats>,
     ) {
@@ -90,12 +93,16 @@ impl LocalMiner {
             let chain_clone = chain.clone();
             let block_clone = block.clone();
             let stats_clone = stats.clone();
+            let network_clone = network.clone();
             tokio::spawn(async move {
-                let result = chain_clone.write().add_block(block_clone).await;
+                let result = NumiBlockchain::add_block_to(&chain_clone, block_clone.clone(), None).await;
                 match result {
                     Ok(true) => {
                         stats_clone.blocks_found.fetch_add(1, Ordering::Relaxed);
                         log::info!("🎉 CPU-miner found valid block #{}!", height);
+                        if let Err(e) = network_clone.broadcast_block(block_clone) {
+                            log::warn!("Failed to broadcast block #{}: {}", height, e);
+                        }
                     }
                     Ok(false) => {
                         log::warn!("⚠️  CPU-miner block #{} was already known", height);
@@ -135,6 +142,7 @@ impl LocalMiner {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn mining_loop(
         _thread_id: usize,
         chain: Arc<RwLock<NumiBlockchain>>,
@@ -193,59 +201,22 @@ impl LocalMiner {
             current_tip_hash = new_tip_hash;
             last_mempool_tx_count = new_mempool_count;
 
-            // Build candidate block with proper miner public key
-            let (height, difficulty, txs, miner_public_key) = {
-                let bc = chain.read();
-                let miner_pk = miner.read().get_public_key();
-                (
-                    bc.get_current_height() + 1,
-                    bc.get_current_difficulty(),
-                    bc.get_transactions_for_block(256 * 1024, 10_000),
-                    miner_pk,
-                )
+            // Candidate block paying our miner key
+            let template = chain.read().block_template(miner.read().get_keypair(), 256 * 1024, 10_000);
+            let mut block = match template {
+                Ok(block) => block,
+                Err(e) => {
+                    log::error!("❌ Failed to build block template: {e}");
+                    continue;
+                }
             };
 
-            // ------------------------------------------------------------------
-            // Create mining-reward (coinbase) transaction and prepend it
-            // ------------------------------------------------------------------
-            use crate::transaction::{Transaction, TransactionType};
-            use crate::miner::WalletManager;
-
-            // Block subsidy according to halving schedule
-            let base_reward = WalletManager::calculate_mining_reward_with_config(height, &consensus);
-            // Sum of fees from included mempool transactions
-            let total_fees: u64 = txs.iter().map(|tx| tx.fee).sum();
-            let reward_amount = base_reward.saturating_add(total_fees);
-
-            let mut reward_tx = Transaction::new(
-                miner_public_key.clone(),
-                TransactionType::MiningReward {
-                    block_height: height,
-                    amount: reward_amount,
-                },
-                0,
-            );
-
-            if let Err(e) = reward_tx.sign(&miner.read().get_keypair()) {
-                log::error!("❌ Failed to sign reward transaction: {e}");
-                continue; // Skip this iteration and try again
-            }
-
-            // Combine reward + normal transactions (reward must be first)
-            let mut full_txs = Vec::with_capacity(1 + txs.len());
-            full_txs.push(reward_tx);
-            full_txs.extend(txs);
-
-            let mut block = Block::new(height, new_tip_hash, full_txs, difficulty, miner_public_key);
-            // Ensure Merkle root includes reward tx
-            block.header.merkle_root = Block::calculate_merkle_root(&block.transactions);
-
-            if let Err(e) = block.mine(&miner.read().get_keypair(), &consensus) {
+            if let Err(e) = block.mine(miner.read().get_keypair(), &consensus) {
                 log::error!("❌ Failed to mine block: {e}");
                 continue;
             }
 
-            if let Err(_) = block_tx.send(block) {
+            if block_tx.send(block).is_err() {
                 log::error!("Failed to send found block to processor");
             }
         }
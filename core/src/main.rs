@@ -1,18 +1,31 @@
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::signal;
 use numi_core::RwLock;
 use futures::channel::mpsc;
 use crossbeam::channel::bounded;
+use zeroize::Zeroizing;
 
 use numi_core::{
     config::Config,
     blockchain::NumiBlockchain,
     storage::BlockchainStorage,
-    rpc::{RpcServer, RateLimitConfig, AuthConfig, client::{show_status, show_balance, send_transaction}},
-    crypto::{Dilithium3Keypair, derive_address_from_public_key},
+    rpc::{RpcServer, RateLimitConfig, AuthConfig, client::{show_status, show_balance, show_history, show_nonce, send_transaction, broadcast_transaction}},
+    crypto::Dilithium3Keypair,
+    address::{Address, Network},
+    hd_wallet::{HdWallet, MNEMONIC_WORDS},
+    wallet::{
+        create_wallet, export_pem, import_account, list_accounts, load_account, load_wallet, migrate_wallet,
+        write_signed_transaction, PassphraseSource, DEFAULT_KEY_ID,
+    },
+    transaction::{Transaction, TransactionType},
+    fee_estimator::DEFAULT_TARGET_BLOCKS,
+    mempool::{MEMPOOL_FILE, MEMPOOL_SAVE_INTERVAL},
     network::NetworkManager,
+    inbound::InboundProcessor,
+    legacy,
+    sync::SyncManager,
     mining_service::MiningService,
     miner::Miner,
     local_miner::LocalMiner,
@@ -35,6 +48,9 @@ struct Cli {
     
     #[arg(short, long)]
     verbose: bool,
+
+    #[arg(long, global = true, help = "Read the wallet passphrase from this file instead of NUMI_WALLET_PASSPHRASE or a prompt")]
+    passphrase_file: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -68,10 +84,20 @@ enum Commands {
         amount: f64,
         #[arg(long, help = "Optional memo")]
         memo: Option<String>,
+        #[arg(long, default_value_t = DEFAULT_TARGET_BLOCKS, help = "Pay a fee expected to confirm within this many blocks")]
+        target_blocks: u32,
     },
     
     /// Show mining information (Stratum V2)
     Mining,
+
+    /// Carry the balances of pre-chain-id block files into a genesis file
+    MigrateLegacy {
+        #[arg(long, help = "Directory of the legacy block_<height>.bin files (default: <data_directory>/blocks)")]
+        blocks: Option<PathBuf>,
+        #[arg(long, default_value = "legacy-genesis.toml", help = "Output genesis file")]
+        output: PathBuf,
+    },
 }
 
 #[derive(Subcommand)]
@@ -80,44 +106,182 @@ enum WalletCommands {
     Create {
         #[arg(long, default_value = "wallet.json", help = "Output file path")]
         output: PathBuf,
+        #[arg(long, help = "Derive the key from a new recovery phrase")]
+        mnemonic: bool,
+    },
+
+    /// Recreate a wallet from its recovery phrase
+    Restore {
+        #[arg(long, default_value = "wallet.json", help = "Output file path")]
+        output: PathBuf,
+        #[arg(long, default_value_t = 0, help = "Account number to derive")]
+        index: u32,
     },
     
+    /// Encrypt a plaintext wallet file from an older release in place
+    Migrate {
+        #[arg(help = "Wallet file path")]
+        wallet: PathBuf,
+    },
+
     /// Check wallet balance
     Balance {
         #[arg(help = "Wallet address or file path")]
         address: String,
     },
+
+    /// List the transactions of a wallet, newest first
+    History {
+        #[arg(help = "Wallet address or file path")]
+        address: String,
+        #[arg(long, default_value_t = 0, help = "Number of newest entries to skip")]
+        offset: usize,
+        #[arg(long, default_value_t = 20, help = "Number of entries to show")]
+        limit: usize,
+    },
+
+    /// List the accounts in a wallet file
+    List {
+        #[arg(help = "Wallet file path")]
+        wallet: PathBuf,
+    },
+
+    /// Write an account's keys unencrypted to a PEM file
+    Export {
+        #[arg(help = "Wallet file path")]
+        wallet: PathBuf,
+        #[arg(long, default_value = DEFAULT_KEY_ID, help = "Account to export")]
+        account: String,
+        #[arg(long, help = "PEM file to create")]
+        output: PathBuf,
+    },
+
+    /// Add the keys of a PEM file to a wallet as a new account
+    Import {
+        #[arg(help = "PEM file path")]
+        pem: PathBuf,
+        #[arg(long, default_value = "wallet.json", help = "Wallet file path, created if missing")]
+        wallet: PathBuf,
+        #[arg(long, default_value = DEFAULT_KEY_ID, help = "Name of the new account")]
+        account: String,
+    },
+
+    /// Sign a transfer offline and write it to a file for `wallet broadcast`
+    SignTx {
+        #[arg(long, help = "Wallet file path")]
+        wallet: PathBuf,
+        #[arg(long, default_value = DEFAULT_KEY_ID, help = "Account to spend from")]
+        account: String,
+        #[arg(help = "Recipient address")]
+        to: String,
+        #[arg(help = "Amount to send (NUMI)")]
+        amount: f64,
+        #[arg(long, help = "Account nonce to use, as shown by `wallet nonce`")]
+        nonce: u64,
+        #[arg(long, help = "Fee in NANO (defaults to the minimum)")]
+        fee: Option<u64>,
+        #[arg(long, help = "Optional memo")]
+        memo: Option<String>,
+        #[arg(long, help = "Hours the transaction may wait before it is broadcast (default 1)")]
+        valid_for: Option<u32>,
+        #[arg(long, default_value = "signed-tx.hex", help = "Output file path")]
+        output: PathBuf,
+    },
+
+    /// Submit a transaction file written by `wallet sign-tx` to the node
+    Broadcast {
+        #[arg(help = "Signed transaction file path")]
+        file: PathBuf,
+    },
+
+    /// Show the nonces of a wallet: confirmed, pending and next to use
+    Nonce {
+        #[arg(help = "Wallet address or file path")]
+        address: String,
+    },
 }
 
 // CLI subcommand handlers
-async fn handle_wallet_create(output: PathBuf) -> Result<()> {
+async fn handle_wallet_create(output: PathBuf, mnemonic: bool, config: Config, passphrase: &PassphraseSource) -> Result<()> {
     println!("🔑 Creating new wallet...");
-    let keypair = Dilithium3Keypair::new()?;
-    
-    // Ensure parent directory exists
-    if let Some(parent) = output.parent() {
-        std::fs::create_dir_all(parent)?;
-    }
+    let (keypair, phrase) = if mnemonic {
+        let wallet = HdWallet::generate()?;
+        (wallet.keypair(0)?, Some(wallet.phrase()))
+    } else {
+        (Dilithium3Keypair::new()?, None)
+    };
     
-    keypair.save_to_file(&output)?;
-    let address = derive_address_from_public_key(&keypair.public_key_bytes())?;
+    create_wallet(&output, &keypair, passphrase)?;
+    let address = wallet_address(&keypair, &config);
     
     println!("✅ Wallet created successfully!");
     println!("   File: {}", output.display());
     println!("   Address: {}", address);
     println!();
-    println!("⚠️  IMPORTANT: Keep this wallet file secure!");
-    println!("   Loss of this file means loss of funds.");
+    if let Some(phrase) = phrase {
+        println!("📝 Recovery phrase ({} words):", MNEMONIC_WORDS);
+        println!();
+        println!("   {}", phrase.as_str());
+        println!();
+        println!("⚠️  IMPORTANT: Write these words down and keep them offline!");
+        println!("   They restore this wallet with `wallet restore`; anyone who");
+        println!("   has them can spend its funds. They are not shown again.");
+    } else {
+        println!("⚠️  IMPORTANT: Keep this wallet file and its passphrase safe!");
+        println!("   Losing either means losing the funds.");
+    }
     
     Ok(())
 }
 
-async fn handle_wallet_balance(address_or_file: String, config: Config) -> Result<()> {
+async fn handle_wallet_restore(output: PathBuf, index: u32, config: Config, passphrase: &PassphraseSource) -> Result<()> {
+    if output.exists() {
+        return Err(BlockchainError::InvalidArgument(format!(
+            "{} already exists; choose another --output",
+            output.display()
+        )));
+    }
+
+    // Read from stdin so the phrase stays out of shell history.
+    println!("Enter the {}-word recovery phrase:", MNEMONIC_WORDS);
+    let mut phrase = Zeroizing::new(String::new());
+    std::io::stdin().read_line(&mut phrase)?;
+    let keypair = HdWallet::from_phrase(phrase.trim(), "")?.keypair(index)?;
+    create_wallet(&output, &keypair, passphrase)?;
+
+    println!("✅ Wallet restored!");
+    println!("   File: {}", output.display());
+    println!("   Account: {}", index);
+    println!("   Address: {}", wallet_address(&keypair, &config));
+    Ok(())
+}
+
+async fn handle_wallet_migrate(wallet: PathBuf, config: Config, passphrase: &PassphraseSource) -> Result<()> {
+    println!("🔐 Encrypting {}...", wallet.display());
+    let keypair = migrate_wallet(&wallet, passphrase)?;
+    println!("✅ Wallet encrypted in place.");
+    println!("   Address: {}", wallet_address(&keypair, &config));
+    Ok(())
+}
+
+fn handle_migrate_legacy(blocks: Option<PathBuf>, output: PathBuf, config: Config) -> Result<()> {
+    let blocks = blocks.unwrap_or_else(|| config.storage.data_directory.join("blocks"));
+    println!("📦 Reading legacy blocks from {}...", blocks.display());
+    let spec = legacy::genesis_spec(&blocks, &config.consensus.chain_id)?;
+    std::fs::write(&output, spec.to_toml_string()?)?;
+    println!("✅ Genesis written to {}", output.display());
+    println!("   Accounts: {}", spec.accounts.accounts.len());
+    println!("   Supply:   {:.2} NUMI", spec.total_supply() as f64 / 100.0);
+    println!("   Set consensus.genesis_file to this file and move the old block files aside before starting the node.");
+    Ok(())
+}
+
+async fn handle_wallet_balance(address_or_file: String, config: Config, passphrase: &PassphraseSource) -> Result<()> {
     // Check if it's a file path or an address
-    if std::path::Path::new(&address_or_file).exists() {
+    if Path::new(&address_or_file).exists() {
         // It's a wallet file - load it and get the address
-        let keypair = Dilithium3Keypair::load_from_file(&address_or_file)?;
-        let address = derive_address_from_public_key(&keypair.public_key_bytes())?;
+        let keypair = load_wallet(Path::new(&address_or_file), passphrase)?;
+        let address = wallet_address(&keypair, &config);
         show_balance_for_address(address, config).await
     } else {
         // It's an address directly
@@ -129,8 +293,122 @@ async fn show_balance_for_address(address: String, config: Config) -> Result<()>
     show_balance(config, address).await
 }
 
-async fn handle_send(wallet: PathBuf, to: String, amount: f64, memo: Option<String>, config: Config) -> Result<()> {
-    send_transaction(config, wallet, to, amount, memo).await
+async fn handle_wallet_history(address_or_file: String, offset: usize, limit: usize, config: Config, passphrase: &PassphraseSource) -> Result<()> {
+    let address = if Path::new(&address_or_file).exists() {
+        let keypair = load_wallet(Path::new(&address_or_file), passphrase)?;
+        wallet_address(&keypair, &config)
+    } else {
+        address_or_file
+    };
+    show_history(config, address, offset, limit).await
+}
+
+async fn handle_wallet_list(wallet: PathBuf, config: Config, passphrase: &PassphraseSource) -> Result<()> {
+    let network = Network::from_chain_id(&config.consensus.chain_id);
+    println!("Wallet: {}", wallet.display());
+    for (account, address) in list_accounts(&wallet, passphrase)? {
+        println!("   {:<16} {}", account, address.encode(network));
+    }
+    Ok(())
+}
+
+async fn handle_wallet_export(wallet: PathBuf, account: String, output: PathBuf, config: Config, passphrase: &PassphraseSource) -> Result<()> {
+    let keypair = load_account(&wallet, &account, passphrase)?;
+    export_pem(&output, &keypair)?;
+    println!("✅ Account '{}' exported", account);
+    println!("   File: {}", output.display());
+    println!("   Address: {}", wallet_address(&keypair, &config));
+    println!();
+    println!("⚠️  IMPORTANT: The PEM file is NOT encrypted. Import it into a");
+    println!("   wallet or keep it offline, and delete it when done.");
+    Ok(())
+}
+
+async fn handle_wallet_import(pem: PathBuf, wallet: PathBuf, account: String, config: Config, passphrase: &PassphraseSource) -> Result<()> {
+    let keypair = Dilithium3Keypair::load_from_file(&pem)?;
+    import_account(&wallet, &account, &keypair, passphrase)?;
+    println!("✅ Imported as account '{}'", account);
+    println!("   Wallet: {}", wallet.display());
+    println!("   Address: {}", wallet_address(&keypair, &config));
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_wallet_sign_tx(
+    wallet: PathBuf,
+    account: String,
+    to: String,
+    amount: f64,
+    nonce: u64,
+    fee: Option<u64>,
+    memo: Option<String>,
+    valid_for: Option<u32>,
+    output: PathBuf,
+    config: Config,
+    passphrase: &PassphraseSource,
+) -> Result<()> {
+    let network = Network::from_chain_id(&config.consensus.chain_id);
+    let recipient = Address::parse(&to, network)?;
+    // NUMI to NANO in integer units, as `send` does.
+    let amount_raw = (amount * 100.0).round() as u64;
+    let keypair = load_account(&wallet, &account, passphrase)?;
+
+    let mut tx = Transaction::new(
+        &config.consensus.chain_id,
+        keypair.public_key.clone(),
+        TransactionType::Transfer { to: recipient, amount: amount_raw, memo },
+        nonce,
+    );
+    // Offline signing may happen long before the transaction is carried to
+    // a node, so the window can be widened before it is signed.
+    if let Some(hours) = valid_for {
+        if hours == 0 {
+            return Err(BlockchainError::InvalidArgument("--valid-for must be at least 1 hour".into()));
+        }
+        let valid_until = tx.timestamp + chrono::Duration::hours(hours.into());
+        tx = tx.with_valid_until(valid_until);
+    }
+    if let Some(fee) = fee {
+        if fee < tx.fee {
+            return Err(BlockchainError::InvalidArgument(format!(
+                "Fee {} NANO is below the minimum of {} NANO",
+                fee, tx.fee
+            )));
+        }
+        tx.fee = fee;
+    }
+    tx.sign(&keypair)?;
+    write_signed_transaction(&output, &tx)?;
+
+    println!("✅ Transaction signed");
+    println!("   File: {}", output.display());
+    println!("   From: {}", wallet_address(&keypair, &config));
+    println!("   To: {}", recipient.encode(network));
+    println!("   Amount: {:.2} NUMI", amount_raw as f64 / 100.0);
+    println!("   Fee: {} NANO", tx.fee);
+    println!("   Nonce: {}", tx.nonce);
+    println!("   Transaction ID: {}", hex::encode(tx.id));
+    println!("   Broadcast before: {}", tx.valid_until.to_rfc3339());
+    Ok(())
+}
+
+async fn handle_wallet_nonce(address_or_file: String, config: Config, passphrase: &PassphraseSource) -> Result<()> {
+    let address = if Path::new(&address_or_file).exists() {
+        let keypair = load_wallet(Path::new(&address_or_file), passphrase)?;
+        wallet_address(&keypair, &config)
+    } else {
+        address_or_file
+    };
+    show_nonce(config, address).await
+}
+
+/// Address of a wallet's key on the configured network.
+fn wallet_address(keypair: &Dilithium3Keypair, config: &Config) -> String {
+    Address::from_public_key(keypair.public_key_bytes()).encode(Network::from_chain_id(&config.consensus.chain_id))
+}
+
+async fn handle_send(wallet: PathBuf, to: String, amount: f64, memo: Option<String>, target_blocks: u32, config: Config, passphrase: &PassphraseSource) -> Result<()> {
+    send_transaction(config, wallet, passphrase, to, amount, memo, target_blocks).await
 }
 
 async fn handle_mining_info(config: Config) -> Result<()> {
@@ -182,17 +460,31 @@ async fn main() -> Result<()> {
         config.storage.data_directory = data_dir;
     }
     
+    let passphrase = PassphraseSource::from_file_or_prompt(cli.passphrase_file);
+    
     match cli.command {
-        Commands::Node { stratum, mining, threads } => start_node(stratum, mining, threads, config).await?,
+        Commands::Node { stratum, mining, threads } => start_node(stratum, mining, threads, config, &passphrase).await?,
         Commands::Status => show_status(config).await?,
         Commands::Wallet { wallet_cmd } => {
             match wallet_cmd {
-                WalletCommands::Create { output } => handle_wallet_create(output).await?,
-                WalletCommands::Balance { address } => handle_wallet_balance(address, config).await?,
+                WalletCommands::Create { output, mnemonic } => handle_wallet_create(output, mnemonic, config, &passphrase).await?,
+                WalletCommands::Restore { output, index } => handle_wallet_restore(output, index, config, &passphrase).await?,
+                WalletCommands::Migrate { wallet } => handle_wallet_migrate(wallet, config, &passphrase).await?,
+                WalletCommands::Balance { address } => handle_wallet_balance(address, config, &passphrase).await?,
+                WalletCommands::History { address, offset, limit } => handle_wallet_history(address, offset, limit, config, &passphrase).await?,
+                WalletCommands::List { wallet } => handle_wallet_list(wallet, config, &passphrase).await?,
+                WalletCommands::Export { wallet, account, output } => handle_wallet_export(wallet, account, output, config, &passphrase).await?,
+                WalletCommands::Import { pem, wallet, account } => handle_wallet_import(pem, wallet, account, config, &passphrase).await?,
+                WalletCommands::SignTx { wallet, account, to, amount, nonce, fee, memo, valid_for, output } => {
+                    handle_wallet_sign_tx(wallet, account, to, amount, nonce, fee, memo, valid_for, output, config, &passphrase).await?
+                }
+                WalletCommands::Broadcast { file } => broadcast_transaction(config, &file).await?,
+                WalletCommands::Nonce { address } => handle_wallet_nonce(address, config, &passphrase).await?,
             }
         },
-        Commands::Send { wallet, to, amount, memo } => handle_send(wallet, to, amount, memo, config).await?,
+        Commands::Send { wallet, to, amount, memo, target_blocks } => handle_send(wallet, to, amount, memo, target_blocks, config, &passphrase).await?,
         Commands::Mining => handle_mining_info(config).await?,
+        Commands::MigrateLegacy { blocks, output } => handle_migrate_legacy(blocks, output, config)?,
     }
     
     Ok(())
@@ -213,7 +505,7 @@ async fn load_config(cli: &Cli) -> Result<Config> {
     }
 }
 
-async fn start_node(stratum: bool, mining: bool, threads: Option<usize>, mut config: Config) -> Result<()> {
+async fn start_node(stratum: bool, mining: bool, threads: Option<usize>, mut config: Config, passphrase: &PassphraseSource) -> Result<()> {
     config.mining.enabled = stratum;
     config.mining.local_mining_enabled = mining;
     if let Some(t) = threads {
@@ -236,23 +528,63 @@ async fn start_node(stratum: bool, mining: bool, threads: Option<usize>, mut con
 
     log::info!("Starting NumiCoin node...");
     
+    // Unlock the miner wallet before anything else starts, so a passphrase
+    // prompt is not buried in log output
+    let miner = Arc::new(RwLock::new(Miner::new(&config, passphrase)?));
+    
     // Initialize storage and load blockchain
-    let storage = Arc::new(BlockchainStorage::new(&config.storage.data_directory)?);
+    let mut storage = BlockchainStorage::new(&config.storage.data_directory)?;
+    storage.set_address_index(config.storage.address_index)?;
+    let storage = Arc::new(storage);
     let blockchain = Arc::new(RwLock::new(
-        NumiBlockchain::load_from_storage(&storage, config.consensus.clone()).await?
+        NumiBlockchain::load_from_storage(&storage, config.consensus.clone(), miner.read().get_keypair().clone()).await?
     ));
+
+    // Restore pending transactions saved by the previous run
+    let mempool = blockchain.read().mempool_handle();
+    let mempool_path = config.storage.data_directory.join(MEMPOOL_FILE);
+    match mempool.load_from_file(&mempool_path).await {
+        Ok(0) => {}
+        Ok(n) => log::info!("Restored {} pending transaction(s) from {}", n, mempool_path.display()),
+        Err(e) => log::warn!("Could not restore mempool from {}: {}", mempool_path.display(), e),
+    }
+    {
+        let mempool = mempool.clone();
+        let mempool_path = mempool_path.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MEMPOOL_SAVE_INTERVAL);
+            interval.tick().await; // the first tick fires immediately
+            loop {
+                interval.tick().await;
+                if let Err(e) = mempool.save_to_file(&mempool_path).await {
+                    log::warn!("Failed to save mempool: {}", e);
+                }
+            }
+        });
+    }
     
     // Initialize network manager
-    let (in_tx, _in_rx) = mpsc::unbounded();
-    let (network_manager, network_handle) = NetworkManager::new(&config.network, in_tx)?;
+    let (in_tx, in_rx) = mpsc::unbounded();
+    let genesis_hash = blockchain.read().genesis_hash();
+    let (network_manager, network_handle) =
+        NetworkManager::new(&config.network, &config.consensus.chain_id, &genesis_hash, in_tx)?;
 
     // Spawn the network manager in the background (event processing)
     tokio::spawn(async move {
         network_manager.run().await;
     });
-    
-    // Initialize miner
-    let miner = Arc::new(RwLock::new(Miner::new(&config)?));
+
+    // Validate and apply blocks / transactions received over gossip
+    let inbound = InboundProcessor::new(blockchain.clone(), network_handle.clone());
+    tokio::spawn(async move {
+        inbound.run(in_rx).await;
+    });
+
+    // Download missing history from peers (headers first, then bodies)
+    let (sync_manager, sync_handle) = SyncManager::new(blockchain.clone(), network_handle.clone());
+    tokio::spawn(async move {
+        sync_manager.run().await;
+    });
     
     // Create channel for Stratum connection tracking
     let (stratum_signal_tx, stratum_signal_rx) = bounded::<bool>(1);
@@ -266,6 +598,7 @@ async fn start_node(stratum: bool, mining: bool, threads: Option<usize>, mut con
             config.mining.cpu_threads,
             config.consensus.clone(),
             stratum_signal_rx,
+            network_handle.clone(),
         ))
     } else {
         None
@@ -299,7 +632,8 @@ async fn start_node(stratum: bool, mining: bool, threads: Option<usize>, mut con
         config.rpc.clone(),
         network_handle.clone(),
         miner.clone(),
-    )?;
+    )?
+    .with_sync(sync_handle);
     
     // Start RPC server in background
     let rpc_port = config.rpc.port;
@@ -348,6 +682,11 @@ async fn start_node(stratum: bool, mining: bool, threads: Option<usize>, mut con
                 log::info!("🛑 Stopping local miner...");
                 miner.shutdown();
             }
+
+            match mempool.save_to_file(&mempool_path).await {
+                Ok(n) => log::info!("Saved {} pending transaction(s) to {}", n, mempool_path.display()),
+                Err(e) => log::error!("Failed to save mempool: {}", e),
+            }
         }
     }
     
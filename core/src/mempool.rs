@@ -4,27 +4,41 @@
 // -------------------------------------------------
 // • Pure Rust, no unsafe, lock-free reads via DashMap / parking_lot
 // • Fee-rate + age weighted priority queue (LWAPQ¹)
+// • Per-sender queues ordered by nonce: transactions beyond a nonce gap are
+//   held until the gap is filled, and block templates never skip a nonce
 // • Rate-limit & size-limit eviction
+// • Records how long mined transactions waited, for fee estimation
+// • Saved to the data directory and re-validated on start-up; entries keep
+//   their admission time, so age decay carries across restarts
 //
 // ¹ LWAPQ = Log-Weighted Age Penalty Queue: fee_per_byte is weighted by an
 //   exponential age decay so old low-fee spam cannot clog the pool indefinitely.
 
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
-    sync::{Arc, Weak},
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
+use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use crate::RwLock;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    blockchain::NumiBlockchain,
+    address::Address,
+    blockchain::AccountState,
     config::ConsensusConfig,
     error::BlockchainError,
+    fee_estimator::{fee_per_mib, FeeEstimate, FeeEstimator, FeeSample},
+    state_overlay::StateOverlay,
     transaction::{
-        Transaction, TransactionId,
+        LockTime, Transaction, TransactionId,
     },
     Result,
 };
@@ -71,8 +85,38 @@ pub enum ValidationResult {
     FeeTooLow { minimum: u64, got: u64 },
     AccountSpamming { rate_limit: u64 },
     TransactionExpired,
+    WrongChain,
+    TransactionLocked { lock_until: LockTime },
+    /// Accepted in place of the pending transaction with the same sender
+    /// and nonce (replace-by-fee)
+    Replaced { previous: TransactionId },
+    /// Same sender and nonce as a pending transaction, without the fee bump
+    /// needed to replace it
+    ReplacementFeeTooLow { minimum: u64, got: u64 },
+    /// Accepted, but held until the sender's transaction with
+    /// `missing_nonce` arrives
+    Pending { missing_nonce: u64 },
+}
+
+impl ValidationResult {
+    /// The transaction is now in the mempool.
+    pub fn is_accepted(&self) -> bool {
+        matches!(
+            self,
+            ValidationResult::Valid | ValidationResult::Replaced { .. } | ValidationResult::Pending { .. }
+        )
+    }
 }
 
+/// A replacement must raise the fee by this much (at least the minimum fee).
+pub const RBF_MIN_FEE_BUMP_PERCENT: u64 = 10;
+/// How far past the confirmed nonce a sender may queue transactions.
+pub const MAX_NONCE_GAP: u64 = 64;
+/// File in the data directory holding the saved mempool.
+pub const MEMPOOL_FILE: &str = "mempool.dat";
+/// How often a running node saves its mempool.
+pub const MEMPOOL_SAVE_INTERVAL: Duration = Duration::from_secs(300);
+
 /// ---------------------------------------------------------------------
 /// Mempool statistics snapshot (for RPC / monitoring)
 /// ---------------------------------------------------------------------
@@ -93,11 +137,20 @@ pub struct MempoolStats {
 struct Entry {
     tx: Transaction,
     added: Instant,
+    height: u64, // chain height at admission
     size: usize,
     fee_rate: u64,
     key: PriorityKey,
 }
 
+/// A pooled transaction as written to `MEMPOOL_FILE`.
+#[derive(Serialize, Deserialize)]
+struct SavedEntry {
+    tx: Transaction,
+    added_at: DateTime<Utc>,
+    height: u64,
+}
+
 /// ---------------------------------------------------------------------
 /// TransactionMempool
 /// ---------------------------------------------------------------------
@@ -105,9 +158,10 @@ pub struct TransactionMempool {
     // Core structures
     queue: Arc<RwLock<BTreeMap<PriorityKey, TransactionId>>>,
     map: Arc<DashMap<TransactionId, Entry>>,
-    by_account: Arc<DashMap<Vec<u8>, HashSet<TransactionId>>>,
-    nonces: Arc<DashMap<Vec<u8>, u64>>,
-    blockchain: Option<Weak<RwLock<NumiBlockchain>>>,
+    by_account: Arc<DashMap<Vec<u8>, BTreeMap<u64, TransactionId>>>, // sender address → nonce → tx
+    nonces: Arc<DashMap<Vec<u8>, u64>>,                               // confirmed (on-chain) nonces
+    balances: Arc<DashMap<Vec<u8>, u64>>,                             // confirmed (on-chain) balances
+    chain_synced: Arc<AtomicBool>,                                    // balances known (a chain pushed them)
 
     // Limits / config
     cfg: ConsensusConfig,
@@ -121,6 +175,8 @@ pub struct TransactionMempool {
     rejects_1h: Arc<RwLock<usize>>,
     submissions: Arc<DashMap<Vec<u8>, Vec<Instant>>>,
     last_hour_tick: Arc<RwLock<Instant>>,
+    estimator: Arc<RwLock<FeeEstimator>>,
+    tip_height: Arc<RwLock<u64>>,
 }
 
 impl Default for TransactionMempool {
@@ -146,28 +202,37 @@ impl TransactionMempool {
             map: Arc::new(DashMap::new()),
             by_account: Arc::new(DashMap::new()),
             nonces: Arc::new(DashMap::new()),
-            blockchain: None,
+            balances: Arc::new(DashMap::new()),
+            chain_synced: Arc::new(AtomicBool::new(false)),
             bytes_used: Arc::new(RwLock::new(0)),
             rejects_1h: Arc::new(RwLock::new(0)),
             submissions: Arc::new(DashMap::new()),
             last_hour_tick: Arc::new(RwLock::new(Instant::now())),
+            estimator: Arc::new(RwLock::new(FeeEstimator::new())),
+            tip_height: Arc::new(RwLock::new(0)),
         }
     }
 
-    pub fn attach_chain(&mut self, chain: &Arc<RwLock<NumiBlockchain>>) {
-        self.blockchain = Some(Arc::downgrade(chain));
-    }
-
     /* ---------------- admission ------------------- */
     pub async fn add_transaction(&self, tx: Transaction) -> Result<ValidationResult> {
         let id = tx.id;
-        let sender = &tx.from;
+        let sender = &tx.sender().to_bytes();
 
         // dup check
         if self.map.contains_key(&id) {
             return Ok(ValidationResult::DuplicateTransaction);
         }
 
+        // replace-by-fee: a pending tx with the same nonce must be outbid
+        let replaced = self.pending_with_nonce(sender, tx.nonce);
+        if let Some((_, old_fee, _)) = replaced {
+            let minimum = self.replacement_min_fee(old_fee);
+            if tx.fee < minimum {
+                *self.rejects_1h.write() += 1;
+                return Ok(ValidationResult::ReplacementFeeTooLow { minimum, got: tx.fee });
+            }
+        }
+
         // structural / sig / balance / fee checks
         let v = self.validate(&tx).await?;
         if v != ValidationResult::Valid {
@@ -187,12 +252,18 @@ impl TransactionMempool {
 
         let fee_rate = if size == 0 { 0 } else { tx.fee.div_ceil(size as u64) };
 
-        if !self.can_fit(size, fee_rate) && !self.evict_for(size, fee_rate).await {
+        // A replacement only needs room for what it adds.
+        let needed = size.saturating_sub(replaced.map_or(0, |(_, _, old_size)| old_size));
+        if needed > 0 && !self.can_fit(needed, fee_rate) && !self.evict_for(needed, fee_rate).await {
             return Ok(ValidationResult::FeeTooLow {
                 minimum: self.dynamic_min_fee(),
                 got: fee_rate,
             });
         }
+        if let Some((previous, ..)) = replaced {
+            self.remove_transactions(&[previous]);
+            log::debug!("Replaced mempool transaction {} by {}", hex::encode(previous), hex::encode(id));
+        }
 
         // build entry & priority key
         let key = PriorityKey {
@@ -203,6 +274,7 @@ impl TransactionMempool {
         let entry = Entry {
             tx: tx.clone(),
             added: Instant::now(),
+            height: self.chain_height(),
             size,
             fee_rate,
             key: key.clone(),
@@ -217,39 +289,64 @@ impl TransactionMempool {
         self.by_account
             .entry(sender.clone())
             .or_default()
-            .insert(id);
-        self.nonces
-            .entry(sender.clone())
-            .and_modify(|n| *n = (*n).max(tx.nonce))
-            .or_insert(tx.nonce);
+            .insert(tx.nonce, id);
         *self.bytes_used.write() += size;
         self.record_submission(sender).await;
 
-        Ok(ValidationResult::Valid)
+        Ok(match (replaced, self.missing_nonce(sender, tx.nonce)) {
+            (Some((previous, ..)), _) => ValidationResult::Replaced { previous },
+            (None, Some(missing_nonce)) => ValidationResult::Pending { missing_nonce },
+            (None, None) => ValidationResult::Valid,
+        })
     }
 
     /* ---------------- block selection ------------ */
+    /// Highest-priority transactions that fit in a block on top of
+    /// `accounts`, each sender's in nonce order.  Once a chain has pushed its
+    /// state, every pick is applied to an overlay of `accounts`; one that
+    /// does not apply is left out together with the sender's later nonces.
     pub fn select_for_block(
         &self,
+        accounts: &DashMap<Vec<u8>, AccountState>,
         max_block_bytes: usize,
         max_block_txs: usize,
     ) -> Vec<Transaction> {
         self.refresh_priorities();
+        let checked = self.chain_synced.load(Ordering::Acquire);
+        let mut overlay = StateOverlay::new(accounts);
+
+        // Only the next nonce of each sender is a candidate; taking it makes
+        // the sender's following nonce one.
+        let mut candidates = BinaryHeap::new();
+        for queue in self.by_account.iter() {
+            let next = self.confirmed_nonce(queue.key()) + 1;
+            if let Some(ent) = queue.get(&next).and_then(|id| self.map.get(id)) {
+                candidates.push(Reverse((ent.key.clone(), queue.key().clone(), next)));
+            }
+        }
 
         let mut selected = Vec::new();
         let mut used = 0;
-        let q = self.queue.read();
-
-        for (_, id) in q.iter() {
+        while let Some(Reverse((_, sender, nonce))) = candidates.pop() {
             if selected.len() >= max_block_txs {
                 break;
             }
-            if let Some(ent) = self.map.get(id) {
-                if used + ent.size > max_block_bytes {
+            let Some(ent) = self.pending_entry(&sender, nonce) else { continue };
+            // Later nonces of this sender cannot go in without it.
+            if used + ent.size > max_block_bytes {
+                continue;
+            }
+            if checked {
+                if let Err(e) = overlay.apply(&ent.tx) {
+                    log::debug!("Leaving {} out of the block template: {e}", hex::encode(ent.tx.id));
                     continue;
                 }
-                selected.push(ent.tx.clone());
-                used += ent.size;
+            }
+            selected.push(ent.tx.clone());
+            used += ent.size;
+            drop(ent);
+            if let Some(next) = self.pending_entry(&sender, nonce + 1) {
+                candidates.push(Reverse((next.key.clone(), sender, nonce + 1)));
             }
         }
 
@@ -257,19 +354,21 @@ impl TransactionMempool {
     }
 
     /* ---------------- removal (post-block) ------- */
-    pub async fn remove_transactions(&self, ids: &[TransactionId]) {
+    pub fn remove_transactions(&self, ids: &[TransactionId]) {
         for id in ids {
             if let Some((_, ent)) = self.map.remove(id) {
                 {
                     let mut q = self.queue.write();
                     q.remove(&ent.key);
                 }
-                if let Some(mut set) = self.by_account.get_mut(&ent.tx.from) {
-                    set.remove(id);
-                    if set.is_empty() {
-                        drop(set);
-                        self.by_account.remove(&ent.tx.from);
-                        self.nonces.remove(&ent.tx.from);
+                let sender = ent.tx.sender().to_bytes();
+                if let Some(mut queue) = self.by_account.get_mut(&sender) {
+                    if queue.get(&ent.tx.nonce) == Some(id) {
+                        queue.remove(&ent.tx.nonce);
+                    }
+                    if queue.is_empty() {
+                        drop(queue);
+                        self.by_account.remove(&sender);
                     }
                 }
                 *self.bytes_used.write() -= ent.size;
@@ -277,15 +376,84 @@ impl TransactionMempool {
         }
     }
 
-    /// Refresh the cached sender nonces from authoritative chain state after a
-    /// new block is applied.  This prevents nonce-related rejects when multiple
-    /// transactions from the same account are mined across successive blocks.
-    pub async fn sync_nonces_from_chain(&self, accounts: &DashMap<Vec<u8>, crate::blockchain::AccountState>) {
-        for entry in accounts.iter() {
-            self.nonces.insert(entry.key().clone(), entry.value().nonce);
+    /// Refresh the cached tip height and the nonces and balances of the
+    /// `touched` accounts from authoritative chain state after a block is
+    /// applied or undone.  New transactions are checked against them.  Each
+    /// touched sender's queue is re-checked in nonce order: transactions
+    /// whose nonce the chain has already used are dropped, and so is
+    /// everything from the first transaction the new balance cannot pay.
+    pub fn sync_accounts_from_chain<'a>(
+        &self,
+        accounts: &DashMap<Vec<u8>, AccountState>,
+        touched: impl IntoIterator<Item = &'a Vec<u8>>,
+        tip_height: u64,
+    ) {
+        *self.tip_height.write() = tip_height;
+        self.chain_synced.store(true, Ordering::Release);
+        let mut dropped = Vec::new();
+        for key in touched {
+            match accounts.get(key) {
+                Some(account) => {
+                    self.nonces.insert(key.clone(), account.nonce);
+                    self.balances.insert(key.clone(), account.balance);
+                }
+                // Accounts undone by a reorg are forgotten, not left at
+                // their old state.
+                None => {
+                    self.nonces.remove(key);
+                    self.balances.remove(key);
+                }
+            }
+            dropped.extend(self.unpayable(key));
+        }
+        if !dropped.is_empty() {
+            log::debug!("Dropping {} mempool transaction(s) the chain state no longer allows", dropped.len());
+            self.remove_transactions(&dropped);
         }
     }
 
+    /// Offer the transactions of blocks a reorganization disconnected back
+    /// to the pool.  Returns how many were accepted.
+    pub async fn readmit(&self, transactions: Vec<Transaction>) -> usize {
+        let mut returned = 0;
+        for tx in transactions {
+            if self.add_transaction(tx).await.is_ok_and(|r| r.is_accepted()) {
+                returned += 1;
+            }
+        }
+        if returned > 0 {
+            log::info!("{returned} transaction(s) returned to mempool");
+        }
+        returned
+    }
+
+    /* ---------------- fee estimation ------------ */
+    /// Remember how long the pooled transactions mined at `height` waited.
+    /// Call before the block's transactions are removed.
+    pub fn record_mined(&self, height: u64, transactions: &[Transaction]) {
+        let samples = transactions
+            .iter()
+            .filter_map(|tx| self.map.get(&tx.id))
+            .map(|ent| FeeSample {
+                fee_per_mib: fee_per_mib(ent.tx.fee, ent.size),
+                delay: height.saturating_sub(ent.height).max(1),
+            })
+            .collect();
+        self.estimator.write().record_block(samples);
+    }
+
+    /// Fee rate likely to get a transaction mined within `target_blocks`.
+    pub fn estimate_fee(&self, target_blocks: u32) -> FeeEstimate {
+        let mut pending: Vec<(u64, usize)> = self
+            .map
+            .iter()
+            .map(|ent| (fee_per_mib(ent.tx.fee, ent.size), ent.size))
+            .collect();
+        self.estimator
+            .read()
+            .estimate(target_blocks, &mut pending, self.cfg.max_block_size, self.dynamic_min_fee())
+    }
+
     /* ---------------- stats / maintenance -------- */
     pub fn stats(&self) -> MempoolStats {
         let now = Instant::now();
@@ -325,7 +493,7 @@ impl TransactionMempool {
             }
         }
         if !expired.is_empty() {
-            self.remove_transactions(&expired).await;
+            self.remove_transactions(&expired);
         }
 
         // hourly tick
@@ -345,7 +513,177 @@ impl TransactionMempool {
         self.map.iter().map(|e| e.tx.clone()).collect()
     }
 
+    pub fn get_transaction(&self, id: &TransactionId) -> Option<Transaction> {
+        self.map.get(id).map(|ent| ent.tx.clone())
+    }
+
+    /// Time since the transaction entered the mempool.
+    pub fn transaction_age(&self, id: &TransactionId) -> Option<Duration> {
+        self.map.get(id).map(|ent| ent.added.elapsed())
+    }
+
+    /// The account's confirmed nonce and the nonces it has queued, ascending.
+    pub fn account_nonces(&self, account: &Address) -> (u64, Vec<u64>) {
+        let sender = account.to_bytes();
+        let queued = self
+            .by_account
+            .get(&sender)
+            .map(|queue| queue.keys().copied().collect())
+            .unwrap_or_default();
+        (self.confirmed_nonce(&sender), queued)
+    }
+
+    /// Drop a transaction by hand.  Later nonces of its sender stay queued
+    /// and become pending.  Returns whether it was in the mempool.
+    pub async fn evict(&self, id: &TransactionId) -> bool {
+        if !self.map.contains_key(id) {
+            return false;
+        }
+        self.remove_transactions(&[*id]);
+        log::info!("Evicted mempool transaction {}", hex::encode(id));
+        true
+    }
+
+    /* ---------------- persistence ---------------- */
+    /// Write every pooled transaction to `path`; returns how many.
+    pub async fn save_to_file(&self, path: &Path) -> Result<usize> {
+        let now = Utc::now();
+        let saved: Vec<SavedEntry> = self
+            .map
+            .iter()
+            .map(|ent| SavedEntry {
+                tx: ent.tx.clone(),
+                added_at: now - chrono::Duration::from_std(ent.added.elapsed()).unwrap_or_default(),
+                height: ent.height,
+            })
+            .collect();
+        let bytes = bincode::serialize(&saved).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+
+        // Write aside and rename so a crash never leaves a truncated file.
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, bytes)
+            .await
+            .map_err(|e| BlockchainError::IoError(format!("Failed to write {}: {e}", tmp.display())))?;
+        tokio::fs::rename(&tmp, path)
+            .await
+            .map_err(|e| BlockchainError::IoError(format!("Failed to replace {}: {e}", path.display())))?;
+        Ok(saved.len())
+    }
+
+    /// Re-admit the transactions saved at `path`.  Each goes through full
+    /// validation again; those that fail or have expired are dropped.
+    /// Returns how many were restored.
+    pub async fn load_from_file(&self, path: &Path) -> Result<usize> {
+        let bytes = match tokio::fs::read(path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(BlockchainError::IoError(format!("Failed to read {}: {e}", path.display()))),
+        };
+        let mut saved: Vec<SavedEntry> =
+            bincode::deserialize(&bytes).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+        // Lower nonces first, so no sender's queue looks gapped.
+        saved.sort_by_key(|s| s.tx.nonce);
+
+        let now = Utc::now();
+        let (mut restored, mut dropped) = (0, 0);
+        for s in saved {
+            let age = (now - s.added_at).to_std().unwrap_or_default();
+            if age > self.max_age {
+                dropped += 1;
+                continue;
+            }
+            let id = s.tx.id;
+            // Rejections (e.g. a transaction past its `valid_until`) surface
+            // as errors; they drop that entry, not the whole file.
+            match self.add_transaction(s.tx).await {
+                Ok(result) if result.is_accepted() => {}
+                Ok(_) => {
+                    dropped += 1;
+                    continue;
+                }
+                Err(e) => {
+                    log::debug!("Dropping saved transaction {}: {e}", hex::encode(id));
+                    dropped += 1;
+                    continue;
+                }
+            }
+            if let Some(mut ent) = self.map.get_mut(&id) {
+                ent.added = Instant::now().checked_sub(age).unwrap_or(ent.added);
+                ent.height = s.height;
+            }
+            restored += 1;
+        }
+        if dropped > 0 {
+            log::info!("{dropped} saved transaction(s) no longer valid, dropped from mempool");
+        }
+        // Re-key the queue with the restored ages.
+        self.refresh_priorities();
+        Ok(restored)
+    }
+
     /* ---------------- internal helpers ----------- */
+    /// Id, fee and size of the pending transaction from `sender` with `nonce`.
+    fn pending_with_nonce(&self, sender: &[u8], nonce: u64) -> Option<(TransactionId, u64, usize)> {
+        self.pending_entry(sender, nonce).map(|ent| (ent.tx.id, ent.tx.fee, ent.size))
+    }
+
+    fn pending_entry(&self, sender: &[u8], nonce: u64) -> Option<dashmap::mapref::one::Ref<'_, TransactionId, Entry>> {
+        let id = *self.by_account.get(sender)?.get(&nonce)?;
+        self.map.get(&id)
+    }
+
+    fn chain_height(&self) -> u64 {
+        *self.tip_height.read()
+    }
+
+    /// Nonce of the sender's last transaction on chain (0 if none).
+    fn confirmed_nonce(&self, sender: &[u8]) -> u64 {
+        self.nonces.get(sender).map(|n| *n).unwrap_or(0)
+    }
+
+    /// First nonce below `nonce` the sender has neither confirmed nor queued.
+    fn missing_nonce(&self, sender: &[u8], nonce: u64) -> Option<u64> {
+        let queue = self.by_account.get(sender)?;
+        (self.confirmed_nonce(sender) + 1..nonce).find(|n| !queue.contains_key(n))
+    }
+
+    /// The sender's queued transactions whose nonce the chain has used, and
+    /// those from the first one its confirmed balance no longer covers.
+    fn unpayable(&self, sender: &[u8]) -> Vec<TransactionId> {
+        let Some(queue) = self.by_account.get(sender) else { return Vec::new() };
+        let confirmed = self.confirmed_nonce(sender);
+        let available = self.balances.get(sender).map(|b| *b).unwrap_or(0);
+        let mut spent: u64 = 0;
+        let mut dropped = Vec::new();
+        for (nonce, id) in queue.iter() {
+            if *nonce > confirmed {
+                let required = self.map.get(id).map_or(0, |ent| ent.tx.required_balance());
+                spent = spent.saturating_add(required);
+                if spent <= available {
+                    continue;
+                }
+            }
+            dropped.push(*id);
+        }
+        dropped
+    }
+
+    /// Total spent by the sender's queued transactions other than the one
+    /// at `nonce`, which a new transaction would take the place of.
+    fn queued_spend(&self, sender: &[u8], nonce: u64) -> u64 {
+        let Some(queue) = self.by_account.get(sender) else { return 0 };
+        queue
+            .iter()
+            .filter(|(n, _)| **n != nonce)
+            .filter_map(|(_, id)| self.map.get(id).map(|ent| ent.tx.required_balance()))
+            .fold(0, u64::saturating_add)
+    }
+
+    fn replacement_min_fee(&self, old_fee: u64) -> u64 {
+        let bump = (old_fee * RBF_MIN_FEE_BUMP_PERCENT / 100).max(self.cfg.min_transaction_fee);
+        old_fee.saturating_add(bump)
+    }
+
     fn dynamic_min_fee(&self) -> u64 {
         let util = (*self.bytes_used.read() as f64 / self.max_bytes as f64)
             .max(self.map.len() as f64 / self.max_txs as f64);
@@ -382,6 +720,11 @@ impl TransactionMempool {
     }
 
     async fn validate(&self, tx: &Transaction) -> Result<ValidationResult> {
+        // signed for another network
+        if tx.chain_id != self.cfg.chain_id {
+            return Ok(ValidationResult::WrongChain);
+        }
+
         // structural & fee checks
         match tx.validate_structure() {
             Err(BlockchainError::InvalidTransaction(msg))
@@ -404,26 +747,30 @@ impl TransactionMempool {
             return Ok(ValidationResult::InvalidSignature);
         }
 
-        // nonce sequence
-        if let Some(n) = self.nonces.get(&tx.from) {
-            if tx.nonce <= *n {
-                return Ok(ValidationResult::InvalidNonce {
-                    expected: *n + 1,
-                    got: tx.nonce,
-                });
-            }
+        // nonce sequence: after the confirmed nonce, gaps allowed up to a limit
+        let sender = tx.sender().to_bytes();
+        let confirmed = self.confirmed_nonce(&sender);
+        if tx.nonce <= confirmed || tx.nonce > confirmed + MAX_NONCE_GAP {
+            return Ok(ValidationResult::InvalidNonce {
+                expected: confirmed + 1,
+                got: tx.nonce,
+            });
         }
 
-        // balance
-        if let Some(w) = &self.blockchain {
-            if let Some(bc) = w.upgrade() {
-                let bal = bc.read().get_account_state_or_default(&tx.from).balance;
-                if bal < tx.required_balance() {
-                    return Ok(ValidationResult::InsufficientBalance {
-                        required: tx.required_balance(),
-                        available: bal,
-                    });
-                }
+        // the lock must be open for the next block
+        let next_height = self.chain_height() + 1;
+        if let Some(lock_until) = tx.lock_until.filter(|_| !tx.is_unlocked_at(next_height, Utc::now())) {
+            return Ok(ValidationResult::TransactionLocked { lock_until });
+        }
+
+        // balance, once a chain has pushed it; the sender's other queued
+        // transactions must stay payable too, so a replacement or a gap
+        // fill cannot strand the nonces after it
+        if self.chain_synced.load(Ordering::Acquire) {
+            let available = self.balances.get(&sender).map(|b| *b).unwrap_or(0);
+            let required = tx.required_balance().saturating_add(self.queued_spend(&sender, tx.nonce));
+            if available < required {
+                return Ok(ValidationResult::InsufficientBalance { required, available });
             }
         }
 
@@ -456,7 +803,7 @@ impl TransactionMempool {
             }
         }
         if freed >= needed {
-            self.remove_transactions(&victims).await;
+            self.remove_transactions(&victims);
             true
         } else {
             false
@@ -1,7 +1,9 @@
 use crate::{
+    address::Address,
     crypto::Dilithium3Keypair,
     Result,
     config::Config,
+    wallet::{create_wallet, load_wallet, PassphraseSource},
 };
 use std::path::Path;
 
@@ -27,38 +29,24 @@ impl WalletManager {
     }
     
     /// Load or create miner wallet with consistent logic
-    pub fn load_or_create_miner_wallet(data_directory: &Path) -> Result<Dilithium3Keypair> {
+    pub fn load_or_create_miner_wallet(data_directory: &Path, passphrase: &PassphraseSource) -> Result<Dilithium3Keypair> {
         let wallet_path = data_directory.join("miner-wallet.json");
-        Self::load_or_create_miner_wallet_at_path(&wallet_path)
+        Self::load_or_create_miner_wallet_at_path(&wallet_path, passphrase)
     }
     
-    /// Load or create miner wallet with custom path
-    pub fn load_or_create_miner_wallet_at_path(wallet_path: &Path) -> Result<Dilithium3Keypair> {
-        match Dilithium3Keypair::load_from_file(wallet_path) {
-            Ok(kp) => {
-                log::info!("🔑 Loaded existing miner wallet from {:?}.", wallet_path);
-                Ok(kp)
-            }
-            Err(_) => {
-                log::info!("🔑 Creating new miner keypair at {:?} (no existing wallet found).", wallet_path);
-                let kp = Dilithium3Keypair::new()?;
-                
-                // Ensure parent directory exists
-                if let Some(parent) = wallet_path.parent() {
-                    if let Err(e) = std::fs::create_dir_all(parent) {
-                        log::warn!("⚠️ Failed to create parent directory for wallet: {e}");
-                    }
-                }
-                
-                // Save the new keypair
-                if let Err(e) = kp.save_to_file(wallet_path) {
-                    log::warn!("⚠️ Failed to save new keypair to disk: {e}");
-                } else {
-                    log::info!("✅ New miner wallet saved to {:?}.", wallet_path);
-                }
-                Ok(kp)
-            }
+    /// Load or create the encrypted miner wallet at a custom path
+    pub fn load_or_create_miner_wallet_at_path(wallet_path: &Path, passphrase: &PassphraseSource) -> Result<Dilithium3Keypair> {
+        if wallet_path.exists() {
+            let kp = load_wallet(wallet_path, passphrase)?;
+            log::info!("🔑 Loaded existing miner wallet from {:?}.", wallet_path);
+            return Ok(kp);
         }
+
+        log::info!("🔑 Creating new miner keypair at {:?} (no existing wallet found).", wallet_path);
+        let kp = Dilithium3Keypair::new()?;
+        create_wallet(wallet_path, &kp, passphrase)?;
+        log::info!("✅ New encrypted miner wallet saved to {:?}.", wallet_path);
+        Ok(kp)
     }
 }
 
@@ -70,15 +58,15 @@ pub struct Miner {
 
 impl Miner {
     /// Create new miner: only initializes the keypair
-    pub fn new(config: &Config) -> Result<Self> {
+    pub fn new(config: &Config, passphrase: &PassphraseSource) -> Result<Self> {
         let wallet_path = config.storage.data_directory.join("wallet.json");
-        let keypair = WalletManager::load_or_create_miner_wallet_at_path(&wallet_path)?;
+        let keypair = WalletManager::load_or_create_miner_wallet_at_path(&wallet_path, passphrase)?;
         Ok(Self { keypair })
     }
     
     /// Create miner from existing keypair file
-    pub fn from_wallet_path(wallet_path: &Path) -> Result<Self> {
-        let keypair = WalletManager::load_or_create_miner_wallet_at_path(wallet_path)?;
+    pub fn from_wallet_path(wallet_path: &Path, passphrase: &PassphraseSource) -> Result<Self> {
+        let keypair = WalletManager::load_or_create_miner_wallet_at_path(wallet_path, passphrase)?;
         Ok(Self { keypair })
     }
     
@@ -93,10 +81,8 @@ impl Miner {
     }
     
     /// Get the miner's address for balance tracking
-    pub fn get_address(&self) -> String {
-        // This would use the same address derivation as the blockchain
-        crate::crypto::derive_address_from_public_key(&self.get_public_key())
-            .unwrap_or_else(|_| "invalid_address".to_string())
+    pub fn get_address(&self) -> Address {
+        Address::from_public_key(self.keypair.public_key_bytes())
     }
 } 
 
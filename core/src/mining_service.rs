@@ -26,7 +26,7 @@ pub struct JobTemplate {
 /// Mining service that manages job templates and share validation
 pub struct MiningService {
     blockchain: Arc<RwLock<NumiBlockchain>>,
-    _network_handle: NetworkHandle,
+    network_handle: NetworkHandle,
     miner: Arc<RwLock<Miner>>,
     _config: MiningConfig,
     consensus: ConsensusConfig,
@@ -44,7 +44,7 @@ impl MiningService {
     ) -> Self {
         Self {
             blockchain,
-            _network_handle: network_handle,
+            network_handle,
             miner,
             _config: config,
             consensus,
@@ -62,49 +62,12 @@ impl MiningService {
 
     /// Retrieve a new mining job template based on current blockchain state
     pub fn get_job(&self) -> std::result::Result<JobTemplate, MiningServiceError> {
-        let height = self.blockchain.read().get_current_height() + 1;
-        let previous_hash = self.blockchain.read().get_latest_block_hash();
-        let difficulty = self.blockchain.read().get_current_difficulty();
-        let transactions = self.blockchain.read().get_transactions_for_block(1_000_000, 1000);
-
-        // --------------------------------------------------------------
-        // Assemble coinbase (mining reward) transaction
-        // --------------------------------------------------------------
-        use crate::transaction::{Transaction, TransactionType};
-        use crate::miner::WalletManager;
-
-        let miner_pk = self.miner.read().get_public_key();
-
-        let base_reward = WalletManager::calculate_mining_reward_with_config(height, &self.consensus);
-        let total_fees: u64 = transactions.iter().map(|tx| tx.fee).sum();
-        let reward_amount = base_reward.saturating_add(total_fees);
-
-        let mut reward_tx = Transaction::new(
-            miner_pk.clone(),
-            TransactionType::MiningReward {
-                block_height: height,
-                amount: reward_amount,
-            },
-            0,
-        );
-        // Signing reward tx should not fail; if it does, skip job creation
-        if reward_tx.sign(&self.miner.read().get_keypair()).is_err() {
-            return Err(MiningServiceError::MiningError("Failed to sign reward tx".into()));
-        }
-
-        let mut all_txs = Vec::with_capacity(1 + transactions.len());
-        all_txs.push(reward_tx);
-        all_txs.extend(transactions);
-
-        // Build a block template with nonce = 0 and reward tx included
-        let mut block = Block::new(
-            height,
-            previous_hash,
-            all_txs,
-            difficulty,
-            miner_pk,
-        );
-        block.header.merkle_root = Block::calculate_merkle_root(&block.transactions);
+        let block = self
+            .blockchain
+            .read()
+            .block_template(self.miner.read().get_keypair(), 1_000_000, 1000)
+            .map_err(|e| MiningServiceError::MiningError(e.to_string()))?;
+        let (height, difficulty) = (block.header.height, block.header.difficulty);
         let header_blob = block.serialize_header_for_hashing()
             .map_err(|e| MiningServiceError::MiningError(e.to_string()))?;
         let target = generate_difficulty_target(difficulty);
@@ -154,14 +117,14 @@ impl MiningService {
         let mut block = job.block.clone();
         block.header.nonce = nonce;
         block.sign(
-            &self.miner.read().get_keypair(),
+            self.miner.read().get_keypair(),
             None,
         ).map_err(|e| MiningServiceError::MiningError(e.to_string()))?;
         
         let blockchain_clone = self.blockchain.clone();
         let block_clone = block.clone();
         let added = tokio::spawn(async move {
-            blockchain_clone.write().add_block(block_clone).await
+            NumiBlockchain::add_block_to(&blockchain_clone, block_clone, None).await
         }).await
         .map_err(|e| MiningServiceError::MiningError(format!("Task error: {}", e)))?
         .map_err(|e| MiningServiceError::MiningError(e.to_string()))?;
@@ -169,6 +132,12 @@ impl MiningService {
         // Clean up the job
         self.active_jobs.write().remove(&job_id);
 
+        if added {
+            if let Err(e) = self.network_handle.broadcast_block(block) {
+                log::warn!("Failed to broadcast mined block: {}", e);
+            }
+        }
+
         Ok(added)
     }
 
@@ -187,20 +156,23 @@ mod tests {
     use tempfile::tempdir;
     use crate::storage::BlockchainStorage;
     use crate::network::NetworkManager;
+    use crate::wallet::PassphraseSource;
+    use crate::crypto::Dilithium3Keypair;
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_get_job_template() {
-        let chain = Arc::new(RwLock::new(NumiBlockchain::new(crate::config::ConsensusConfig::default()).unwrap()));
+        let chain = Arc::new(RwLock::new(NumiBlockchain::new_with_keypair(Dilithium3Keypair::new().unwrap(), crate::config::ConsensusConfig::default()).unwrap()));
         let storage_dir = tempdir().unwrap();
         let _storage = Arc::new(BlockchainStorage::new(storage_dir.path()).unwrap());
         
         // Create network config and channel for NetworkManager
         let network_config = crate::config::NetworkConfig::default();
         let (in_tx, _in_rx) = futures::channel::mpsc::unbounded();
-        let (network_mgr, network_handle) = NetworkManager::new(&network_config, in_tx).unwrap();
+        let (_network_mgr, network_handle) = NetworkManager::new(&network_config, crate::config::MAINNET_CHAIN_ID, &chain.read().genesis_hash(), in_tx).unwrap();
         
-        let cfg = crate::config::Config::default();
-        let miner = Arc::new(RwLock::new(Miner::new(&cfg).unwrap()));
+        let mut cfg = crate::config::Config::default();
+        cfg.storage.data_directory = storage_dir.path().to_path_buf();
+        let miner = Arc::new(RwLock::new(Miner::new(&cfg, &PassphraseSource::value("test")).unwrap()));
 
         let default_cfg = crate::config::Config::default();
         let mining_cfg = default_cfg.mining.clone();
@@ -222,7 +194,7 @@ mod tests {
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_submit_share_invalid_nonce() {
         // Setup as above
-        let blockchain_result = NumiBlockchain::new(crate::config::ConsensusConfig::default());
+        let blockchain_result = NumiBlockchain::new_with_keypair(Dilithium3Keypair::new().unwrap(), crate::config::ConsensusConfig::default());
         if let Err(e) = &blockchain_result {
             println!("Failed to create blockchain: {:?}", e);
         }
@@ -234,10 +206,11 @@ mod tests {
         // Create network config and channel for NetworkManager
         let network_config = crate::config::NetworkConfig::default();
         let (in_tx, _in_rx) = futures::channel::mpsc::unbounded();
-        let (network_mgr, network_handle) = NetworkManager::new(&network_config, in_tx).unwrap();
+        let (_network_mgr, network_handle) = NetworkManager::new(&network_config, crate::config::MAINNET_CHAIN_ID, &chain.read().genesis_hash(), in_tx).unwrap();
         
-        let miner_cfg = crate::config::Config::default();
-        let miner = Arc::new(RwLock::new(Miner::new(&miner_cfg).unwrap()));
+        let mut miner_cfg = crate::config::Config::default();
+        miner_cfg.storage.data_directory = storage_dir.path().to_path_buf();
+        let miner = Arc::new(RwLock::new(Miner::new(&miner_cfg, &PassphraseSource::value("test")).unwrap()));
 
         let default_cfg = crate::config::Config::default();
         let mining_cfg = default_cfg.mining.clone();
@@ -255,7 +228,7 @@ mod tests {
         let result = service.submit_share(job.job_id.clone(), u64::MAX).await;
         
         match result {
-            Ok(valid) => {
+            Ok(_valid) => {
                 // The mining logic might accept this nonce as valid due to low difficulty
                 // or other factors, so we'll just check that it doesn't error
                 // assert!(!valid); // Should be false for invalid nonce
@@ -0,0 +1,157 @@
+// src/multisig.rs
+//
+// M-of-N Dilithium3 accounts.
+// --------------------------------------------------------------
+// • A `MultisigPolicy` (threshold + sorted key set) owns the multisig
+//   `Address` hashed from its encoding; coins are sent to it like to any
+//   other account
+// • Spending uses `TransactionType::MultisigTransfer`, which reveals the
+//   policy; co-signers' signatures travel next to the transaction and are
+//   not part of its signing bytes or id
+// • Co-signers sign offline with `Transaction::multisig_partial_sign` and
+//   the signatures are combined with `Transaction::add_multisig_signature`
+//
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    address::Address,
+    crypto::{Dilithium3Keypair, Dilithium3Signature, DILITHIUM3_PUBKEY_SIZE, DILITHIUM3_SIGNATURE_SIZE},
+    error::BlockchainError,
+    Result,
+};
+
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+/// Encoded size of one `MultisigSignature`: key index, then the signature
+/// and public key (each length-prefixed), message hash and timestamp.
+pub const MULTISIG_SIGNATURE_BYTES: usize = 1 + 8 + DILITHIUM3_SIGNATURE_SIZE + 8 + DILITHIUM3_PUBKEY_SIZE + 32 + 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultisigPolicy {
+    pub threshold: u8,
+    /// Co-signer public keys, sorted and unique
+    pub keys: Vec<Vec<u8>>,
+}
+
+/// One co-signer's signature over a multisig transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultisigSignature {
+    /// Position of the signer in `MultisigPolicy::keys`
+    pub key_index: u8,
+    pub signature: Dilithium3Signature,
+}
+
+/// Is `key` the account key of a multisig policy?
+pub fn is_multisig_address(key: &[u8]) -> bool {
+    Address::from_bytes(key).is_some_and(|address| address.is_multisig())
+}
+
+impl MultisigPolicy {
+    /// Policy requiring `threshold` of `keys`; the keys are sorted so the
+    /// resulting address does not depend on the order they are listed in.
+    pub fn new(threshold: u8, mut keys: Vec<Vec<u8>>) -> Result<Self> {
+        keys.sort();
+        let policy = Self { threshold, keys };
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.keys.is_empty() || self.keys.len() > MAX_MULTISIG_KEYS {
+            return Err(BlockchainError::InvalidTransaction(format!(
+                "multisig needs 1 to {MAX_MULTISIG_KEYS} keys"
+            )));
+        }
+        if self.threshold == 0 || self.threshold as usize > self.keys.len() {
+            return Err(BlockchainError::InvalidTransaction("bad multisig threshold".into()));
+        }
+        if self.keys.iter().any(|k| k.is_empty() || Address::from_bytes(k).is_some()) {
+            return Err(BlockchainError::InvalidTransaction("bad multisig key".into()));
+        }
+        if self.keys.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(BlockchainError::InvalidTransaction("multisig keys must be sorted and unique".into()));
+        }
+        Ok(())
+    }
+
+    /// Address of the account controlled by this policy.
+    pub fn address(&self) -> Address {
+        Address::multisig(&bincode::serialize(self).unwrap_or_default())
+    }
+
+    pub fn key_index(&self, public_key: &[u8]) -> Option<u8> {
+        self.keys.iter().position(|k| k == public_key).map(|i| i as u8)
+    }
+
+    /// Sign `message` as co-signer `keypair`.
+    pub fn sign(&self, message: &[u8], keypair: &Dilithium3Keypair) -> Result<MultisigSignature> {
+        let key_index = self.key_index(&keypair.public_key).ok_or_else(|| {
+            BlockchainError::InvalidArgument("Key is not a co-signer of this multisig account".into())
+        })?;
+        Ok(MultisigSignature { key_index, signature: keypair.sign(message)? })
+    }
+
+    /// Check a single co-signer signature over `message`.
+    pub fn verify_one(&self, message: &[u8], signature: &MultisigSignature) -> Result<bool> {
+        match self.keys.get(signature.key_index as usize) {
+            Some(key) => Dilithium3Keypair::verify(message, &signature.signature, key),
+            None => Ok(false),
+        }
+    }
+
+    /// At least `threshold` distinct co-signers signed `message`.
+    pub fn verify(&self, message: &[u8], signatures: &[MultisigSignature]) -> Result<bool> {
+        let mut signers = HashSet::new();
+        for signature in signatures {
+            if !signers.insert(signature.key_index) || !self.verify_one(message, signature)? {
+                return Ok(false);
+            }
+        }
+        Ok(signers.len() >= self.threshold as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_ignores_key_order() {
+        let a = MultisigPolicy::new(2, vec![vec![1], vec![2], vec![3]]).unwrap();
+        let b = MultisigPolicy::new(2, vec![vec![3], vec![1], vec![2]]).unwrap();
+        assert_eq!(a.address(), b.address());
+        assert!(is_multisig_address(&a.address().to_bytes()));
+        assert_ne!(a.address(), MultisigPolicy::new(1, vec![vec![1], vec![2], vec![3]]).unwrap().address());
+    }
+
+    #[test]
+    fn test_rejects_bad_policies() {
+        assert!(MultisigPolicy::new(0, vec![vec![1]]).is_err());
+        assert!(MultisigPolicy::new(2, vec![vec![1]]).is_err());
+        assert!(MultisigPolicy::new(1, vec![vec![1], vec![1]]).is_err());
+        assert!(MultisigPolicy::new(1, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_threshold_of_distinct_signers() {
+        let keys: Vec<Dilithium3Keypair> = (0..3).map(|_| Dilithium3Keypair::new().unwrap()).collect();
+        let policy = MultisigPolicy::new(2, keys.iter().map(|k| k.public_key.clone()).collect()).unwrap();
+        let msg = b"spend";
+
+        let first = policy.sign(msg, &keys[0]).unwrap();
+        let second = policy.sign(msg, &keys[2]).unwrap();
+        assert!(!policy.verify(msg, std::slice::from_ref(&first)).unwrap());
+        assert!(!policy.verify(msg, &[first.clone(), first.clone()]).unwrap());
+        assert!(policy.verify(msg, &[first.clone(), second.clone()]).unwrap());
+        assert!(!policy.verify(b"other", &[first.clone(), second]).unwrap());
+
+        // The fee estimate for unsigned co-signatures matches their encoding.
+        assert_eq!(bincode::serialized_size(&first).unwrap() as usize, MULTISIG_SIGNATURE_BYTES);
+
+        let outsider = Dilithium3Keypair::new().unwrap();
+        assert!(policy.sign(msg, &outsider).is_err());
+    }
+}
@@ -3,41 +3,73 @@
 // Minimal P2P layer for Numicoin.
 // --------------------------------------------------------------
 // • libp2p TCP → Noise XX → Yamux transport
-// • gossipsub v1.1 for blocks & transactions
+// • gossipsub v1.1 for blocks & transactions, on topics named after the
+//   chain id so networks never share a mesh
+// • identify handshake: peers announcing another chain id or genesis block
+//   are disconnected before they count as peers
 // • mDNS for LAN discovery, static bootstrap list for WAN
+// • request-response (CBOR) for header-first block sync, see `sync.rs`
 // • NetworkHandle lets RPC layer broadcast tx/block & query peer count
 //
 
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
-use futures::{StreamExt, channel::mpsc};
+use futures::{StreamExt, channel::{mpsc, oneshot}};
 use libp2p::{
     core::upgrade,
     gossipsub::{
-        Behaviour as Gossipsub, Event as GossipsubEvent, IdentTopic, Config as GossipsubConfig, 
-        MessageAuthenticity
+        Behaviour as Gossipsub, Event as GossipsubEvent, IdentTopic, ConfigBuilder as GossipsubConfigBuilder,
+        MessageAcceptance, MessageAuthenticity, MessageId, ValidationMode,
     },
+    identify::{self, Behaviour as Identify, Event as IdentifyEvent},
     identity,
     mdns::{tokio::Behaviour as Mdns, Event as MdnsEvent},
     noise,
+    request_response::{
+        self, Event as RequestResponseEvent, InboundRequestId, Message as RequestResponseMessage,
+        OutboundRequestId, ProtocolSupport, ResponseChannel,
+    },
     swarm::{NetworkBehaviour, Swarm, SwarmEvent},
-    tcp, yamux, Multiaddr, PeerId, Transport,
+    tcp, yamux, Multiaddr, PeerId, StreamProtocol, Transport,
 };
 use crate::RwLock;
 
 use crate::{
-    block::Block,
+    block::{Block, BlockHash},
     transaction::Transaction,
     config::NetworkConfig,
     error::BlockchainError,
+    sync::{SyncRequest, SyncResponse},
     Result,
 };
 
+const MAX_BLOCK_MESSAGE_BYTES: usize = 10 * 1024 * 1024; // 10MB
+const MAX_TX_MESSAGE_BYTES: usize = 1024 * 1024;         // 1MB
+const SYNC_PROTOCOL: &str = "/numi/sync/1";
+const SYNC_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Identify protocol version announced to peers; it names our network by
+/// chain id and genesis block, so nodes sharing a chain id but started from
+/// another genesis are told apart too.
+fn network_protocol_version(chain_id: &str, genesis: &BlockHash) -> String {
+    format!("/numi/{chain_id}/{}/1", hex::encode(genesis))
+}
+
 // Events that go FROM network manager TO other parts of the app (inbound)
+//
+// Gossip messages are held back by gossipsub until the application reports a
+// validation verdict, so every event carries the `source` / `message_id` pair
+// needed to call `NetworkHandle::report_validation`.
 #[derive(Debug, Clone)]
 pub enum InEvent {
-    Block(Block),
-    Tx(Transaction),
+    Block { block: Block, source: PeerId, message_id: MessageId },
+    Tx { tx: Transaction, source: PeerId, message_id: MessageId },
+    /// A peer asked us for chain data; answer with `NetworkHandle::respond`.
+    SyncRequest { peer: PeerId, request_id: InboundRequestId, request: SyncRequest },
 }
 
 // Events that go FROM other parts TO network manager (outbound)
@@ -45,21 +77,34 @@ pub enum InEvent {
 pub enum OutEvent {
     BroadcastBlock(Block),
     BroadcastTx(Transaction),
+    ReportValidation { message_id: MessageId, source: PeerId, acceptance: MessageAcceptance },
+    SyncRequest { peer: PeerId, request: SyncRequest, reply: oneshot::Sender<Result<SyncResponse>> },
+    SyncResponse { request_id: InboundRequestId, response: SyncResponse },
 }
 
 // ---------- Behaviour  ---------------------------------------
 #[derive(NetworkBehaviour)]
 #[behaviour(to_swarm = "NetEvent")]
 struct NetBehaviour {
+    identify: Identify,
     mdns: Mdns,
     gossipsub: Gossipsub,
+    sync: request_response::cbor::Behaviour<SyncRequest, SyncResponse>,
     // TODO: Implement peer scoring and reputation management to mitigate Sybil attacks.
 }
 
 #[derive(Debug)]
 enum NetEvent {
+    Identify(Box<IdentifyEvent>),
     Mdns(MdnsEvent),
     Gossipsub(GossipsubEvent),
+    Sync(RequestResponseEvent<SyncRequest, SyncResponse>),
+}
+
+impl From<IdentifyEvent> for NetEvent {
+    fn from(event: IdentifyEvent) -> Self {
+        NetEvent::Identify(Box::new(event))
+    }
 }
 
 impl From<MdnsEvent> for NetEvent {
@@ -74,6 +119,12 @@ impl From<GossipsubEvent> for NetEvent {
     }
 }
 
+impl From<RequestResponseEvent<SyncRequest, SyncResponse>> for NetEvent {
+    fn from(event: RequestResponseEvent<SyncRequest, SyncResponse>) -> Self {
+        NetEvent::Sync(event)
+    }
+}
+
 // ---------- Public handle (for RPC / miner) ------------------
 #[derive(Clone)]
 pub struct NetworkHandle {
@@ -85,6 +136,9 @@ impl NetworkHandle {
     pub fn peer_count(&self) -> usize {
         self.peer_set.read().len()
     }
+    pub fn peers(&self) -> Vec<PeerId> {
+        self.peer_set.read().iter().copied().collect()
+    }
     pub fn broadcast_block(&self, b: Block) -> Result<()> {
         self.out_tx.unbounded_send(OutEvent::BroadcastBlock(b))
             .map_err(|e| BlockchainError::NetworkError(format!("Send error: {e}")))
@@ -93,21 +147,45 @@ impl NetworkHandle {
         self.out_tx.unbounded_send(OutEvent::BroadcastTx(t))
             .map_err(|e| BlockchainError::NetworkError(format!("Send error: {e}")))
     }
+    /// Tell gossipsub whether an inbound message may be forwarded to our mesh.
+    /// Only `Accept` re-broadcasts; `Reject` also penalises the sending peer.
+    pub fn report_validation(&self, message_id: MessageId, source: PeerId, acceptance: MessageAcceptance) -> Result<()> {
+        self.out_tx.unbounded_send(OutEvent::ReportValidation { message_id, source, acceptance })
+            .map_err(|e| BlockchainError::NetworkError(format!("Send error: {e}")))
+    }
+    /// Send a sync request to `peer` and wait for its answer (or a timeout).
+    pub async fn request(&self, peer: PeerId, request: SyncRequest) -> Result<SyncResponse> {
+        let (reply, rx) = oneshot::channel();
+        self.out_tx.unbounded_send(OutEvent::SyncRequest { peer, request, reply })
+            .map_err(|e| BlockchainError::NetworkError(format!("Send error: {e}")))?;
+        rx.await
+            .map_err(|_| BlockchainError::NetworkError("Network manager stopped".to_string()))?
+    }
+    /// Answer a request previously delivered as `InEvent::SyncRequest`.
+    pub fn respond(&self, request_id: InboundRequestId, response: SyncResponse) -> Result<()> {
+        self.out_tx.unbounded_send(OutEvent::SyncResponse { request_id, response })
+            .map_err(|e| BlockchainError::NetworkError(format!("Send error: {e}")))
+    }
 }
 
 // ---------- NetworkManager -----------------------------------
 pub struct NetworkManager {
     swarm:        Swarm<NetBehaviour>,
-    _in_tx:       mpsc::UnboundedSender<InEvent>,
+    in_tx:        mpsc::UnboundedSender<InEvent>,
     out_rx:       mpsc::UnboundedReceiver<OutEvent>,
     peer_set:     Arc<RwLock<HashSet<PeerId>>>,
     topic_blocks: IdentTopic,
     topic_txs:    IdentTopic,
+    protocol_version: String,
+    pending_requests:  HashMap<OutboundRequestId, oneshot::Sender<Result<SyncResponse>>>,
+    pending_responses: HashMap<InboundRequestId, ResponseChannel<SyncResponse>>,
 }
 
 impl NetworkManager {
     pub fn new(
         cfg: &NetworkConfig,
+        chain_id: &str,
+        genesis: &BlockHash,
         in_tx: mpsc::UnboundedSender<InEvent>,
     ) -> Result<(Self, NetworkHandle)> {
         // --- keys & peer id ---
@@ -123,7 +201,14 @@ impl NetworkManager {
             .boxed();
 
         // --- gossipsub config ---
-        let gossipsub_config = GossipsubConfig::default();
+        // Messages are only forwarded once the node has validated them
+        // (see `NetworkHandle::report_validation`).
+        let gossipsub_config = GossipsubConfigBuilder::default()
+            .validation_mode(ValidationMode::Strict)
+            .validate_messages()
+            .max_transmit_size(MAX_BLOCK_MESSAGE_BYTES)
+            .build()
+            .map_err(|e| BlockchainError::NetworkError(format!("Gossipsub config: {e}")))?;
 
         // --- gossipsub ---
         let mut gossipsub = Gossipsub::new(
@@ -131,19 +216,29 @@ impl NetworkManager {
             gossipsub_config,
         ).map_err(|e| BlockchainError::NetworkError(format!("Gossipsub init: {e}")))?;
 
-        let topic_blocks = IdentTopic::new("numicoin-blocks");
-        let topic_txs = IdentTopic::new("numicoin-txs");
+        let topic_blocks = IdentTopic::new(format!("numicoin-blocks/{chain_id}"));
+        let topic_txs = IdentTopic::new(format!("numicoin-txs/{chain_id}"));
         
         gossipsub.subscribe(&topic_blocks)
             .map_err(|e| BlockchainError::NetworkError(format!("Subscribe blocks: {e}")))?;
         gossipsub.subscribe(&topic_txs)
             .map_err(|e| BlockchainError::NetworkError(format!("Subscribe txs: {e}")))?;
 
+        // --- identify (network handshake) ---
+        let protocol_version = network_protocol_version(chain_id, genesis);
+        let identify = Identify::new(identify::Config::new(protocol_version.clone(), id_keys.public()));
+
         // --- mdns ---
         let mdns = Mdns::new(Default::default(), peer_id)?;
 
+        // --- sync request-response ---
+        let sync = request_response::cbor::Behaviour::new(
+            [(StreamProtocol::new(SYNC_PROTOCOL), ProtocolSupport::Full)],
+            request_response::Config::default().with_request_timeout(SYNC_REQUEST_TIMEOUT),
+        );
+
         // --- behaviour / swarm ---
-        let behaviour = NetBehaviour { mdns, gossipsub };
+        let behaviour = NetBehaviour { identify, mdns, gossipsub, sync };
         let mut swarm = Swarm::new(
             transport, 
             behaviour, 
@@ -171,11 +266,14 @@ impl NetworkManager {
         Ok((
             Self {
                 swarm,
-                _in_tx: in_tx,
+                in_tx,
                 out_rx,
                 peer_set,
                 topic_blocks,
                 topic_txs,
+                protocol_version,
+                pending_requests: HashMap::new(),
+                pending_responses: HashMap::new(),
             },
             handle,
         ))
@@ -198,8 +296,8 @@ impl NetworkManager {
                     match swarm_event {
                         SwarmEvent::Behaviour(NetEvent::Mdns(ev)) => match ev {
                             MdnsEvent::Discovered(list) => {
+                                // Counted as peers once the identify handshake succeeds.
                                 for (p, _addr) in list { 
-                                    self.peer_set.write().insert(p);
                                     self.swarm.behaviour_mut().gossipsub.add_explicit_peer(&p);
                                 }
                             }
@@ -211,41 +309,56 @@ impl NetworkManager {
                             }
                         },
                         SwarmEvent::Behaviour(NetEvent::Gossipsub(ev)) => match ev {
-                            GossipsubEvent::Message { 
-                                propagation_source: _,
-                                message_id: _,
+                            GossipsubEvent::Message {
+                                propagation_source,
+                                message_id,
                                 message,
                             } => {
                                 // Security: Validate message size before deserializing to prevent DoS.
-                                if message.topic == self.topic_blocks.hash() {
-                                    if message.data.len() > 10 * 1024 * 1024 { // 10MB limit
+                                let event = if message.topic == self.topic_blocks.hash() {
+                                    if message.data.len() > MAX_BLOCK_MESSAGE_BYTES {
                                         log::warn!("Received block message larger than 10MB, discarding.");
-                                        continue;
-                                    }
-                                    if let Ok(b) = bincode::deserialize::<Block>(&message.data) {
-                                        let _ = self._in_tx.unbounded_send(InEvent::Block(b));
+                                        None
+                                    } else {
+                                        bincode::deserialize::<Block>(&message.data).ok().map(|block| {
+                                            InEvent::Block { block, source: propagation_source, message_id: message_id.clone() }
+                                        })
                                     }
                                 } else if message.topic == self.topic_txs.hash() {
-                                    if message.data.len() > 1024 * 1024 { // 1MB limit
+                                    if message.data.len() > MAX_TX_MESSAGE_BYTES {
                                         log::warn!("Received transaction message larger than 1MB, discarding.");
-                                        continue;
+                                        None
+                                    } else {
+                                        bincode::deserialize::<Transaction>(&message.data).ok().map(|tx| {
+                                            InEvent::Tx { tx, source: propagation_source, message_id: message_id.clone() }
+                                        })
                                     }
-                                    if let Ok(tx) = bincode::deserialize::<Transaction>(&message.data) {
-                                        let _ = self._in_tx.unbounded_send(InEvent::Tx(tx));
+                                } else {
+                                    None
+                                };
+
+                                // Anything we cannot decode is never forwarded; the
+                                // peer that relayed it is penalised.
+                                match event {
+                                    Some(ev) => {
+                                        let _ = self.in_tx.unbounded_send(ev);
+                                    }
+                                    None => {
+                                        self.swarm.behaviour_mut().gossipsub.report_message_validation_result(
+                                            &message_id,
+                                            &propagation_source,
+                                            MessageAcceptance::Reject,
+                                        );
                                     }
                                 }
                             }
-                            GossipsubEvent::Subscribed { peer_id, topic: _ } => {
-                                self.peer_set.write().insert(peer_id);
-                            }
                             GossipsubEvent::Unsubscribed { peer_id, topic: _ } => {
                                 self.peer_set.write().remove(&peer_id);
                             }
                             _ => {}
                         },
-                        SwarmEvent::ConnectionEstablished { peer_id, .. } => {
-                            self.peer_set.write().insert(peer_id);
-                        }
+                        SwarmEvent::Behaviour(NetEvent::Sync(ev)) => self.handle_sync_event(ev),
+                        SwarmEvent::Behaviour(NetEvent::Identify(ev)) => self.handle_identify_event(*ev),
                         SwarmEvent::ConnectionClosed { peer_id, .. } => {
                             self.peer_set.write().remove(&peer_id);
                         }
@@ -272,6 +385,24 @@ impl NetworkManager {
                                         );
                                     }
                                 }
+                                OutEvent::ReportValidation { message_id, source, acceptance } => {
+                                    self.swarm.behaviour_mut().gossipsub.report_message_validation_result(
+                                        &message_id,
+                                        &source,
+                                        acceptance,
+                                    );
+                                }
+                                OutEvent::SyncRequest { peer, request, reply } => {
+                                    let id = self.swarm.behaviour_mut().sync.send_request(&peer, request);
+                                    self.pending_requests.insert(id, reply);
+                                }
+                                OutEvent::SyncResponse { request_id, response } => {
+                                    if let Some(channel) = self.pending_responses.remove(&request_id) {
+                                        if self.swarm.behaviour_mut().sync.send_response(channel, response).is_err() {
+                                            log::debug!("Sync response dropped: peer connection closed");
+                                        }
+                                    }
+                                }
                             }
                         },
                         None => {
@@ -283,4 +414,48 @@ impl NetworkManager {
             }
         }
     }
-}
\ No newline at end of file
+    /// Admit peers that announce our network; drop the rest.
+    fn handle_identify_event(&mut self, event: IdentifyEvent) {
+        if let IdentifyEvent::Received { peer_id, info, .. } = event {
+            if info.protocol_version == self.protocol_version {
+                self.peer_set.write().insert(peer_id);
+                return;
+            }
+            log::warn!(
+                "Disconnecting peer {peer_id}: it runs network '{}', we run '{}'",
+                info.protocol_version,
+                self.protocol_version
+            );
+            self.peer_set.write().remove(&peer_id);
+            self.swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+    }
+
+    fn handle_sync_event(&mut self, event: RequestResponseEvent<SyncRequest, SyncResponse>) {
+        match event {
+            RequestResponseEvent::Message { peer, message, .. } => match message {
+                RequestResponseMessage::Request { request_id, request, channel } => {
+                    self.pending_responses.insert(request_id, channel);
+                    let _ = self.in_tx.unbounded_send(InEvent::SyncRequest { peer, request_id, request });
+                }
+                RequestResponseMessage::Response { request_id, response } => {
+                    if let Some(reply) = self.pending_requests.remove(&request_id) {
+                        let _ = reply.send(Ok(response));
+                    }
+                }
+            },
+            RequestResponseEvent::OutboundFailure { peer, request_id, error, .. } => {
+                log::debug!("Sync request to {peer} failed: {error}");
+                if let Some(reply) = self.pending_requests.remove(&request_id) {
+                    let _ = reply.send(Err(BlockchainError::NetworkError(format!("Sync request failed: {error}"))));
+                }
+            }
+            RequestResponseEvent::InboundFailure { peer, request_id, error, .. } => {
+                log::debug!("Sync request from {peer} failed: {error}");
+                self.pending_responses.remove(&request_id);
+            }
+            RequestResponseEvent::ResponseSent { .. } => {}
+        }
+    }
+}
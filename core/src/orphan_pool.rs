@@ -0,0 +1,192 @@
+// src/orphan_pool.rs
+//
+// Blocks that arrived before their parent.
+// --------------------------------------------------------------
+// • Indexed by `previous_hash` so descendants can be released as soon as
+//   their parent is connected
+// • Bounded: the oldest orphan is evicted once `capacity` is reached, and
+//   a single peer's oldest orphan once it relayed `MAX_ORPHANS_PER_PEER`,
+//   so one peer cannot flush everyone else's
+//
+
+use std::collections::{HashMap, VecDeque};
+
+use libp2p::PeerId;
+
+use crate::block::{Block, BlockHash};
+
+pub const MAX_ORPHAN_BLOCKS: usize = 128;
+pub const MAX_ORPHANS_PER_PEER: usize = 16;
+
+#[derive(Debug)]
+pub struct OrphanPool {
+    blocks: HashMap<BlockHash, Block>,
+    by_parent: HashMap<BlockHash, Vec<BlockHash>>,
+    order: VecDeque<BlockHash>, // insertion order, oldest first
+    sources: HashMap<BlockHash, PeerId>, // peer that relayed the orphan
+    capacity: usize,
+    per_peer: usize,
+}
+
+impl Default for OrphanPool {
+    fn default() -> Self {
+        Self::new(MAX_ORPHAN_BLOCKS, MAX_ORPHANS_PER_PEER)
+    }
+}
+
+impl OrphanPool {
+    pub fn new(capacity: usize, per_peer: usize) -> Self {
+        Self {
+            blocks: HashMap::new(),
+            by_parent: HashMap::new(),
+            order: VecDeque::new(),
+            sources: HashMap::new(),
+            capacity: capacity.max(1),
+            per_peer: per_peer.max(1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn contains(&self, hash: &BlockHash) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    /// Add an orphan relayed by `source`, evicting that peer's oldest orphan
+    /// if it is at its quota and the pool's oldest one if the pool is full.
+    /// Returns `false` if the block was already pooled.
+    pub fn insert(&mut self, hash: BlockHash, block: Block, source: Option<PeerId>) -> bool {
+        if self.blocks.contains_key(&hash) {
+            return false;
+        }
+        if let Some(peer) = source {
+            let from_peer: Vec<BlockHash> =
+                self.order.iter().filter(|h| self.sources.get(*h) == Some(&peer)).copied().collect();
+            if from_peer.len() >= self.per_peer {
+                self.remove(&from_peer[0]);
+            }
+            self.sources.insert(hash, peer);
+        }
+        while self.blocks.len() >= self.capacity {
+            match self.order.front().copied() {
+                Some(oldest) => {
+                    self.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+        self.by_parent.entry(block.header.previous_hash).or_default().push(hash);
+        self.blocks.insert(hash, block);
+        self.order.push_back(hash);
+        true
+    }
+
+    /// Remove and return every orphan whose parent is `parent`.
+    pub fn take_children(&mut self, parent: &BlockHash) -> Vec<(BlockHash, Block)> {
+        let hashes = self.by_parent.get(parent).cloned().unwrap_or_default();
+        hashes
+            .into_iter()
+            .filter_map(|hash| self.remove(&hash).map(|block| (hash, block)))
+            .collect()
+    }
+
+    /// Drop orphans at or below `min_height`; they can no longer connect.
+    pub fn prune_below(&mut self, min_height: u64) {
+        let stale: Vec<BlockHash> = self
+            .blocks
+            .iter()
+            .filter(|(_, b)| b.header.height <= min_height)
+            .map(|(h, _)| *h)
+            .collect();
+        for hash in stale {
+            self.remove(&hash);
+        }
+    }
+
+    fn remove(&mut self, hash: &BlockHash) -> Option<Block> {
+        let block = self.blocks.remove(hash)?;
+        let parent = block.header.previous_hash;
+        if let Some(siblings) = self.by_parent.get_mut(&parent) {
+            siblings.retain(|h| h != hash);
+            if siblings.is_empty() {
+                self.by_parent.remove(&parent);
+            }
+        }
+        self.order.retain(|h| h != hash);
+        self.sources.remove(hash);
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(height: u64, parent: BlockHash) -> Block {
+        Block::new("test", height, parent, vec![], 1, vec![])
+    }
+
+    #[test]
+    fn test_take_children() {
+        let mut pool = OrphanPool::new(8, 8);
+        assert!(pool.insert([1; 32], block(5, [0; 32]), None));
+        assert!(pool.insert([2; 32], block(5, [0; 32]), None));
+        assert!(pool.insert([3; 32], block(6, [1; 32]), None));
+        assert!(!pool.insert([3; 32], block(6, [1; 32]), None));
+
+        let children = pool.take_children(&[0; 32]);
+        assert_eq!(children.len(), 2);
+        assert_eq!(pool.len(), 1);
+        assert!(pool.take_children(&[0; 32]).is_empty());
+
+        let grandchildren = pool.take_children(&[1; 32]);
+        assert_eq!(grandchildren[0].0, [3; 32]);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_evicts_oldest_when_full() {
+        let mut pool = OrphanPool::new(2, 2);
+        pool.insert([1; 32], block(1, [0; 32]), None);
+        pool.insert([2; 32], block(2, [1; 32]), None);
+        pool.insert([3; 32], block(3, [2; 32]), None);
+
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains(&[1; 32]));
+        assert!(pool.contains(&[2; 32]));
+        assert!(pool.contains(&[3; 32]));
+    }
+
+    #[test]
+    fn test_caps_orphans_per_peer() {
+        let (flooder, honest) = (PeerId::random(), PeerId::random());
+        let mut pool = OrphanPool::new(8, 2);
+        pool.insert([1; 32], block(1, [0; 32]), Some(honest));
+        for i in 2..6 {
+            pool.insert([i; 32], block(1, [0; 32]), Some(flooder));
+        }
+
+        assert_eq!(pool.len(), 3);
+        assert!(pool.contains(&[1; 32]));
+        assert!(!pool.contains(&[3; 32]));
+        assert!(pool.contains(&[4; 32]) && pool.contains(&[5; 32]));
+    }
+
+    #[test]
+    fn test_prune_below() {
+        let mut pool = OrphanPool::new(8, 8);
+        pool.insert([1; 32], block(10, [0; 32]), None);
+        pool.insert([2; 32], block(20, [9; 32]), None);
+        pool.prune_below(10);
+
+        assert!(!pool.contains(&[1; 32]));
+        assert!(pool.contains(&[2; 32]));
+        assert!(pool.take_children(&[0; 32]).is_empty());
+    }
+}
@@ -1,8 +1,9 @@
-use crate::{config::Config, BlockchainError, Result, crypto::{Dilithium3Keypair, self}, transaction::{Transaction, TransactionType}};
-use crate::rpc::types::{ApiResponse, BalanceResponse, StatusResponse, TransactionRequest, TransactionResponse};
+use crate::{address::{Address, Network}, config::Config, BlockchainError, Result, fee_estimator::FeeEstimate, transaction::{Transaction, TransactionType}, wallet::{load_wallet, read_signed_transaction, PassphraseSource}};
+use crate::rpc::types::{AccountNoncesResponse, AddressHistoryResponse, ApiResponse, BalanceResponse, RawTransactionRequest, StatusResponse, TransactionRequest, TransactionResponse};
+use crate::storage::TxDirection;
 use reqwest::Client;
 use std::time::Duration;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use hex;
 
 /// Construct the base RPC URL from config
@@ -43,6 +44,15 @@ pub async fn show_status(config: Config) -> Result<()> {
         } else {
             println!("Is Syncing: {}", data.is_syncing);
         }
+        if let Some(sync) = data.sync.filter(|s| s.is_syncing()) {
+            println!(
+                "Sync Progress: {}/{} ({:.1}%) from {} peer(s)",
+                sync.current_height,
+                sync.target_height,
+                sync.progress() * 100.0,
+                sync.sync_peers
+            );
+        }
     } else {
         return Err(BlockchainError::NetworkError(response.error.unwrap_or_else(|| "Unknown error".into())));
     }
@@ -79,15 +89,127 @@ pub async fn show_balance(config: Config, address: String) -> Result<()> {
     Ok(())
 }
 
+/// Show an address's transaction history via RPC
+pub async fn show_history(config: Config, address: String, offset: usize, limit: usize) -> Result<()> {
+    let client = Client::builder().timeout(Duration::from_secs(5)).build().map_err(|e| BlockchainError::NetworkError(e.to_string()))?;
+    let url = format!("{}/address/{}/history?offset={}&limit={}", rpc_base_url(&config), address, offset, limit);
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| BlockchainError::NetworkError(e.to_string()))?
+        .json::<ApiResponse<AddressHistoryResponse>>()
+        .await
+        .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    if !response.success {
+        return Err(BlockchainError::NetworkError(response.error.unwrap_or_else(|| "Unknown error".into())));
+    }
+    let data = response.data.ok_or_else(|| BlockchainError::InvalidArgument("No data in response".to_string()))?;
+    println!("Address: {}", data.address);
+    if data.entries.is_empty() {
+        println!("No transactions");
+    }
+    for entry in data.entries {
+        let (sign, label) = match entry.direction {
+            TxDirection::Sent => ("-", "sent"),
+            TxDirection::Received => ("+", "received"),
+        };
+        println!(
+            "#{:<8} {}{:.2} NUMI {:<8} {}",
+            entry.height,
+            sign,
+            entry.amount as f64 / 100.0,
+            label,
+            entry.tx_id
+        );
+    }
+    Ok(())
+}
+
+/// Show an account's confirmed nonce, its pending nonces and the next free
+/// one (the `--nonce` for `wallet sign-tx`) via RPC
+pub async fn show_nonce(config: Config, address: String) -> Result<()> {
+    let client = Client::builder().timeout(Duration::from_secs(5)).build().map_err(|e| BlockchainError::NetworkError(e.to_string()))?;
+    let url = format!("{}/mempool/nonces/{}", rpc_base_url(&config), address);
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| BlockchainError::NetworkError(e.to_string()))?
+        .json::<ApiResponse<AccountNoncesResponse>>()
+        .await
+        .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    if !response.success {
+        return Err(BlockchainError::NetworkError(response.error.unwrap_or_else(|| "Unknown error".into())));
+    }
+    let data = response.data.ok_or_else(|| BlockchainError::InvalidArgument("No data in response".to_string()))?;
+    println!("Address: {}", data.address);
+    println!("Confirmed Nonce: {}", data.confirmed_nonce);
+    if !data.pending_nonces.is_empty() {
+        let pending: Vec<String> = data.pending_nonces.iter().map(u64::to_string).collect();
+        println!("Pending Nonces: {}", pending.join(", "));
+    }
+    println!("Next Nonce: {}", data.next_nonce);
+    Ok(())
+}
+
+/// Submit a transaction file written by `wallet sign-tx` via RPC
+pub async fn broadcast_transaction(config: Config, path: &Path) -> Result<()> {
+    let tx = read_signed_transaction(path)?;
+    if tx.chain_id != config.consensus.chain_id {
+        return Err(BlockchainError::InvalidArgument(format!(
+            "Transaction is for chain '{}', the node runs '{}'",
+            tx.chain_id, config.consensus.chain_id
+        )));
+    }
+    let bytes = bincode::serialize(&tx).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    let request = RawTransactionRequest { transaction: hex::encode(bytes) };
+
+    let client = Client::builder().timeout(Duration::from_secs(5)).build().map_err(|e| BlockchainError::NetworkError(e.to_string()))?;
+    let url = format!("{}/transaction/raw", rpc_base_url(&config));
+    let response = client
+        .post(&url)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| BlockchainError::NetworkError(e.to_string()))?
+        .json::<ApiResponse<TransactionResponse>>()
+        .await
+        .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    let data = response.data.ok_or_else(|| BlockchainError::NetworkError(response.error.unwrap_or_else(|| "Unknown error".into())))?;
+    println!("Transaction ID: {}", data.id);
+    println!("Validation Result: {}", data.validation_result);
+    println!("Status: {}", data.status);
+    Ok(())
+}
+
+/// Fetch the node's fee estimate for confirmation within `target_blocks`
+pub async fn fetch_fee_estimate(client: &Client, base_url: &str, target_blocks: u32) -> Result<FeeEstimate> {
+    let url = format!("{}/fee/estimate?target_blocks={}", base_url, target_blocks);
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| BlockchainError::NetworkError(format!("Failed to fetch fee estimate from {}: {}", url, e)))?
+        .json::<ApiResponse<FeeEstimate>>()
+        .await
+        .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    if response.success {
+        response.data.ok_or_else(|| BlockchainError::NetworkError("Fee estimate response contained no data".to_string()))
+    } else {
+        Err(BlockchainError::NetworkError(format!("API error when fetching fee estimate: {}", response.error.unwrap_or_else(|| "Unknown error".to_string()))))
+    }
+}
+
 /// Send a transaction via RPC
-pub async fn send_transaction(config: Config, wallet_path: PathBuf, to: String, amount: f64, memo: Option<String>) -> Result<()> {
+pub async fn send_transaction(config: Config, wallet_path: PathBuf, passphrase: &PassphraseSource, to: String, amount: f64, memo: Option<String>, target_blocks: u32) -> Result<()> {
     let client = Client::builder().timeout(Duration::from_secs(5)).build().map_err(|e| BlockchainError::NetworkError(e.to_string()))?;
     let base_url = rpc_base_url(&config);
-    // Use the secure keypair loader to ensure file permissions are checked.
-    let keypair = Dilithium3Keypair::load_from_file(&wallet_path)?;
+    let keypair = load_wallet(&wallet_path, passphrase)?;
     let sender_pubkey = keypair.public_key_bytes().to_vec();
     let from_pubkey_hex = hex::encode(&sender_pubkey);
-    let from_address_derived = crypto::derive_address_from_public_key(&sender_pubkey)?;
+    let network = Network::from_chain_id(&config.consensus.chain_id);
+    let from_address_derived = Address::from_public_key(&sender_pubkey).encode(network);
 
     // Fetch current nonce using the derived address
     let url = format!("{}/balance/{}", base_url, from_address_derived);
@@ -107,26 +229,28 @@ pub async fn send_transaction(config: Config, wallet_path: PathBuf, to: String,
         return Err(BlockchainError::NetworkError(format!("Failed to fetch nonce. Server response: {}", error_body)));
     };
 
-    // The `to` address is provided in user-friendly Base58. The RPC endpoint
-    // expects a hex-encoded public key. This is an inconsistency that should be
-    // fixed in a future version. For now, we will work around it by assuming the
-    // recipient's public key is required, not their address.
-    // TODO: Refactor RPC endpoint to accept Base58 addresses directly.
-    let recipient_pubkey_hex = &to;
-
-
-    // Parse recipient
-    let recipient = hex::decode(recipient_pubkey_hex).map_err(|_| BlockchainError::InvalidArgument(format!("Invalid recipient public key hex: '{}'", recipient_pubkey_hex)))?;
+    // Parse recipient; an address for another network is refused here
+    // rather than by the node.
+    let recipient = Address::parse(&to, network)?;
     // Use integer arithmetic for currency to avoid floating point inaccuracies.
     // The input `amount` is in NUMI, so we convert to the base unit (NANO).
     let amount_raw = (amount * 100.0).round() as u64;
 
     let new_nonce = current_nonce + 1;
-    let mut tx = Transaction::new(sender_pubkey.clone(), TransactionType::Transfer { to: recipient, amount: amount_raw, memo }, new_nonce);
+    let mut tx = Transaction::new(&config.consensus.chain_id, sender_pubkey.clone(), TransactionType::Transfer { to: recipient, amount: amount_raw, memo }, new_nonce);
     tx.sign(&keypair)?;
+    // Price the signed size; changing the fee does not change the size.
+    let estimate = fetch_fee_estimate(&client, &base_url, target_blocks).await?;
+    let size = bincode::serialize(&tx).map(|b| b.len()).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    let fee = estimate.fee_for_size(size).max(tx.fee);
+    if fee != tx.fee {
+        tx.fee = fee;
+        tx.sign(&keypair)?;
+    }
+    println!("Fee: {} NANO (target {} block(s))", tx.fee, estimate.target_blocks);
     let sig_hex = tx.signature.as_ref().map(|s| hex::encode(&s.signature)).ok_or_else(|| BlockchainError::InvalidSignature("Missing signature".to_string()))?;
     let tx_req = TransactionRequest { from: from_pubkey_hex, to: to.clone(), amount: amount_raw, nonce: new_nonce, fee: Some(tx.fee), signature: sig_hex };
-    let resp = client.post(&format!("{}/transaction", base_url)).json(&tx_req).send().await.map_err(|e| BlockchainError::NetworkError(e.to_string()))?.json::<ApiResponse<TransactionResponse>>().await.map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    let resp = client.post(format!("{}/transaction", base_url)).json(&tx_req).send().await.map_err(|e| BlockchainError::NetworkError(e.to_string()))?.json::<ApiResponse<TransactionResponse>>().await.map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
     if let Some(data) = resp.data { println!("Transaction ID: {}", data.id); println!("Validation Result: {}", data.validation_result); println!("Status: {}", data.status); } else {
         return Err(BlockchainError::InvalidArgument(resp.error.unwrap_or_else(|| "Unknown error".into())));
     }
@@ -1,10 +1,12 @@
 use std::sync::Arc;
-use crate::block::Block;
+use crate::blockchain::NumiBlockchain;
 use tokio::time::timeout;
 use std::time::Duration;
 
 use warp::Rejection;
 
+use crate::address::{Address, Network};
+use crate::fee_estimator::{DEFAULT_TARGET_BLOCKS, MAX_TARGET_BLOCKS};
 use crate::rpc::RpcServer;
 use crate::transaction::{Transaction, TransactionType};
 use super::types::*;
@@ -34,6 +36,7 @@ pub async fn handle_status(
     // Now make async calls without holding the lock
     let network_peers = rpc_server.get_peer_count().await;
     let is_syncing = rpc_server.is_syncing().await;
+    let sync = rpc_server.sync.as_ref().map(|s| s.status());
     
     let response = StatusResponse {
         total_blocks,
@@ -45,6 +48,7 @@ pub async fn handle_status(
         network_peers,
         is_syncing,
         chain_work: format!("{cumulative_difficulty}"),
+        sync,
     };
     
     rpc_server.increment_stat("successful_requests").await;
@@ -79,17 +83,302 @@ pub async fn handle_balance(
     }
 }
 
+/// Account proof endpoint handler.  Accepts an address or a hex public key;
+/// accounts that do not exist get a proof of absence.
+pub async fn handle_proof(
+    account: String,
+    query: ProofQuery,
+    rpc_server: Arc<RpcServer>,
+) -> std::result::Result<warp::reply::Json, Rejection> {
+    let result = {
+        let blockchain = rpc_server.blockchain.read();
+        match blockchain.parse_account(&account) {
+            Some(address) => blockchain
+                .account_proof(&address, query.height)
+                .map(|proof| (address.encode(blockchain.network()), proof))
+                .map_err(|e| e.to_string()),
+            None => Err("Invalid address".to_string()),
+        }
+    };
+
+    match result {
+        Ok((address, proof)) => {
+            let response = ProofResponse {
+                address,
+                height: proof.height,
+                block_hash: hex::encode(proof.block_hash),
+                state_root: hex::encode(proof.state_root),
+                balance: proof.account.as_ref().map_or(0, |a| a.balance),
+                nonce: proof.account.as_ref().map_or(0, |a| a.nonce),
+                account: proof.account,
+                proof: proof.proof,
+            };
+            rpc_server.increment_stat("successful_requests").await;
+            Ok(warp::reply::json(&ApiResponse::success(response)))
+        }
+        Err(e) => {
+            rpc_server.increment_stat("failed_requests").await;
+            Ok(warp::reply::json(&ApiResponse::<()>::error(e)))
+        }
+    }
+}
+
+/// Fee estimate endpoint handler
+pub async fn handle_fee_estimate(
+    query: FeeEstimateQuery,
+    rpc_server: Arc<RpcServer>,
+) -> std::result::Result<warp::reply::Json, Rejection> {
+    let target_blocks = query.target_blocks.unwrap_or(DEFAULT_TARGET_BLOCKS);
+    if target_blocks == 0 || target_blocks > MAX_TARGET_BLOCKS {
+        rpc_server.increment_stat("failed_requests").await;
+        return Ok(warp::reply::json(&ApiResponse::<()>::error(format!(
+            "target_blocks must be between 1 and {MAX_TARGET_BLOCKS}"
+        ))));
+    }
+    let estimate = rpc_server.blockchain.read().estimate_fee(target_blocks);
+
+    rpc_server.increment_stat("successful_requests").await;
+    Ok(warp::reply::json(&ApiResponse::success(estimate)))
+}
+
+fn pending_transaction_info(tx: &Transaction, age: Duration, network: Network) -> PendingTransactionInfo {
+    PendingTransactionInfo {
+        id: hex::encode(tx.id),
+        from: tx.sender().encode(network),
+        tx_type: transaction_type_name(tx).to_string(),
+        amount: tx.amount(),
+        fee: tx.fee,
+        nonce: tx.nonce,
+        size_bytes: bincode::serialized_size(tx).unwrap_or(0) as usize,
+        age_seconds: age.as_secs(),
+    }
+}
+
+fn parse_transaction_id(id: &str) -> Option<[u8; 32]> {
+    hex::decode(id).ok()?.try_into().ok()
+}
+
+/// Mempool listing handler: pending transactions, highest fee first
+pub async fn handle_mempool(
+    query: MempoolQuery,
+    rpc_server: Arc<RpcServer>,
+) -> std::result::Result<warp::reply::Json, Rejection> {
+    let (mempool, stats, sender, network) = {
+        let blockchain = rpc_server.blockchain.read();
+        let sender = query.sender.as_ref().map(|s| blockchain.parse_account(s));
+        (blockchain.mempool_handle(), blockchain.get_mempool_stats(), sender, blockchain.network())
+    };
+    let sender = match sender {
+        Some(None) => {
+            rpc_server.increment_stat("failed_requests").await;
+            return Ok(warp::reply::json(&ApiResponse::<()>::error("Invalid sender".to_string())));
+        }
+        Some(Some(key)) => Some(key),
+        None => None,
+    };
+
+    let mut transactions = mempool.all_transactions();
+    if let Some(sender) = &sender {
+        transactions.retain(|tx| tx.sender() == *sender);
+    }
+    transactions.sort_by(|a, b| b.fee.cmp(&a.fee).then_with(|| a.id.cmp(&b.id)));
+
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_MEMPOOL_PAGE).min(MAX_MEMPOOL_PAGE);
+    let page = transactions
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .filter_map(|tx| mempool.transaction_age(&tx.id).map(|age| pending_transaction_info(tx, age, network)))
+        .collect();
+
+    let response = MempoolResponse {
+        total_transactions: transactions.len(),
+        total_size_bytes: stats.total_size_bytes,
+        offset,
+        limit,
+        transactions: page,
+    };
+    rpc_server.increment_stat("successful_requests").await;
+    Ok(warp::reply::json(&ApiResponse::success(response)))
+}
+
+/// Single pending transaction handler
+pub async fn handle_mempool_transaction(
+    id: String,
+    rpc_server: Arc<RpcServer>,
+) -> std::result::Result<warp::reply::Json, Rejection> {
+    let (mempool, network) = {
+        let blockchain = rpc_server.blockchain.read();
+        (blockchain.mempool_handle(), blockchain.network())
+    };
+    let pending = parse_transaction_id(&id).and_then(|id| {
+        let tx = mempool.get_transaction(&id)?;
+        let age = mempool.transaction_age(&id)?;
+        Some(pending_transaction_info(&tx, age, network))
+    });
+
+    match pending {
+        Some(info) => {
+            rpc_server.increment_stat("successful_requests").await;
+            Ok(warp::reply::json(&ApiResponse::success(info)))
+        }
+        None => {
+            rpc_server.increment_stat("failed_requests").await;
+            Ok(warp::reply::json(&ApiResponse::<()>::error("Transaction not in mempool".to_string())))
+        }
+    }
+}
+
+/// Per-account pending nonces handler
+pub async fn handle_mempool_nonces(
+    account: String,
+    rpc_server: Arc<RpcServer>,
+) -> std::result::Result<warp::reply::Json, Rejection> {
+    let (mempool, address, network) = {
+        let blockchain = rpc_server.blockchain.read();
+        (blockchain.mempool_handle(), blockchain.parse_account(&account), blockchain.network())
+    };
+    let Some(address) = address else {
+        rpc_server.increment_stat("failed_requests").await;
+        return Ok(warp::reply::json(&ApiResponse::<()>::error("Invalid address".to_string())));
+    };
+
+    let (confirmed_nonce, pending_nonces) = mempool.account_nonces(&address);
+    let mut next_nonce = confirmed_nonce + 1;
+    for nonce in &pending_nonces {
+        if *nonce == next_nonce {
+            next_nonce += 1;
+        }
+    }
+    let response = AccountNoncesResponse {
+        address: address.encode(network),
+        confirmed_nonce,
+        pending_nonces,
+        next_nonce,
+    };
+    rpc_server.increment_stat("successful_requests").await;
+    Ok(warp::reply::json(&ApiResponse::success(response)))
+}
+
+/// Admin handler: drop a transaction from the mempool
+pub async fn handle_mempool_evict(
+    id: String,
+    rpc_server: Arc<RpcServer>,
+) -> std::result::Result<warp::reply::Json, Rejection> {
+    if !rpc_server.rpc_config.admin_endpoints_enabled {
+        rpc_server.increment_stat("failed_requests").await;
+        return Ok(warp::reply::json(&ApiResponse::<()>::error(
+            "Admin endpoints are disabled".to_string()
+        )));
+    }
+    let mempool = rpc_server.blockchain.read().mempool_handle();
+    let evicted = match parse_transaction_id(&id) {
+        Some(tx_id) => mempool.evict(&tx_id).await,
+        None => false,
+    };
+
+    if evicted {
+        rpc_server.increment_stat("successful_requests").await;
+        Ok(warp::reply::json(&ApiResponse::success(id)))
+    } else {
+        rpc_server.increment_stat("failed_requests").await;
+        Ok(warp::reply::json(&ApiResponse::<()>::error("Transaction not in mempool".to_string())))
+    }
+}
+
+/// Address history handler.  Accepts an address or a hex public key; only
+/// served by nodes running with the address index.
+pub async fn handle_address_history(
+    address: String,
+    query: HistoryQuery,
+    rpc_server: Arc<RpcServer>,
+) -> std::result::Result<warp::reply::Json, Rejection> {
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_PAGE).min(MAX_HISTORY_PAGE);
+    let result = {
+        let blockchain = rpc_server.blockchain.read();
+        match blockchain.parse_account(&address) {
+            Some(account) => blockchain.address_history(&account, offset, limit).map_err(|e| e.to_string()),
+            None => Err("Invalid address".to_string()),
+        }
+    };
+
+    match result {
+        Ok(entries) => {
+            let entries = entries
+                .into_iter()
+                .map(|entry| HistoryEntryInfo {
+                    height: entry.height,
+                    tx_id: hex::encode(entry.tx_id),
+                    direction: entry.direction,
+                    amount: entry.amount,
+                })
+                .collect();
+            rpc_server.increment_stat("successful_requests").await;
+            Ok(warp::reply::json(&ApiResponse::success(AddressHistoryResponse { address, offset, limit, entries })))
+        }
+        Err(e) => {
+            rpc_server.increment_stat("failed_requests").await;
+            Ok(warp::reply::json(&ApiResponse::<()>::error(e)))
+        }
+    }
+}
+
+/// Transaction lookup handler: mined transactions first, then the mempool
+pub async fn handle_transaction_lookup(
+    id: String,
+    rpc_server: Arc<RpcServer>,
+) -> std::result::Result<warp::reply::Json, Rejection> {
+    let found = parse_transaction_id(&id).and_then(|tx_id| {
+        let blockchain = rpc_server.blockchain.read();
+        if let Some((tx, height)) = blockchain.find_transaction(&tx_id) {
+            let confirmations = blockchain.get_current_height().saturating_sub(height) + 1;
+            return Some(TransactionLookupResponse {
+                id: hex::encode(tx_id),
+                status: "confirmed".to_string(),
+                in_mempool: false,
+                block_height: Some(height),
+                block_hash: blockchain.get_block_hash(height).map(hex::encode),
+                confirmations,
+                transaction: tx,
+            });
+        }
+        let tx = blockchain.mempool_handle().get_transaction(&tx_id)?;
+        Some(TransactionLookupResponse {
+            id: hex::encode(tx_id),
+            status: "pending".to_string(),
+            in_mempool: true,
+            block_height: None,
+            block_hash: None,
+            confirmations: 0,
+            transaction: tx,
+        })
+    });
+
+    match found {
+        Some(response) => {
+            rpc_server.increment_stat("successful_requests").await;
+            Ok(warp::reply::json(&ApiResponse::success(response)))
+        }
+        None => {
+            rpc_server.increment_stat("failed_requests").await;
+            Ok(warp::reply::json(&ApiResponse::<()>::error("Transaction not found".to_string())))
+        }
+    }
+}
+
 /// Block endpoint handler - fixed to avoid holding locks across await
 pub async fn handle_block(
     hash_or_height: String,
     rpc_server: Arc<RpcServer>,
 ) -> std::result::Result<warp::reply::Json, Rejection> {
     // Get block data without holding lock across await
-    let block = {
+    let (block, network) = {
         let blockchain = rpc_server.blockchain.read();
         
         // Try to parse as height first, then as hash
-        if let Ok(height) = hash_or_height.parse::<u64>() {
+        let block = if let Ok(height) = hash_or_height.parse::<u64>() {
             blockchain.get_block_by_height(height)
         } else if hash_or_height.len() == 64 {
             // Assume it's a hash
@@ -107,23 +396,19 @@ pub async fn handle_block(
             }
         } else {
             None
-        }
+        };
+        (block, blockchain.network())
     };
     
     match block {
         Some(block) => {
             // Calculate transaction summaries without holding lock
             let transaction_summaries: Vec<TransactionSummary> = block.transactions.iter().map(|tx| {
-                let (tx_type, amount) = match tx.kind {
-                    TransactionType::Transfer { amount, .. } => ("transfer".to_string(), amount),
-                    TransactionType::MiningReward { amount, .. } => ("mining_reward".to_string(), amount),
-                };
-                
                 TransactionSummary {
                     id: hex::encode(tx.id),
-                    from: hex::encode(&tx.from),
-                    tx_type,
-                    amount: amount as f64 / 100.0,
+                    from: tx.sender().encode(network),
+                    tx_type: transaction_type_name(tx).to_string(),
+                    amount: tx.amount() as f64 / 100.0,
                     fee: get_transaction_fee_display(tx),
                 }
             }).collect();
@@ -166,11 +451,12 @@ pub async fn handle_transaction(
         }
     };
 
-    let to_pubkey = match decode_hex_field(&tx_request.to, "to address").await {
-        Ok(key) => key,
-        Err(msg) => {
+    let network = rpc_server.blockchain.read().network();
+    let to_address = match Address::parse(&tx_request.to, network) {
+        Ok(address) => address,
+        Err(e) => {
             rpc_server.increment_stat("failed_requests").await;
-            return Ok(warp::reply::json(&ApiResponse::<()>::error(msg)));
+            return Ok(warp::reply::json(&ApiResponse::<()>::error(e.to_string())));
         }
     };
 
@@ -186,10 +472,12 @@ pub async fn handle_transaction(
     let fee = tx_request.fee.unwrap_or(rpc_server.blockchain.read().consensus_params().min_transaction_fee);
 
     // Create transaction with proper fee
+    let chain_id = rpc_server.blockchain.read().consensus_params().chain_id;
     let mut transaction = Transaction::new(
+        &chain_id,
         from_pubkey.clone(),
         TransactionType::Transfer {
-            to: to_pubkey,
+            to: to_address,
             amount: tx_request.amount,
             memo: None,
         },
@@ -302,30 +590,65 @@ pub async fn handle_transaction(
     Ok(warp::reply::json(&ApiResponse::success(response)))
 }
 
+/// Raw transaction handler: submits a fully signed transaction as is, so
+/// any transaction type (including multisig) signed offline can be relayed
+pub async fn handle_raw_transaction(
+    request: RawTransactionRequest,
+    rpc_server: Arc<RpcServer>,
+) -> std::result::Result<warp::reply::Json, Rejection> {
+    let transaction = match decode_hex_field(&request.transaction, "transaction").await.and_then(|bytes| {
+        bincode::deserialize::<Transaction>(&bytes).map_err(|e| format!("Invalid transaction encoding: {e}"))
+    }) {
+        Ok(tx) => tx,
+        Err(msg) => {
+            rpc_server.increment_stat("failed_requests").await;
+            return Ok(warp::reply::json(&ApiResponse::<()>::error(msg)));
+        }
+    };
+
+    // The mempool checks chain id, structure and signatures.
+    let mempool = rpc_server.blockchain.read().mempool_handle();
+    let result = match mempool.add_transaction(transaction.clone()).await {
+        Ok(result) => result,
+        Err(e) => {
+            rpc_server.increment_stat("failed_requests").await;
+            return Ok(warp::reply::json(&ApiResponse::<()>::error(
+                format!("Transaction processing error: {e}")
+            )));
+        }
+    };
+
+    if result.is_accepted() {
+        if let Some(ref network) = rpc_server.network_manager {
+            let _ = network.broadcast_tx(transaction.clone());
+        }
+    }
+
+    let response = TransactionResponse {
+        id: hex::encode(transaction.id),
+        status: validation_result_to_status(&result),
+        validation_result: format!("{result:?}"),
+    };
+    rpc_server.increment_stat("successful_requests").await;
+    Ok(warp::reply::json(&ApiResponse::success(response)))
+}
+
 pub async fn handle_mine_block(
     _body: MineBlockRequest,
     rpc_server: Arc<RpcServer>,
 ) -> std::result::Result<warp::reply::Json, Rejection> {
-    let (height, previous_hash, difficulty, transactions, consensus) = {
+    let miner_keypair = rpc_server.miner.read().get_keypair().clone();
+    let (template, consensus) = {
         let blockchain = rpc_server.blockchain.read();
-        (
-            blockchain.get_current_height() + 1,
-            blockchain.get_latest_block_hash(),
-            blockchain.get_current_difficulty(),
-            blockchain.get_transactions_for_block(1024 * 1024, 100),
-            blockchain.consensus_params(),
-        )
+        (blockchain.block_template(&miner_keypair, 1024 * 1024, 100), blockchain.consensus_params())
+    };
+    let mut block_to_mine = match template {
+        Ok(block) => block,
+        Err(e) => {
+            rpc_server.increment_stat("failed_requests").await;
+            return Err(warp::reject::custom(RpcError(format!("Failed to build block template: {}", e))));
+        }
     };
-
-    let miner_keypair = rpc_server.miner.read().get_keypair().clone();
-
-    let mut block_to_mine = Block::new(
-        height,
-        previous_hash,
-        transactions,
-        difficulty,
-        miner_keypair.public_key.clone(),
-    );
 
     let mining_result = timeout(
         Duration::from_secs(120),
@@ -339,11 +662,16 @@ pub async fn handle_mine_block(
         Ok(Ok(Ok(mined_block))) => {
             let hash = mined_block.calculate_hash(None).unwrap_or_default();
             let nonce = mined_block.header.nonce;
+            let height = mined_block.header.height;
             let transactions_count = mined_block.transactions.len();
             
-            let blockchain_write = rpc_server.blockchain.write();
-            match blockchain_write.add_block(mined_block).await {
-                Ok(_) => {
+            match NumiBlockchain::add_block_to(&rpc_server.blockchain, mined_block.clone(), None).await {
+                Ok(added) => {
+                    if added {
+                        if let Some(ref network) = rpc_server.network_manager {
+                            let _ = network.broadcast_block(mined_block);
+                        }
+                    }
                     let response = MineBlockResponse {
                         height,
                         hash: hex::encode(hash),
@@ -18,6 +18,8 @@ use crate::{
     storage::BlockchainStorage,
     network::{NetworkManager, NetworkHandle},
     miner::Miner,
+    sync::SyncHandle,
+    transaction::MAX_BATCH_TX_BYTES,
     Result,
 };
 
@@ -29,6 +31,10 @@ use rate_limit::RateLimiter;
 use middleware::{with_rpc_server, rate_limit_filter};
 use handlers::*;
 
+/// Body limit of `POST /transaction/raw`: the hex encoding of the largest
+/// transaction (a full batch) plus room for the JSON around it.
+const RAW_TRANSACTION_BODY_LIMIT: u64 = 2 * MAX_BATCH_TX_BYTES as u64 + 4 * 1024;
+
 /// Production-ready RPC server with comprehensive security
 pub struct RpcServer {
     pub blockchain: Arc<RwLock<NumiBlockchain>>,
@@ -40,10 +46,12 @@ pub struct RpcServer {
     pub start_time: Instant,
     pub network_manager: Option<NetworkHandle>,
     pub miner: Arc<RwLock<Miner>>,
+    pub sync: Option<SyncHandle>,
 }
 
 impl RpcServer {
     /// Create RPC server with custom configuration and components
+    #[allow(clippy::too_many_arguments)]
     pub fn with_config_and_components(
         blockchain: NumiBlockchain,
         storage: BlockchainStorage,
@@ -76,6 +84,7 @@ impl RpcServer {
             start_time: Instant::now(),
             network_manager: Some(network_handle),
             miner: Arc::new(RwLock::new(miner)),
+            sync: None,
         })
     }
     
@@ -109,8 +118,15 @@ impl RpcServer {
             start_time: Instant::now(),
             network_manager: Some(network_manager),
             miner,
+            sync: None,
         })
     }
+
+    /// Report sync progress from `sync` on the status endpoints
+    pub fn with_sync(mut self, sync: SyncHandle) -> Self {
+        self.sync = Some(sync);
+        self
+    }
     
     /// Start the RPC server with all security middleware
     pub async fn start(self, port: u16) -> Result<()> {
@@ -181,6 +197,77 @@ impl RpcServer {
             .and(with_rpc_server(Arc::clone(&rpc_server)))
             .and_then(handle_balance);
             
+        let proof_route = warp::path("proof")
+            .and(warp::path::param())
+            .and(warp::get())
+            .and(warp::query::<ProofQuery>())
+            .and(rate_limit.clone())
+            .and(with_rpc_server(Arc::clone(&rpc_server)))
+            .and_then(handle_proof);
+            
+        let fee_route = warp::path("fee")
+            .and(warp::path("estimate"))
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(warp::query::<FeeEstimateQuery>())
+            .and(rate_limit.clone())
+            .and(with_rpc_server(Arc::clone(&rpc_server)))
+            .and_then(handle_fee_estimate);
+
+        let address_history_route = warp::path("address")
+            .and(warp::path::param())
+            .and(warp::path("history"))
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(warp::query::<HistoryQuery>())
+            .and(rate_limit.clone())
+            .and(with_rpc_server(Arc::clone(&rpc_server)))
+            .and_then(handle_address_history);
+
+        let tx_lookup_route = warp::path("tx")
+            .and(warp::path::param())
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(rate_limit.clone())
+            .and(with_rpc_server(Arc::clone(&rpc_server)))
+            .and_then(handle_transaction_lookup);
+
+        let mempool_route = warp::path("mempool")
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(warp::query::<MempoolQuery>())
+            .and(rate_limit.clone())
+            .and(with_rpc_server(Arc::clone(&rpc_server)))
+            .and_then(handle_mempool);
+
+        let mempool_tx_route = warp::path("mempool")
+            .and(warp::path("tx"))
+            .and(warp::path::param())
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(rate_limit.clone())
+            .and(with_rpc_server(Arc::clone(&rpc_server)))
+            .and_then(handle_mempool_transaction);
+
+        let mempool_nonces_route = warp::path("mempool")
+            .and(warp::path("nonces"))
+            .and(warp::path::param())
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(rate_limit.clone())
+            .and(with_rpc_server(Arc::clone(&rpc_server)))
+            .and_then(handle_mempool_nonces);
+
+        let mempool_evict_route = warp::path("mempool")
+            .and(warp::path("tx"))
+            .and(warp::path::param())
+            .and(warp::path::end())
+            .and(warp::delete())
+            .and(rate_limit.clone())
+            .and(auth_admin.clone())
+            .and(with_rpc_server(Arc::clone(&rpc_server)))
+            .and_then(handle_mempool_evict);
+
         let block_route = warp::path("block")
             .and(warp::path::param())
             .and(warp::get())
@@ -197,6 +284,18 @@ impl RpcServer {
             .and(with_rpc_server(Arc::clone(&rpc_server)))
             .and_then(handle_transaction);
 
+        // Signed transactions can carry several Dilithium3 keys and
+        // signatures, or a whole batch, hex-encoded
+        let raw_transaction_route = warp::path("transaction")
+            .and(warp::path("raw"))
+            .and(warp::path::end())
+            .and(warp::post())
+            .and(warp::body::content_length_limit(RAW_TRANSACTION_BODY_LIMIT))
+            .and(warp::body::json())
+            .and(rate_limit.clone())
+            .and(with_rpc_server(Arc::clone(&rpc_server)))
+            .and_then(handle_raw_transaction);
+
         let mine_route = warp::path("mine")
             .and(warp::post())
             .and(warp::body::json())
@@ -225,7 +324,16 @@ impl RpcServer {
         
         status_route
             .or(balance_route)
+            .or(proof_route)
+            .or(fee_route)
+            .or(tx_lookup_route)
+            .or(address_history_route)
+            .or(mempool_route)
+            .or(mempool_tx_route)
+            .or(mempool_nonces_route)
+            .or(mempool_evict_route)
             .or(block_route)
+            .or(raw_transaction_route)
             .or(transaction_route)
             .or(mine_route)
             .or(stats_route)
@@ -285,7 +393,14 @@ impl RpcServer {
     /// - No peers (single node network or isolated node)
     /// - Already at network consensus height
     /// - Network is idle (no new blocks being produced)
+    ///
+    /// When the block sync manager is running its state is authoritative;
+    /// otherwise the age of our tip is used as a heuristic.
     pub async fn is_syncing(&self) -> bool {
+        if let Some(sync) = &self.sync {
+            return sync.is_syncing();
+        }
+
         let peer_count = self.network_manager.as_ref().map_or(0, |network| network.peer_count());
 
         // If we have no peers, we're not syncing (single node or isolated)
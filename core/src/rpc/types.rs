@@ -1,7 +1,12 @@
 use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use crate::blockchain::AccountState;
 use crate::mempool::ValidationResult;
+use crate::state_tree::StateProof;
+use crate::storage::TxDirection;
+use crate::sync::SyncStatus;
+use crate::transaction::Transaction;
 
 /// Rate limiting configuration
 #[derive(Debug, Clone)]
@@ -66,7 +71,7 @@ pub struct AuthConfig {
 
 impl Default for AuthConfig {
     fn default() -> Self {
-        let require_auth = std::env::var("NUMI_REQUIRE_AUTH").map_or(false, |v| v == "true");
+        let require_auth = std::env::var("NUMI_REQUIRE_AUTH").is_ok_and(|v| v == "true");
 
         let jwt_secret = if require_auth {
             std::env::var("NUMI_JWT_SECRET").expect("CRITICAL: NUMI_JWT_SECRET must be set when auth is required")
@@ -154,6 +159,7 @@ pub struct StatusResponse {
     pub network_peers: usize,
     pub is_syncing: bool,
     pub chain_work: String,
+    pub sync: Option<SyncStatus>,
 }
 
 /// Account balance response with enhanced security
@@ -165,6 +171,70 @@ pub struct BalanceResponse {
     pub transaction_count: u64,
 }
 
+/// Query parameters for the account proof endpoint
+#[derive(Debug, Deserialize)]
+pub struct ProofQuery {
+    pub height: Option<u64>, // defaults to the chain tip
+}
+
+/// Query parameters for the fee estimate endpoint
+#[derive(Debug, Deserialize)]
+pub struct FeeEstimateQuery {
+    pub target_blocks: Option<u32>, // defaults to DEFAULT_TARGET_BLOCKS
+}
+
+/// Query parameters for the mempool listing endpoint
+#[derive(Debug, Deserialize)]
+pub struct MempoolQuery {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,  // defaults to DEFAULT_MEMPOOL_PAGE, at most MAX_MEMPOOL_PAGE
+    pub sender: Option<String>, // address or hex public key
+}
+
+pub const DEFAULT_MEMPOOL_PAGE: usize = 50;
+pub const MAX_MEMPOOL_PAGE: usize = 500;
+
+/// Query parameters for the address history endpoint
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>, // defaults to DEFAULT_HISTORY_PAGE, at most MAX_HISTORY_PAGE
+}
+
+pub const DEFAULT_HISTORY_PAGE: usize = 50;
+pub const MAX_HISTORY_PAGE: usize = 500;
+
+/// One transaction in an address's history
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntryInfo {
+    pub height: u64,
+    pub tx_id: String,
+    pub direction: TxDirection,
+    pub amount: u64, // sent amounts include the fee
+}
+
+/// One page of an address's history, newest first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddressHistoryResponse {
+    pub address: String,
+    pub offset: usize,
+    pub limit: usize,
+    pub entries: Vec<HistoryEntryInfo>,
+}
+
+/// Account state with a proof against a block's state root
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProofResponse {
+    pub address: String,
+    pub height: u64,
+    pub block_hash: String,
+    pub state_root: String,
+    pub balance: u64, // in NANO units
+    pub nonce: u64,
+    pub account: Option<AccountState>, // committed state; None = no such account
+    pub proof: StateProof,
+}
+
 /// Block information response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BlockResponse {
@@ -194,23 +264,73 @@ pub struct MineBlockResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionSummary {
     pub id: String,
-    pub from: String,
+    pub from: String, // sender address
     pub tx_type: String,
     pub amount: f64,
     pub fee: f64,
 }
 
+/// A transaction waiting in the mempool
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingTransactionInfo {
+    pub id: String,
+    pub from: String, // sender address
+    pub tx_type: String,
+    pub amount: u64, // in NANO units
+    pub fee: u64,    // in NANO units
+    pub nonce: u64,
+    pub size_bytes: usize,
+    pub age_seconds: u64,
+}
+
+/// One page of the mempool, highest fee first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MempoolResponse {
+    pub total_transactions: usize, // matching the filter
+    pub total_size_bytes: usize,   // whole mempool
+    pub offset: usize,
+    pub limit: usize,
+    pub transactions: Vec<PendingTransactionInfo>,
+}
+
+/// An account's confirmed nonce and the nonces it has waiting
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountNoncesResponse {
+    pub address: String,
+    pub confirmed_nonce: u64,
+    pub pending_nonces: Vec<u64>,
+    pub next_nonce: u64, // first nonce neither confirmed nor pending
+}
+
+/// Where a transaction is: mined, waiting in the mempool, or neither
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionLookupResponse {
+    pub id: String,
+    pub status: String, // "confirmed" or "pending"
+    pub in_mempool: bool,
+    pub block_height: Option<u64>,
+    pub block_hash: Option<String>,
+    pub confirmations: u64, // 1 once in the tip block, 0 while pending
+    pub transaction: Transaction,
+}
+
 /// Transaction submission request with validation
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionRequest {
     pub from: String,       // Hex-encoded public key
-    pub to: String,         // Hex-encoded recipient address
+    pub to: String,         // Recipient address (Base58Check, this node's network)
     pub amount: u64,        // Amount in smallest units (NANO units, 1 NUMI = 100 NANO)
     pub nonce: u64,         // Account nonce (prevents replay attacks)
     pub fee: Option<u64>,   // Optional custom fee in NANO units (uses calculated minimum if not provided)
     pub signature: String,  // Hex-encoded detached Dilithium3 signature bytes
 }
 
+/// Submission of a transaction signed elsewhere, e.g. by `wallet sign-tx`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawTransactionRequest {
+    pub transaction: String, // Hex-encoded bincode of the signed `Transaction`
+}
+
 /// Transaction response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionResponse {
@@ -233,6 +353,17 @@ pub struct LoginResponse {
     pub token: String,
 }
 
+/// Name of a transaction's type as shown by the API
+pub fn transaction_type_name(transaction: &crate::transaction::Transaction) -> &'static str {
+    use crate::transaction::TransactionType;
+    match transaction.kind {
+        TransactionType::Transfer { .. } => "transfer",
+        TransactionType::MiningReward { .. } => "mining_reward",
+        TransactionType::MultisigTransfer { .. } => "multisig_transfer",
+        TransactionType::BatchTransfer { .. } => "batch_transfer",
+    }
+}
+
 /// Utility function to display transaction fee
 pub fn get_transaction_fee_display(transaction: &crate::transaction::Transaction) -> f64 {
     transaction.fee as f64 / 100.0 // Convert to NUMI
@@ -277,5 +408,12 @@ pub fn validation_result_to_status(result: &ValidationResult) -> String {
             format!("rejected: account spamming (rate limit: {rate_limit})")
         }
         ValidationResult::TransactionExpired => "rejected: transaction expired".to_string(),
+        ValidationResult::WrongChain => "rejected: transaction is for another network".to_string(),
+        ValidationResult::TransactionLocked { lock_until } => format!("rejected: locked until {lock_until}"),
+        ValidationResult::Replaced { previous } => format!("accepted: replaced {}", hex::encode(previous)),
+        ValidationResult::ReplacementFeeTooLow { minimum, got } => {
+            format!("rejected: replacement fee too low (minimum {minimum}, got {got})")
+        }
+        ValidationResult::Pending { missing_nonce } => format!("accepted: pending until nonce {missing_nonce}"),
     }
 }
@@ -12,7 +12,7 @@ use aes_gcm::{
 use argon2::{Argon2, PasswordHasher, password_hash::SaltString};
 use serde::{Deserialize, Serialize};
 
-use crate::crypto::{Dilithium3Keypair, derive_key, generate_random_bytes, blake3_hash, Hash};
+use crate::crypto::{Dilithium3Keypair, generate_random_bytes, blake3_hash, Hash};
 use crate::{Result, BlockchainError};
 
 // Security features implemented:
@@ -231,10 +231,9 @@ impl SecureKeyStore {
         
         // Generate random salt for master password
         let password_salt = generate_random_bytes(self.kdf_config.salt_length)?;
-        // Derive a fixed-length seed from the password
-        let seed = blake3_hash(password.as_bytes());
-        let password_hash = derive_key(&seed, &String::from_utf8_lossy(&password_salt), b"keystore-auth")?;
-        self.password_hash = Some(password_hash);
+        // Same derivation `verify_password` checks against
+        let key = self.derive_key_from_password(password, &password_salt)?;
+        self.password_hash = Some(blake3_hash(&key));
         self.password_salt = password_salt;
         
         log::info!("🔐 Secure key store initialized");
@@ -0,0 +1,204 @@
+// src/state_overlay.rs
+//
+// Copy-on-write account state for executing a block.
+// --------------------------------------------------------------
+// • Reads fall through to the live `accounts` map; writes stay in the
+//   overlay until `commit`
+// • Every transaction is checked against the state left by the ones before
+//   it: sender nonce, balance and a block-wide duplicate id check
+// • A block that fails anywhere is dropped with its overlay, so the live
+//   state never sees half a block
+// • Account statistics (transaction count, totals sent and received) are
+//   part of the committed state and move with every transaction
+//
+
+use std::collections::{BTreeMap, HashSet};
+
+use dashmap::DashMap;
+
+use crate::{
+    address::Address,
+    blockchain::{AccountState, BlockUndo},
+    error::{BlockchainError, InvalidBlockError},
+    transaction::{Transaction, TransactionId, TransactionType},
+    Result,
+};
+
+pub struct StateOverlay<'a> {
+    base: &'a DashMap<Vec<u8>, AccountState>,
+    /// Accounts written so far, sorted so commits and undo records are
+    /// deterministic
+    changes: BTreeMap<Vec<u8>, AccountState>,
+    seen: HashSet<TransactionId>,
+}
+
+impl<'a> StateOverlay<'a> {
+    pub fn new(base: &'a DashMap<Vec<u8>, AccountState>) -> Self {
+        Self { base, changes: BTreeMap::new(), seen: HashSet::new() }
+    }
+
+    /// Account state as of the transactions applied so far.
+    pub fn get(&self, key: &[u8]) -> Option<AccountState> {
+        match self.changes.get(key) {
+            Some(account) => Some(account.clone()),
+            None => self.base.get(key).map(|account| account.clone()),
+        }
+    }
+
+    fn account_mut(&mut self, key: &[u8]) -> &mut AccountState {
+        if !self.changes.contains_key(key) {
+            let current = self.base.get(key).map(|account| account.clone()).unwrap_or_default();
+            self.changes.insert(key.to_vec(), current);
+        }
+        self.changes.get_mut(key).expect("account was just inserted")
+    }
+
+    /// Apply `transactions` in order, stopping at the first that fails.
+    pub fn apply_all(&mut self, transactions: &[Transaction]) -> Result<()> {
+        transactions.iter().try_for_each(|tx| self.apply(tx))
+    }
+
+    /// Apply one transaction.  A transaction that fails leaves the overlay
+    /// as it was.
+    pub fn apply(&mut self, tx: &Transaction) -> Result<()> {
+        if self.seen.contains(&tx.id) {
+            return Err(InvalidBlockError::DuplicateTransaction(hex::encode(tx.id)).into());
+        }
+
+        let from = tx.sender().to_bytes();
+        if let TransactionType::MiningReward { amount, .. } = &tx.kind {
+            let account = self.account_mut(&from);
+            account.balance += amount;
+            account.total_received = account.total_received.saturating_add(*amount);
+            account.transaction_count += 1;
+            self.seen.insert(tx.id);
+            return Ok(());
+        }
+
+        // Every spend uses the sender's next nonce, so transactions from one
+        // account apply in order and never twice.
+        let sender = self.get(&from).unwrap_or_default();
+        let expected = sender.nonce + 1;
+        if tx.nonce != expected {
+            return Err(BlockchainError::InvalidNonce { expected, found: tx.nonce });
+        }
+
+        let credits = credits(tx);
+        let debit = credits.values().fold(tx.fee, |sum, amount| sum.saturating_add(*amount));
+        if sender.balance < debit {
+            return Err(BlockchainError::InvalidTransaction("Insufficient balance".into()));
+        }
+
+        let account = self.account_mut(&from);
+        account.balance -= debit;
+        account.nonce += 1;
+        account.total_sent = account.total_sent.saturating_add(debit);
+        account.transaction_count += 1;
+        for (to, amount) in credits {
+            let to = to.to_bytes();
+            let account = self.account_mut(&to);
+            account.balance += amount;
+            account.total_received = account.total_received.saturating_add(amount);
+            if to != from {
+                account.transaction_count += 1;
+            }
+        }
+        self.seen.insert(tx.id);
+        Ok(())
+    }
+
+    /// Accounts the overlay would write, sorted by key.
+    pub fn changes(&self) -> impl Iterator<Item = (&Vec<u8>, &AccountState)> {
+        self.changes.iter()
+    }
+
+    /// Write the changes into the live accounts and return their previous
+    /// states.
+    pub fn commit(self) -> BlockUndo {
+        self.changes
+            .into_iter()
+            .map(|(key, account)| {
+                let previous = self.base.insert(key.clone(), account);
+                (key, previous)
+            })
+            .collect()
+    }
+}
+
+/// Amounts a transaction credits, summed per recipient.  A mining reward
+/// credits its miner; a self-transfer credits nobody, only its fee leaves
+/// the account.
+pub fn credits(tx: &Transaction) -> BTreeMap<Address, u64> {
+    let outputs: Vec<(Address, u64)> = match &tx.kind {
+        TransactionType::Transfer { to, amount, .. } | TransactionType::MultisigTransfer { to, amount, .. } => {
+            if *to == tx.sender() { Vec::new() } else { vec![(*to, *amount)] }
+        }
+        TransactionType::BatchTransfer { outputs } => outputs.clone(),
+        TransactionType::MiningReward { amount, .. } => vec![(tx.sender(), *amount)],
+    };
+    let mut credits = BTreeMap::new();
+    for (to, amount) in outputs {
+        let total: &mut u64 = credits.entry(to).or_default();
+        *total = total.saturating_add(amount);
+    }
+    credits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(from: u8, to: u8, amount: u64, nonce: u64) -> Transaction {
+        let to = Address::from_public_key(&[to; 32]);
+        Transaction::new("test", vec![from; 32], TransactionType::Transfer { to, amount, memo: None }, nonce)
+    }
+
+    fn key(n: u8) -> Vec<u8> {
+        Address::from_public_key(&[n; 32]).to_bytes()
+    }
+
+    fn funded(balance: u64) -> DashMap<Vec<u8>, AccountState> {
+        let accounts = DashMap::new();
+        accounts.insert(key(1), AccountState { balance, ..Default::default() });
+        accounts
+    }
+
+    #[test]
+    fn test_failed_block_leaves_base_untouched() {
+        let accounts = funded(1_000);
+        let fee = transfer(1, 2, 100, 1).fee;
+
+        let mut overlay = StateOverlay::new(&accounts);
+        overlay.apply(&transfer(1, 2, 100, 1)).unwrap();
+        assert_eq!(overlay.get(&key(2)).unwrap().balance, 100);
+        // The second spend cannot be covered after the first.
+        assert!(overlay.apply(&transfer(1, 3, 900, 2)).is_err());
+        drop(overlay);
+        assert_eq!(accounts.get(&key(1)).unwrap().balance, 1_000);
+        assert!(accounts.get(&key(2)).is_none());
+
+        let mut overlay = StateOverlay::new(&accounts);
+        overlay.apply_all(&[transfer(1, 2, 100, 1), transfer(1, 3, 50, 2)]).unwrap();
+        let undo = overlay.commit();
+        assert_eq!(undo.len(), 3);
+        assert_eq!(accounts.get(&key(1)).unwrap().balance, 1_000 - 150 - 2 * fee);
+        assert_eq!(accounts.get(&key(1)).unwrap().nonce, 2);
+    }
+
+    #[test]
+    fn test_rejects_wrong_nonce_and_duplicates() {
+        let accounts = funded(1_000);
+        let mut overlay = StateOverlay::new(&accounts);
+        assert!(matches!(
+            overlay.apply(&transfer(1, 2, 10, 2)),
+            Err(BlockchainError::InvalidNonce { expected: 1, found: 2 })
+        ));
+
+        let tx = transfer(1, 2, 10, 1);
+        overlay.apply(&tx).unwrap();
+        assert!(matches!(
+            overlay.apply(&tx),
+            Err(BlockchainError::InvalidBlock(InvalidBlockError::DuplicateTransaction(_)))
+        ));
+    }
+}
@@ -0,0 +1,285 @@
+// src/state_tree.rs
+//
+// Authenticated account state.
+// --------------------------------------------------------------
+// • Sparse Merkle tree over 256-bit keys (`blake3(address)`), so the root
+//   does not depend on insertion or iteration order
+// • A subtree holding a single account hashes to that account's leaf, which
+//   keeps paths only as long as the accounts around them require
+// • Subtree hashes are cached; updating an account only rehashes its path
+// • Proofs show either an account's state or its absence under a root
+//
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::AccountState;
+use crate::crypto::{blake3_hash, Hash};
+
+const LEAF_TAG: u8 = 0;
+const NODE_TAG: u8 = 1;
+const KEY_BITS: usize = 256;
+
+/// Root of the empty tree (and hash of every empty subtree).
+pub const EMPTY_ROOT: Hash = [0u8; 32];
+
+/// Position of an account in the tree.
+pub fn account_key(address: &[u8]) -> Hash {
+    blake3_hash(address)
+}
+
+/// Leaf value committed for an account.
+pub fn account_hash(account: &AccountState) -> Hash {
+    blake3_hash(&bincode::serialize(account).unwrap_or_default())
+}
+
+fn leaf_hash(key: &Hash, value: &Hash) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF_TAG]);
+    hasher.update(key);
+    hasher.update(value);
+    *hasher.finalize().as_bytes()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[NODE_TAG]);
+    hasher.update(left);
+    hasher.update(right);
+    *hasher.finalize().as_bytes()
+}
+
+/// Bit `depth` of `key`, most significant first (`true` = right child).
+fn bit(key: &Hash, depth: usize) -> bool {
+    (key[depth / 8] >> (7 - depth % 8)) & 1 == 1
+}
+
+fn set_bit(key: &mut Hash, depth: usize) {
+    key[depth / 8] |= 0x80 >> (depth % 8);
+}
+
+/// First `depth` bits of `key`, the rest cleared.  Together with `depth`
+/// this names the subtree containing `key` at that depth.
+fn prefix(key: &Hash, depth: usize) -> Hash {
+    let mut p = [0u8; 32];
+    let full = depth / 8;
+    p[..full].copy_from_slice(&key[..full]);
+    if !depth.is_multiple_of(8) {
+        p[full] = key[full] & (0xFF << (8 - depth % 8));
+    }
+    p
+}
+
+/// Largest key in the subtree `(depth, prefix)`.
+fn subtree_end(prefix: &Hash, depth: usize) -> Hash {
+    let mut end = *prefix;
+    let full = depth / 8;
+    let mut from = full;
+    if !depth.is_multiple_of(8) {
+        end[full] |= 0xFF >> (depth % 8);
+        from += 1;
+    }
+    for byte in end.iter_mut().skip(from) {
+        *byte = 0xFF;
+    }
+    end
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StateTree {
+    leaves: BTreeMap<Hash, Hash>,      // account key → account hash
+    nodes: HashMap<(u16, Hash), Hash>, // cached hashes of subtrees with 2+ leaves
+}
+
+impl StateTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_accounts<'a>(accounts: impl IntoIterator<Item = (&'a [u8], &'a AccountState)>) -> Self {
+        let leaves = accounts
+            .into_iter()
+            .map(|(address, account)| (account_key(address), account_hash(account)))
+            .collect();
+        Self { leaves, nodes: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Set the state of `address`'s account (`None` removes it).
+    pub fn update(&mut self, address: &[u8], account: Option<&AccountState>) {
+        let key = account_key(address);
+        match account {
+            Some(account) => {
+                self.leaves.insert(key, account_hash(account));
+            }
+            None => {
+                self.leaves.remove(&key);
+            }
+        }
+        for depth in 0..KEY_BITS {
+            self.nodes.remove(&(depth as u16, prefix(&key, depth)));
+        }
+    }
+
+    pub fn root(&mut self) -> Hash {
+        self.subtree(0, [0u8; 32])
+    }
+
+    /// Proof of `address`'s current leaf, or of its absence.
+    pub fn prove(&mut self, address: &[u8]) -> StateProof {
+        let key = account_key(address);
+        let mut siblings = Vec::new();
+        let mut depth = 0;
+        loop {
+            let (first, several) = self.leaves_in(depth, &prefix(&key, depth));
+            if !several {
+                return StateProof { siblings, leaf: first };
+            }
+            let mut sibling = prefix(&key, depth);
+            if !bit(&key, depth) {
+                set_bit(&mut sibling, depth);
+            }
+            siblings.push(self.subtree(depth + 1, sibling));
+            depth += 1;
+        }
+    }
+
+    /// First leaf of the subtree and whether it holds more than one.
+    fn leaves_in(&self, depth: usize, prefix: &Hash) -> (Option<(Hash, Hash)>, bool) {
+        let mut range = self.leaves.range(*prefix..=subtree_end(prefix, depth));
+        let first = range.next().map(|(k, v)| (*k, *v));
+        (first, range.next().is_some())
+    }
+
+    fn subtree(&mut self, depth: usize, prefix: Hash) -> Hash {
+        if let Some(hash) = self.nodes.get(&(depth as u16, prefix)) {
+            return *hash;
+        }
+        match self.leaves_in(depth, &prefix) {
+            (None, _) => EMPTY_ROOT,
+            (Some((key, value)), false) => leaf_hash(&key, &value),
+            (Some(_), true) => {
+                // Keys are unique, so two leaves always split before depth 256.
+                let mut right = prefix;
+                set_bit(&mut right, depth);
+                let hash = node_hash(&self.subtree(depth + 1, prefix), &self.subtree(depth + 1, right));
+                self.nodes.insert((depth as u16, prefix), hash);
+                hash
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateProof {
+    /// Sibling subtree hashes from the root down to where the path ends.
+    pub siblings: Vec<Hash>,
+    /// The only leaf (key, account hash) in the subtree where the path ends.
+    /// For an absent account this is `None` or another account's leaf.
+    pub leaf: Option<(Hash, Hash)>,
+}
+
+impl StateProof {
+    /// Check that `address` has state `account` (`None` = no such
+    /// account) in the tree with the given `root`.
+    pub fn verify(&self, root: &Hash, address: &[u8], account: Option<&AccountState>) -> bool {
+        let depth = self.siblings.len();
+        if depth > KEY_BITS {
+            return false;
+        }
+        let key = account_key(address);
+        let mut hash = match (account, &self.leaf) {
+            (Some(account), Some((leaf_key, value))) => {
+                if *leaf_key != key || *value != account_hash(account) {
+                    return false;
+                }
+                leaf_hash(leaf_key, value)
+            }
+            (Some(_), None) => return false,
+            (None, None) => EMPTY_ROOT,
+            (None, Some((leaf_key, value))) => {
+                // Another account alone in the subtree our key would be in.
+                if *leaf_key == key || prefix(leaf_key, depth) != prefix(&key, depth) {
+                    return false;
+                }
+                leaf_hash(leaf_key, value)
+            }
+        };
+        for (d, sibling) in self.siblings.iter().enumerate().rev() {
+            hash = if bit(&key, d) { node_hash(sibling, &hash) } else { node_hash(&hash, sibling) };
+        }
+        hash == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(balance: u64) -> AccountState {
+        AccountState { balance, ..Default::default() }
+    }
+
+    #[test]
+    fn test_root_is_order_independent() {
+        let accounts: Vec<(Vec<u8>, AccountState)> = (0..20u8).map(|i| (vec![i], account(i as u64))).collect();
+        let mut forward = StateTree::from_accounts(accounts.iter().map(|(k, a)| (k.as_slice(), a)));
+        let mut backward = StateTree::new();
+        for (k, a) in accounts.iter().rev() {
+            backward.update(k, Some(a));
+        }
+        assert_eq!(forward.root(), backward.root());
+        assert_eq!(StateTree::new().root(), EMPTY_ROOT);
+    }
+
+    #[test]
+    fn test_incremental_update_matches_rebuild() {
+        let mut tree = StateTree::new();
+        for i in 0..10u8 {
+            tree.update(&[i], Some(&account(1)));
+        }
+        let _ = tree.root();
+        tree.update(&[3], Some(&account(7)));
+        tree.update(&[4], None);
+
+        let mut rebuilt = StateTree::new();
+        for i in (0..10u8).filter(|i| *i != 4) {
+            rebuilt.update(&[i], Some(&account(if i == 3 { 7 } else { 1 })));
+        }
+        assert_eq!(tree.len(), 9);
+        assert_eq!(tree.root(), rebuilt.root());
+    }
+
+    #[test]
+    fn test_inclusion_and_exclusion_proofs() {
+        let mut tree = StateTree::new();
+        for i in 0..10u8 {
+            tree.update(&[i], Some(&account(i as u64)));
+        }
+        let root = tree.root();
+
+        let proof = tree.prove(&[5]);
+        assert!(proof.verify(&root, &[5], Some(&account(5))));
+        assert!(!proof.verify(&root, &[5], Some(&account(6))));
+        assert!(!proof.verify(&root, &[5], None));
+        assert!(!proof.verify(&[1u8; 32], &[5], Some(&account(5))));
+
+        let absent = tree.prove(&[42]);
+        assert!(absent.verify(&root, &[42], None));
+        assert!(!absent.verify(&root, &[42], Some(&account(0))));
+
+        let mut single = StateTree::new();
+        single.update(&[1], Some(&account(1)));
+        let root = single.root();
+        assert!(single.prove(&[1]).verify(&root, &[1], Some(&account(1))));
+        assert!(single.prove(&[2]).verify(&root, &[2], None));
+    }
+}
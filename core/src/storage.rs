@@ -1,7 +1,7 @@
 use std::path::Path;
 use std::fs::File;
 use fs2::FileExt;
-use sled::{self, transaction::TransactionalTree, Transactional};
+use sled::{self, transaction::{ConflictableTransactionError, TransactionalTree}, Transactional};
 use serde::{Serialize, Deserialize};
 
 use aes_gcm::{
@@ -14,10 +14,31 @@ use bincode;
 
 use crate::block::Block;
 use crate::transaction::Transaction;
-use crate::blockchain::{ChainState, AccountState, SecurityCheckpoint};
+use crate::blockchain::{AccountSnapshot, ChainState, AccountState, BlockUndo, SecurityCheckpoint};
 use crate::error::BlockchainError;
+use crate::state_tree::account_key;
 use crate::Result;
 
+/// Metadata key present while the address index covers the whole chain.
+const ADDRESS_INDEX_BUILT: &[u8] = b"address_index_built";
+
+/// Which way value moved for an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxDirection {
+    Sent,
+    Received,
+}
+
+/// One transaction in an address's history.  Sent amounts include the fee.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressHistoryEntry {
+    pub height: u64,
+    pub tx_id: [u8; 32],
+    pub direction: TxDirection,
+    pub amount: u64,
+}
+
 /// Optional encryption key for sensitive data (AES-256)
 #[derive(Debug, Clone)]
 pub struct EncryptionKey {
@@ -162,7 +183,11 @@ pub struct BlockchainStorage {
     accounts: sled::Tree,
     state: sled::Tree,
     checkpoints: sled::Tree,
+    undo: sled::Tree, // per-height account undo records for reorgs
+    snapshots: sled::Tree, // full account snapshots taken at checkpoints
     metadata: sled::Tree, // For version and other metadata
+    address_history: sled::Tree, // optional address → transaction index
+    index_addresses: bool,
     encryption_key: Option<EncryptionKey>, // Optional encryption for sensitive data
     base_path: std::path::PathBuf, // root directory of the database – used for auxiliary files
     _lock: File,
@@ -176,9 +201,13 @@ pub struct StorageTransaction<'a> {
     accounts: &'a TransactionalTree,
     state: &'a TransactionalTree,
     checkpoints: &'a TransactionalTree,
+    undo: &'a TransactionalTree,
+    snapshots: &'a TransactionalTree,
+    address_history: &'a TransactionalTree,
 }
 
 impl<'a> StorageTransaction<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         storage: &'a BlockchainStorage,
         blocks: &'a TransactionalTree,
@@ -186,6 +215,9 @@ impl<'a> StorageTransaction<'a> {
         accounts: &'a TransactionalTree,
         state: &'a TransactionalTree,
         checkpoints: &'a TransactionalTree,
+        undo: &'a TransactionalTree,
+        snapshots: &'a TransactionalTree,
+        address_history: &'a TransactionalTree,
     ) -> Self {
         Self {
             storage,
@@ -194,6 +226,9 @@ impl<'a> StorageTransaction<'a> {
             accounts,
             state,
             checkpoints,
+            undo,
+            snapshots,
+            address_history,
         }
     }
     
@@ -204,6 +239,12 @@ impl<'a> StorageTransaction<'a> {
         self.blocks.insert(key, value)?;
         Ok(())
     }
+
+    /// Remove the block at `height` (reorg disconnect)
+    pub fn delete_block(&mut self, height: u64) -> Result<()> {
+        self.blocks.remove(self.storage.block_key(height))?;
+        Ok(())
+    }
     
     /// Add transaction to batch
     pub fn save_transaction(&mut self, tx_id: &[u8; 32], transaction: &Transaction) -> Result<()> {
@@ -211,6 +252,12 @@ impl<'a> StorageTransaction<'a> {
         self.transactions.insert(tx_id.as_slice(), value)?;
         Ok(())
     }
+
+    /// Remove transaction from batch
+    pub fn delete_transaction(&mut self, tx_id: &[u8; 32]) -> Result<()> {
+        self.transactions.remove(tx_id.as_slice())?;
+        Ok(())
+    }
     
     /// Add account to batch
     pub fn save_account(&mut self, public_key: &[u8], account: &AccountState) -> Result<()> {
@@ -218,6 +265,12 @@ impl<'a> StorageTransaction<'a> {
         self.accounts.insert(public_key, value)?;
         Ok(())
     }
+
+    /// Remove account from batch
+    pub fn delete_account(&mut self, public_key: &[u8]) -> Result<()> {
+        self.accounts.remove(public_key)?;
+        Ok(())
+    }
     
     /// Add chain state to batch
     pub fn save_chain_state(&mut self, state: &ChainState) -> Result<()> {
@@ -233,6 +286,56 @@ impl<'a> StorageTransaction<'a> {
         self.checkpoints.insert(key, value)?;
         Ok(())
     }
+
+    /// Remove the checkpoint at `height` (reorg disconnect)
+    pub fn delete_checkpoint(&mut self, height: u64) -> Result<()> {
+        self.checkpoints.remove(self.storage.checkpoint_key(height))?;
+        Ok(())
+    }
+
+    /// Add the account snapshot taken at checkpoint `height` to batch
+    pub fn save_snapshot(&mut self, height: u64, snapshot: &AccountSnapshot) -> Result<()> {
+        let value = serialize_with_encryption(snapshot, self.storage.encryption_key.as_ref())?;
+        self.snapshots.insert(self.storage.checkpoint_key(height), value)?;
+        Ok(())
+    }
+
+    /// Remove the account snapshot taken at checkpoint `height`
+    pub fn delete_snapshot(&mut self, height: u64) -> Result<()> {
+        self.snapshots.remove(self.storage.checkpoint_key(height))?;
+        Ok(())
+    }
+
+    /// Add the undo record of the block at `height` to batch
+    pub fn save_undo(&mut self, height: u64, undo: &BlockUndo) -> Result<()> {
+        let value = serialize_with_encryption(undo, self.storage.encryption_key.as_ref())?;
+        self.undo.insert(self.storage.block_key(height), value)?;
+        Ok(())
+    }
+
+    /// Remove the undo record of the block at `height`
+    pub fn delete_undo(&mut self, height: u64) -> Result<()> {
+        self.undo.remove(self.storage.block_key(height))?;
+        Ok(())
+    }
+
+    /// Add an entry to `address`'s history (no-op unless the address index
+    /// is enabled)
+    pub fn save_address_entry(&mut self, address: &[u8], entry: &AddressHistoryEntry) -> Result<()> {
+        if self.storage.index_addresses {
+            let value = serialize_with_encryption(entry, self.storage.encryption_key.as_ref())?;
+            self.address_history.insert(self.storage.address_entry_key(address, entry), value)?;
+        }
+        Ok(())
+    }
+
+    /// Remove an entry from `address`'s history (reorg disconnect)
+    pub fn delete_address_entry(&mut self, address: &[u8], entry: &AddressHistoryEntry) -> Result<()> {
+        if self.storage.index_addresses {
+            self.address_history.remove(self.storage.address_entry_key(address, entry))?;
+        }
+        Ok(())
+    }
 }
 
 impl BlockchainStorage {
@@ -267,8 +370,17 @@ impl BlockchainStorage {
         let checkpoints = db.open_tree("checkpoints")
             .map_err(|e| BlockchainError::StorageError(format!("Failed to open checkpoints tree: {e}")))?;
         
+        let undo = db.open_tree("undo")
+            .map_err(|e| BlockchainError::StorageError(format!("Failed to open undo tree: {e}")))?;
+        
+        let snapshots = db.open_tree("snapshots")
+            .map_err(|e| BlockchainError::StorageError(format!("Failed to open snapshots tree: {e}")))?;
+        
         let metadata = db.open_tree("metadata")
             .map_err(|e| BlockchainError::StorageError(format!("Failed to open metadata tree: {e}")))?;
+
+        let address_history = db.open_tree("address_history")
+            .map_err(|e| BlockchainError::StorageError(format!("Failed to open address history tree: {e}")))?;
         
         let storage = Self {
             _db: db,
@@ -277,7 +389,11 @@ impl BlockchainStorage {
             accounts,
             state,
             checkpoints,
+            undo,
+            snapshots,
             metadata,
+            address_history,
+            index_addresses: false,
             encryption_key,
             base_path: path.as_ref().to_path_buf(),
             _lock: lock_file,
@@ -313,13 +429,79 @@ impl BlockchainStorage {
         }
     }
     
-    /// Create a new storage transaction
+    /// Turn the address index on or off.  Blocks committed while it is off
+    /// are not indexed, so turning it off also marks it for a rebuild.
+    pub fn set_address_index(&mut self, enabled: bool) -> Result<()> {
+        self.index_addresses = enabled;
+        if !enabled {
+            self.metadata.remove(ADDRESS_INDEX_BUILT)
+                .map_err(|e| BlockchainError::StorageError(format!("Failed to update metadata: {e}")))?;
+        } else if self.load_chain_state()?.is_none() {
+            // Nothing committed yet: every block will be indexed as it lands.
+            self.mark_address_index_built()?;
+        }
+        Ok(())
+    }
+
+    pub fn address_index_enabled(&self) -> bool {
+        self.index_addresses
+    }
+
+    /// Does the address index cover every committed block?
+    pub fn address_index_built(&self) -> Result<bool> {
+        self.metadata.contains_key(ADDRESS_INDEX_BUILT)
+            .map_err(|e| BlockchainError::StorageError(format!("Failed to read metadata: {e}")))
+    }
+
+    fn mark_address_index_built(&self) -> Result<()> {
+        self.metadata.insert(ADDRESS_INDEX_BUILT, vec![1u8])
+            .map_err(|e| BlockchainError::StorageError(format!("Failed to update metadata: {e}")))?;
+        Ok(())
+    }
+
+    /// Replace the address index with `entries` and mark it complete.  The
+    /// old entries, the new ones and the flag are written in one transaction,
+    /// so an interrupted rebuild is simply redone on the next start.
+    pub fn rebuild_address_index(&self, entries: &[(Vec<u8>, AddressHistoryEntry)]) -> Result<()> {
+        let stale = self.address_history.iter().keys().collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| BlockchainError::StorageError(format!("Failed to read address index: {e}")))?;
+        let fresh = entries.iter()
+            .map(|(address, entry)| Ok((self.address_entry_key(address, entry), serialize_with_encryption(entry, self.encryption_key.as_ref())?)))
+            .collect::<Result<Vec<_>>>()?;
+        (&self.address_history, &self.metadata)
+            .transaction(|(history, metadata)| {
+                for key in &stale {
+                    history.remove(key)?;
+                }
+                for (key, value) in &fresh {
+                    history.insert(key.as_slice(), value.as_slice())?;
+                }
+                metadata.insert(ADDRESS_INDEX_BUILT, vec![1u8])?;
+                Ok(())
+            })
+            .map_err(|e: sled::transaction::TransactionError<BlockchainError>| {
+                BlockchainError::StorageError(format!("Failed to write address index: {e}"))
+            })
+    }
+
+    /// Create a new storage transaction.  Either every write made by `f` is
+    /// committed or, if `f` returns an error, none of them are.
     pub fn transaction<F, R>(&self, f: F) -> Result<R>
     where
         F: Fn(&mut StorageTransaction) -> Result<R>,
     {
-        let result = (&self.blocks, &self.transactions, &self.accounts, &self.state, &self.checkpoints).transaction(
-            |(blocks, transactions, accounts, state, checkpoints)| {
+        let trees = (
+            &self.blocks,
+            &self.transactions,
+            &self.accounts,
+            &self.state,
+            &self.checkpoints,
+            &self.undo,
+            &self.snapshots,
+            &self.address_history,
+        );
+        let result = trees.transaction(
+            |(blocks, transactions, accounts, state, checkpoints, undo, snapshots, address_history)| {
                 let mut storage_tx = StorageTransaction::new(
                     self,
                     blocks,
@@ -327,18 +509,116 @@ impl BlockchainStorage {
                     accounts,
                     state,
                     checkpoints,
+                    undo,
+                    snapshots,
+                    address_history,
                 );
-                Ok(f(&mut storage_tx))
+                f(&mut storage_tx).map_err(ConflictableTransactionError::Abort)
             },
         );
 
-        result.map_err(|e: sled::transaction::TransactionError<BlockchainError>| {
-            BlockchainError::StorageError(format!("Transaction failed: {}", e))
-        })?
+        result.map_err(|e: sled::transaction::TransactionError<BlockchainError>| match e {
+            sled::transaction::TransactionError::Abort(e) => e,
+            other => BlockchainError::StorageError(format!("Transaction failed: {}", other)),
+        })
+    }
+
+    /* ------------------------------------------------------------------
+       Reads
+       ----------------------------------------------------------------*/
+
+    /// Chain state written with the last committed block, if any.
+    pub fn load_chain_state(&self) -> Result<Option<ChainState>> {
+        self.state.get(b"current")
+            .map_err(|e| BlockchainError::StorageError(format!("Failed to get chain state: {e}")))?
+            .map(|data| deserialize_with_encryption(&data, self.encryption_key.as_ref()))
+            .transpose()
+    }
+
+    /// All main-chain blocks in height order.
+    pub fn load_blocks(&self) -> Result<Vec<Block>> {
+        self.blocks.iter()
+            .values()
+            .map(|v| {
+                let data = v.map_err(|e| BlockchainError::StorageError(format!("Failed to read block: {e}")))?;
+                deserialize_with_encryption(&data, self.encryption_key.as_ref())
+            })
+            .collect()
+    }
+
+    pub fn get_block(&self, height: u64) -> Result<Option<Block>> {
+        self.blocks.get(self.block_key(height))
+            .map_err(|e| BlockchainError::StorageError(format!("Failed to get block: {e}")))?
+            .map(|data| deserialize_with_encryption(&data, self.encryption_key.as_ref()))
+            .transpose()
     }
 
+    pub fn get_transaction(&self, tx_id: &[u8; 32]) -> Result<Option<Transaction>> {
+        self.transactions.get(tx_id.as_slice())
+            .map_err(|e| BlockchainError::StorageError(format!("Failed to get transaction: {e}")))?
+            .map(|data| deserialize_with_encryption(&data, self.encryption_key.as_ref()))
+            .transpose()
+    }
 
-    /// Path for simple block file persistence (core-data/blocks)
+    /// Every account keyed by public key.
+    pub fn load_accounts(&self) -> Result<Vec<(Vec<u8>, AccountState)>> {
+        self.accounts.iter()
+            .map(|kv| {
+                let (key, data) = kv.map_err(|e| BlockchainError::StorageError(format!("Failed to read account: {e}")))?;
+                let account = deserialize_with_encryption(&data, self.encryption_key.as_ref())?;
+                Ok((key.to_vec(), account))
+            })
+            .collect()
+    }
+
+    /// Undo records for heights `from..`, in height order.
+    pub fn load_undo_since(&self, from: u64) -> Result<Vec<(u64, BlockUndo)>> {
+        self.undo.range(self.block_key(from)..)
+            .map(|kv| {
+                let (key, data) = kv.map_err(|e| BlockchainError::StorageError(format!("Failed to read undo record: {e}")))?;
+                let height = u64::from_be_bytes(key.as_ref().try_into().map_err(|_| {
+                    BlockchainError::StorageError("Malformed undo key".into())
+                })?);
+                Ok((height, deserialize_with_encryption(&data, self.encryption_key.as_ref())?))
+            })
+            .collect()
+    }
+
+    /// All checkpoints in height order.
+    pub fn load_checkpoints(&self) -> Result<Vec<SecurityCheckpoint>> {
+        self.checkpoints.iter()
+            .values()
+            .map(|v| {
+                let data = v.map_err(|e| BlockchainError::StorageError(format!("Failed to read checkpoint: {e}")))?;
+                deserialize_with_encryption(&data, self.encryption_key.as_ref())
+            })
+            .collect()
+    }
+
+    /// Account snapshot taken at checkpoint `height`, if it is still kept.
+    pub fn load_snapshot(&self, height: u64) -> Result<Option<AccountSnapshot>> {
+        self.snapshots.get(self.checkpoint_key(height))
+            .map_err(|e| BlockchainError::StorageError(format!("Failed to get snapshot: {e}")))?
+            .map(|data| deserialize_with_encryption(&data, self.encryption_key.as_ref()))
+            .transpose()
+    }
+
+    /// Page of `address`'s history, newest first.
+    pub fn address_history(&self, address: &[u8], offset: usize, limit: usize) -> Result<Vec<AddressHistoryEntry>> {
+        self.address_history.scan_prefix(account_key(address))
+            .values()
+            .rev()
+            .skip(offset)
+            .take(limit)
+            .map(|v| {
+                let data = v.map_err(|e| BlockchainError::StorageError(format!("Failed to read address history: {e}")))?;
+                deserialize_with_encryption(&data, self.encryption_key.as_ref())
+            })
+            .collect()
+    }
+
+    /// Directory of the legacy per-height block files (core-data/blocks),
+    /// only read to import chains written before sled persistence.
     pub fn blocks_dir(&self) -> std::path::PathBuf {
         self.base_path.join("blocks")
     }
@@ -353,6 +633,16 @@ impl BlockchainStorage {
         height.to_be_bytes().to_vec()
     }
 
+    /// blake3(address) ‖ height ‖ tx id ‖ direction, so one address's
+    /// entries are contiguous and in height order.
+    fn address_entry_key(&self, address: &[u8], entry: &AddressHistoryEntry) -> Vec<u8> {
+        let mut key = account_key(address).to_vec();
+        key.extend_from_slice(&entry.height.to_be_bytes());
+        key.extend_from_slice(&entry.tx_id);
+        key.push(entry.direction as u8);
+        key
+    }
+
     fn checkpoint_key(&self, height: u64) -> Vec<u8> {
         // Re-use same encoding for checkpoints.
         height.to_be_bytes().to_vec()
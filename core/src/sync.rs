@@ -0,0 +1,501 @@
+// src/sync.rs
+//
+// Header-first initial block download.
+// --------------------------------------------------------------
+// • SyncRequest / SyncResponse travel over libp2p request-response
+//   (see `network.rs`) and are answered from the local chain by
+//   `serve_request`
+// • SyncManager polls peer tips; when a peer reports more work it
+//     1. downloads a window of headers following the last block we share
+//        (found via a block locator) and checks the PoW chain (links,
+//        heights, timestamps, retargeted difficulties, targets)
+//     2. fetches the matching bodies in parallel from every peer whose
+//        tip covers the range
+//     3. applies the bodies in order through `NumiBlockchain::add_block_to`,
+//        which reorganizes onto the peer's branch once it carries more work
+// • SyncHandle exposes progress to the RPC layer
+//
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::{Block, BlockHash, BlockHeader},
+    blockchain::{meets_target, next_difficulty, NumiBlockchain},
+    config::ConsensusConfig,
+    crypto::generate_difficulty_target,
+    error::{BlockchainError, InvalidBlockError},
+    network::NetworkHandle,
+    Result, RwLock,
+};
+
+pub const MAX_HEADERS_PER_REQUEST: u32 = 500;
+pub const MAX_BLOCKS_PER_REQUEST: u32 = 16;
+// Stay below the 10MB response limit of the CBOR codec.
+const MAX_RESPONSE_BYTES: u64 = 8 * 1024 * 1024;
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyncRequest {
+    /// Height, hash and cumulative work of the responder's best block, and
+    /// its genesis hash.
+    GetTip,
+    /// Up to `count` headers following the first `locator` hash found on the
+    /// responder's main chain.  The matched block itself is not included; if
+    /// no hash matches, the headers start with genesis.
+    GetHeaders { locator: Vec<BlockHash>, count: u32 },
+    /// Up to `count` consecutive blocks starting at `start_height`.
+    GetBlocks { start_height: u64, count: u32 },
+    /// A single main-chain block by hash (used to fetch orphan parents).
+    GetBlock { hash: BlockHash },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyncResponse {
+    Tip { height: u64, hash: BlockHash, cumulative_difficulty: u128, genesis: BlockHash },
+    Headers(Vec<BlockHeader>),
+    Blocks(Vec<Block>),
+}
+
+/// Answer a peer's sync request from the local chain.
+pub fn serve_request(chain: &NumiBlockchain, request: SyncRequest) -> SyncResponse {
+    match request {
+        SyncRequest::GetTip => {
+            let state = chain.get_chain_state();
+            SyncResponse::Tip {
+                height: chain.get_current_height(),
+                hash: state.best_block_hash,
+                cumulative_difficulty: state.cumulative_difficulty,
+                genesis: chain.genesis_hash(),
+            }
+        }
+        SyncRequest::GetHeaders { locator, count } => {
+            let start_hash = locator
+                .iter()
+                .find(|hash| chain.get_block_height(hash).is_some())
+                .map(|hash| hash.to_vec())
+                .unwrap_or_default();
+            SyncResponse::Headers(chain.get_block_headers(start_hash, count.min(MAX_HEADERS_PER_REQUEST)))
+        }
+        SyncRequest::GetBlocks { start_height, count } => {
+            let end = start_height.saturating_add(count.min(MAX_BLOCKS_PER_REQUEST) as u64);
+            let mut blocks = Vec::new();
+            let mut bytes = 0u64;
+            for height in start_height..end {
+                let Some(block) = chain.get_block_by_height(height) else { break };
+                bytes += bincode::serialized_size(&block).unwrap_or(0);
+                // Always return at least one block so the requester makes progress.
+                if !blocks.is_empty() && bytes > MAX_RESPONSE_BYTES {
+                    break;
+                }
+                blocks.push(block);
+            }
+            SyncResponse::Blocks(blocks)
+        }
+        SyncRequest::GetBlock { hash } => {
+            SyncResponse::Blocks(chain.get_block_by_hash(&hash).into_iter().collect())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncPhase {
+    Idle,
+    Headers,
+    Blocks,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncStatus {
+    pub phase: SyncPhase,
+    pub current_height: u64,
+    pub target_height: u64,
+    pub sync_peers: usize,
+}
+
+impl SyncStatus {
+    pub fn is_syncing(&self) -> bool {
+        self.phase != SyncPhase::Idle
+    }
+
+    /// Fraction of the target height applied locally, in `[0, 1]`.
+    pub fn progress(&self) -> f64 {
+        if self.target_height == 0 {
+            return 1.0;
+        }
+        (self.current_height as f64 / self.target_height as f64).min(1.0)
+    }
+}
+
+/// Read-only view of the sync state for RPC.
+#[derive(Clone)]
+pub struct SyncHandle {
+    status: Arc<RwLock<SyncStatus>>,
+}
+
+impl SyncHandle {
+    pub fn status(&self) -> SyncStatus {
+        self.status.read().clone()
+    }
+
+    pub fn is_syncing(&self) -> bool {
+        self.status.read().is_syncing()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PeerTip {
+    peer: PeerId,
+    height: u64,
+    cumulative_difficulty: u128,
+}
+
+pub struct SyncManager {
+    chain: Arc<RwLock<NumiBlockchain>>,
+    network: NetworkHandle,
+    consensus: ConsensusConfig,
+    status: Arc<RwLock<SyncStatus>>,
+}
+
+impl SyncManager {
+    pub fn new(chain: Arc<RwLock<NumiBlockchain>>, network: NetworkHandle) -> (Self, SyncHandle) {
+        let (consensus, current_height) = {
+            let guard = chain.read();
+            (guard.consensus_params(), guard.get_current_height())
+        };
+        let status = Arc::new(RwLock::new(SyncStatus {
+            phase: SyncPhase::Idle,
+            current_height,
+            target_height: current_height,
+            sync_peers: 0,
+        }));
+        let handle = SyncHandle { status: status.clone() };
+        (Self { chain, network, consensus, status }, handle)
+    }
+
+    /// Run forever.  Rounds repeat back-to-back while they make progress and
+    /// back off for `SYNC_INTERVAL` once we have caught up.
+    pub async fn run(self) {
+        loop {
+            match self.sync_round().await {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => log::warn!("Block sync round failed: {e}"),
+            }
+            let height = self.chain.read().get_current_height();
+            {
+                let mut status = self.status.write();
+                status.phase = SyncPhase::Idle;
+                status.current_height = height;
+                status.target_height = status.target_height.max(height);
+            }
+            tokio::time::sleep(SYNC_INTERVAL).await;
+        }
+    }
+
+    /// Download and apply one header window.  Returns `true` if any block
+    /// was added to the chain.
+    async fn sync_round(&self) -> Result<bool> {
+        let (local_height, local_work, locator, genesis) = {
+            let chain = self.chain.read();
+            (
+                chain.get_current_height(),
+                chain.get_chain_state().cumulative_difficulty,
+                chain.block_locator(),
+                chain.genesis_hash(),
+            )
+        };
+
+        let mut peers: Vec<PeerTip> = self
+            .poll_tips(genesis)
+            .await
+            .into_iter()
+            .filter(|t| t.cumulative_difficulty > local_work)
+            .collect();
+        if peers.is_empty() {
+            return Ok(false);
+        }
+        peers.sort_by_key(|p| std::cmp::Reverse(p.cumulative_difficulty));
+
+        {
+            let mut status = self.status.write();
+            status.phase = SyncPhase::Headers;
+            status.current_height = local_height;
+            status.target_height = peers[0].height;
+            status.sync_peers = peers.len();
+        }
+        log::info!(
+            "🔄 Syncing from height {} towards {} with {} peer(s)",
+            local_height,
+            peers[0].height,
+            peers.len()
+        );
+
+        let headers = self.download_headers(&peers, locator).await;
+        if headers.is_empty() {
+            return Ok(false);
+        }
+
+        self.status.write().phase = SyncPhase::Blocks;
+        let applied = self.download_blocks(&peers, &headers).await?;
+        Ok(applied > 0)
+    }
+
+    /// Tips of connected peers that share our `genesis`.
+    async fn poll_tips(&self, genesis: BlockHash) -> Vec<PeerTip> {
+        let mut requests: FuturesUnordered<_> = self
+            .network
+            .peers()
+            .into_iter()
+            .map(|peer| {
+                let network = self.network.clone();
+                async move { (peer, network.request(peer, SyncRequest::GetTip).await) }
+            })
+            .collect();
+
+        let mut tips = Vec::new();
+        while let Some((peer, result)) = requests.next().await {
+            match result {
+                Ok(SyncResponse::Tip { genesis: theirs, .. }) if theirs != genesis => {
+                    log::warn!("Ignoring peer {peer}: it follows another chain (genesis {})", hex::encode(theirs));
+                }
+                Ok(SyncResponse::Tip { height, cumulative_difficulty, .. }) => {
+                    tips.push(PeerTip { peer, height, cumulative_difficulty });
+                }
+                Ok(_) => log::debug!("Unexpected tip response from {peer}"),
+                Err(e) => log::debug!("Tip request to {peer} failed: {e}"),
+            }
+        }
+        tips
+    }
+
+    /// Fetch the headers following the last block we share with the best peer
+    /// that returns a valid PoW chain.  Empty if no peer could.
+    async fn download_headers(&self, peers: &[PeerTip], locator: Vec<BlockHash>) -> Vec<BlockHeader> {
+        for p in peers {
+            let request = SyncRequest::GetHeaders { locator: locator.clone(), count: MAX_HEADERS_PER_REQUEST };
+            let headers = match self.network.request(p.peer, request).await {
+                Ok(SyncResponse::Headers(h)) if !h.is_empty() && h.len() <= MAX_HEADERS_PER_REQUEST as usize => h,
+                Ok(_) => {
+                    log::debug!("Peer {} returned no headers following our locator", p.peer);
+                    continue;
+                }
+                Err(e) => {
+                    log::debug!("Header request to {} failed: {e}", p.peer);
+                    continue;
+                }
+            };
+
+            // The window must start right after a block on our main chain.
+            let fork_hash = headers[0].previous_hash;
+            let ancestors = self.chain.read().retarget_headers(&fork_hash);
+            if ancestors.is_empty() {
+                log::warn!("❌ Headers from {} do not connect to our chain", p.peer);
+                continue;
+            }
+
+            // Argon2d is deliberately expensive – keep it off the async workers.
+            let consensus = self.consensus.clone();
+            let checked = tokio::task::spawn_blocking(move || {
+                verify_header_chain(&ancestors, fork_hash, &headers, &consensus).map(|_| headers)
+            })
+            .await;
+            match checked {
+                Ok(Ok(headers)) => return headers,
+                Ok(Err(e)) => log::warn!("❌ Invalid header chain from {}: {e}", p.peer),
+                Err(e) => log::error!("Header verification task failed: {e}"),
+            }
+        }
+        Vec::new()
+    }
+
+    /// Fetch bodies for `headers` in parallel and apply them in height order.
+    /// Peers that fail or serve bodies not matching the headers are dropped
+    /// for the rest of the round and their range is handed to another peer.
+    async fn download_blocks(&self, peers: &[PeerTip], headers: &[BlockHeader]) -> Result<u64> {
+        let first = headers[0].height;
+        let mut pending: VecDeque<(u64, u32)> = headers
+            .chunks(MAX_BLOCKS_PER_REQUEST as usize)
+            .map(|c| (c[0].height, c.len() as u32))
+            .collect();
+        let mut idle: Vec<PeerTip> = peers.to_vec();
+        let mut in_flight = FuturesUnordered::new();
+        let mut buffered: BTreeMap<u64, Block> = BTreeMap::new();
+        let mut next_height = first;
+        let mut applied = 0u64;
+
+        loop {
+            while let Some(&(start, count)) = pending.front() {
+                let end = start + count as u64 - 1;
+                let Some(idx) = idle.iter().position(|p| p.height >= end) else { break };
+                let peer = idle.swap_remove(idx);
+                pending.pop_front();
+                let network = self.network.clone();
+                in_flight.push(async move {
+                    let request = SyncRequest::GetBlocks { start_height: start, count };
+                    (peer, start, count, network.request(peer.peer, request).await)
+                });
+            }
+
+            let Some((peer, start, count, result)) = in_flight.next().await else { break };
+            let offset = (start - first) as usize;
+            let expected = &headers[offset..offset + count as usize];
+            match result.and_then(|response| match_bodies(response, expected)) {
+                Ok(blocks) => {
+                    let received = blocks.len() as u32;
+                    if received < count {
+                        pending.push_front((start + received as u64, count - received));
+                    }
+                    for block in blocks {
+                        buffered.insert(block.header.height, block);
+                    }
+                    idle.push(peer);
+                }
+                Err(e) => {
+                    log::warn!("Dropping sync peer {}: {e}", peer.peer);
+                    pending.push_front((start, count));
+                }
+            }
+
+            while let Some(block) = buffered.remove(&next_height) {
+                if NumiBlockchain::add_block_to(&self.chain, block, None).await? {
+                    applied += 1;
+                }
+                self.status.write().current_height = next_height;
+                next_height += 1;
+            }
+        }
+
+        if !pending.is_empty() {
+            log::warn!("Block sync stopped at height {}: no peer left to serve the remaining range", next_height);
+        }
+        Ok(applied)
+    }
+}
+
+/// Check that `headers` extend the main-chain block `base_hash` as a valid
+/// proof-of-work chain.  `ancestors` are the headers the difficulty
+/// retarget looks at, ending with that block's (see
+/// `NumiBlockchain::retarget_headers`); each header must carry the
+/// difficulty the retarget gives after the headers before it.
+fn verify_header_chain(
+    ancestors: &[BlockHeader],
+    base_hash: BlockHash,
+    headers: &[BlockHeader],
+    consensus: &ConsensusConfig,
+) -> Result<()> {
+    let Some(base) = ancestors.last() else {
+        return Err(InvalidBlockError::StaleChain.into());
+    };
+    let mut prev_hash = base_hash;
+    let mut prev_height = base.height;
+    let mut prev_timestamp = base.timestamp;
+    let mut branch: Vec<&BlockHeader> = ancestors.iter().collect();
+
+    for header in headers {
+        if !header.commits_state_root() {
+            return Err(InvalidBlockError::UnsupportedVersion(header.version).into());
+        }
+        if header.chain_id != consensus.chain_id {
+            return Err(InvalidBlockError::WrongChain(header.chain_id.clone()).into());
+        }
+        if header.previous_hash != prev_hash {
+            return Err(InvalidBlockError::PreviousBlockHashMismatch.into());
+        }
+        if header.height != prev_height + 1 {
+            return Err(InvalidBlockError::InvalidBlockHeight.into());
+        }
+        if header.timestamp <= prev_timestamp {
+            return Err(InvalidBlockError::TimestampOutOfRange(
+                "Header timestamp must be greater than its parent's".to_string(),
+            )
+            .into());
+        }
+        let expected = next_difficulty(&branch, consensus);
+        if header.difficulty != expected {
+            return Err(InvalidBlockError::WrongDifficulty { got: header.difficulty, expected }.into());
+        }
+        let hash = header.pow_hash(consensus)?;
+        if !meets_target(&hash, &generate_difficulty_target(header.difficulty)) {
+            return Err(InvalidBlockError::InvalidPoW.into());
+        }
+
+        prev_hash = hash;
+        prev_height = header.height;
+        prev_timestamp = header.timestamp;
+        branch.push(header);
+    }
+    Ok(())
+}
+
+/// Accept a (possibly truncated) run of bodies only if each one carries the
+/// header we already verified and its transactions match the merkle root.
+fn match_bodies(response: SyncResponse, headers: &[BlockHeader]) -> Result<Vec<Block>> {
+    let blocks = match response {
+        SyncResponse::Blocks(blocks) => blocks,
+        _ => return Err(BlockchainError::NetworkError("Unexpected sync response".to_string())),
+    };
+    if blocks.is_empty() || blocks.len() > headers.len() {
+        return Err(BlockchainError::NetworkError(format!(
+            "Expected 1..={} blocks, got {}",
+            headers.len(),
+            blocks.len()
+        )));
+    }
+    for (block, header) in blocks.iter().zip(headers) {
+        if block.header.calculate_hash()? != header.calculate_hash()? {
+            return Err(BlockchainError::NetworkError(format!(
+                "Block #{} does not match the advertised header",
+                header.height
+            )));
+        }
+        if !block.verify_merkle_root() {
+            return Err(InvalidBlockError::InvalidMerkleRoot.into());
+        }
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Dilithium3Keypair;
+
+    /// A mined child of `parent` at `difficulty`, a minute after it.
+    fn child(parent: &BlockHeader, consensus: &ConsensusConfig, difficulty: u32) -> BlockHeader {
+        let parent_hash = parent.pow_hash(consensus).unwrap();
+        let mut header = Block::new(&consensus.chain_id, parent.height + 1, parent_hash, vec![], difficulty, vec![]).header;
+        header.timestamp = parent.timestamp + chrono::Duration::minutes(1);
+        let target = generate_difficulty_target(difficulty);
+        while !meets_target(&header.pow_hash(consensus).unwrap(), &target) {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    #[test]
+    fn test_headers_must_carry_the_retargeted_difficulty() {
+        let consensus = ConsensusConfig::development();
+        let chain = NumiBlockchain::new_with_keypair(Dilithium3Keypair::new().unwrap(), consensus.clone()).unwrap();
+        let tip = chain.get_latest_block_hash();
+        let ancestors = chain.retarget_headers(&tip);
+
+        let first = child(&ancestors[0], &consensus, ancestors[0].difficulty);
+        verify_header_chain(&ancestors, tip, std::slice::from_ref(&first), &consensus).unwrap();
+
+        // Difficulty 0 is satisfied by any hash; only the retarget rule
+        // tells it apart from real work.
+        let cheap = child(&first, &consensus, 0);
+        match verify_header_chain(&ancestors, tip, &[first, cheap], &consensus) {
+            Err(BlockchainError::InvalidBlock(InvalidBlockError::WrongDifficulty { got: 0, .. })) => {}
+            other => panic!("expected WrongDifficulty, got {other:?}"),
+        }
+    }
+}
@@ -7,8 +7,10 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    address::Address,
     crypto::{blake3_hash_tx, Dilithium3Keypair, Dilithium3Signature},
     error::BlockchainError,
+    multisig::{MultisigPolicy, MultisigSignature, MULTISIG_SIGNATURE_BYTES},
     Result,
 };
 
@@ -18,17 +20,43 @@ pub type TransactionId = [u8; 32];
    Fee & size limits
 ---------------------------------------------------------------------*/
 pub const MAX_TX_BYTES: usize = 64 * 1024; // 64 KB hard cap
+pub const MAX_BATCH_TX_BYTES: usize = 1000 * 1024; // batches stay below the 1 MB gossip limit
+pub const MAX_BATCH_OUTPUTS: usize = 1000;
 pub const BASE_FEE:     u64   = 1;         // 1 nano-NUMI
 pub const FEE_PER_KIB:  u64   = 1;         // 1 nano per KiB (rounded up)
-pub const MAX_FEE:      u64   = 100;       // guard-rail
+pub const MAX_FEE:      u64   = 100;       // guard-rail (batches may pay their size fee)
+pub const DEFAULT_VALIDITY_SECS: i64 = 60 * 60; // valid 1 h after creation (or unlock)
 
 /* ---------------------------------------------------------------------
    Types
 ---------------------------------------------------------------------*/
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TransactionType {
-    Transfer { to: Vec<u8>, amount: u64, memo: Option<String> },
+    Transfer { to: Address, amount: u64, memo: Option<String> },
     MiningReward { block_height: u64, amount: u64 },
+    /// Spend from the account of `policy` (`from` must be its address);
+    /// authorised by `Transaction::multisig_signatures`.
+    MultisigTransfer { policy: MultisigPolicy, to: Address, amount: u64, memo: Option<String> },
+    /// Pay many recipients with one signature, nonce and fee.
+    BatchTransfer { outputs: Vec<(Address, u64)> },
+}
+
+/// Earliest point at which a transaction may be included in a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockTime {
+    /// Block height the including block must have reached
+    Height(u64),
+    /// Timestamp the including block must have reached
+    Time(DateTime<Utc>),
+}
+
+impl std::fmt::Display for LockTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockTime::Height(height) => write!(f, "height {height}"),
+            LockTime::Time(time) => write!(f, "{}", time.to_rfc3339()),
+        }
+    }
 }
 
 impl TransactionType {
@@ -40,40 +68,52 @@ impl TransactionType {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub id:            TransactionId,
+    pub chain_id:      String,
+    /// Signer's public key; the multisig address for `MultisigTransfer`
     pub from:          Vec<u8>,
     pub kind:          TransactionType,
     pub nonce:         u64,
     pub fee:           u64,
     pub timestamp:     DateTime<Utc>,
     pub valid_until:   DateTime<Utc>,
+    #[serde(default)]
+    pub lock_until:    Option<LockTime>,
     pub signature:     Option<Dilithium3Signature>,
+    /// Co-signer signatures of a `MultisigTransfer` (not covered by the id)
+    #[serde(default)]
+    pub multisig_signatures: Vec<MultisigSignature>,
 }
 
 #[derive(Serialize)]
 struct SigningView<'a> {
+    chain_id:    &'a str,     // replay protection across networks
     from:        &'a [u8],
     kind:        &'a TransactionType,
     nonce:       u64,
     fee:         u64,
     timestamp:   DateTime<Utc>,
     valid_until: DateTime<Utc>,
+    lock_until:  Option<LockTime>,
 }
 
 /* ---------------------------------------------------------------------
    Constructors
 ---------------------------------------------------------------------*/
 impl Transaction {
-    pub fn new(from: Vec<u8>, kind: TransactionType, nonce: u64) -> Self {
+    pub fn new(chain_id: &str, from: Vec<u8>, kind: TransactionType, nonce: u64) -> Self {
         let now = Utc::now();
         let mut tx = Self {
             id: [0; 32],
+            chain_id: chain_id.to_string(),
             from,
             kind,
             nonce,
             fee: BASE_FEE,
             timestamp: now,
-            valid_until: now + chrono::Duration::hours(1),
+            valid_until: now + chrono::Duration::seconds(DEFAULT_VALIDITY_SECS),
+            lock_until: None,
             signature: None,
+            multisig_signatures: Vec::new(),
         };
         
         // Set appropriate fee based on transaction type
@@ -87,8 +127,14 @@ impl Transaction {
         tx
     }
 
+    /// Sign as the sender.  For a `MultisigTransfer` this adds `kp`'s
+    /// co-signature instead.
     pub fn sign(&mut self, kp: &Dilithium3Keypair) -> Result<()> {
         self.validate_structure()?;
+        if self.multisig_policy().is_some() {
+            let part = self.multisig_partial_sign(kp)?;
+            return self.add_multisig_signature(part);
+        }
         let msg = self.signing_bytes()?;
         self.signature = Some(kp.sign(&msg)?);
         self.id = self.hash();
@@ -96,6 +142,9 @@ impl Transaction {
     }
 
     pub fn verify_signature(&self) -> Result<bool> {
+        if let Some(policy) = self.multisig_policy() {
+            return policy.verify(&self.signing_bytes()?, &self.multisig_signatures);
+        }
         if let Some(sig) = &self.signature {
             crate::crypto::Dilithium3Keypair::verify(&self.signing_bytes()?, sig, &self.from)
         } else {
@@ -103,21 +152,126 @@ impl Transaction {
         }
     }
 
+    /// Lock the transaction until `lock` (set before signing).  A time lock
+    /// keeps the transaction valid for the default window after it opens;
+    /// for a height lock set the window with `with_valid_until`.
+    pub fn with_lock_until(mut self, lock: LockTime) -> Self {
+        self.lock_until = Some(lock);
+        if let LockTime::Time(t) = lock {
+            self.valid_until = self.valid_until.max(t + chrono::Duration::seconds(DEFAULT_VALIDITY_SECS));
+        }
+        self.refresh_fee_and_id()
+    }
+
+    /// Accept the transaction until `valid_until` (set before signing).
+    pub fn with_valid_until(mut self, valid_until: DateTime<Utc>) -> Self {
+        self.valid_until = valid_until;
+        self.refresh_fee_and_id()
+    }
+
+    fn refresh_fee_and_id(mut self) -> Self {
+        if !self.kind.is_reward() {
+            self.fee = self.fee.max(self.min_fee().total);
+        }
+        self.id = self.hash();
+        self
+    }
+
+    /// May the transaction go into a block at `height` with `timestamp`?
+    pub fn is_unlocked_at(&self, height: u64, timestamp: DateTime<Utc>) -> bool {
+        match self.lock_until {
+            None => true,
+            Some(LockTime::Height(h)) => height >= h,
+            Some(LockTime::Time(t)) => timestamp >= t,
+        }
+    }
+
+    /// Account the transaction spends from (or a reward pays).  Only rewards
+    /// and multisig spends name it by address; any other `from` is the
+    /// signer's key, so a raw address there never reaches that account.
+    pub fn sender(&self) -> Address {
+        match self.kind {
+            TransactionType::MiningReward { .. } | TransactionType::MultisigTransfer { .. } => {
+                Address::from_sender(&self.from)
+            }
+            _ => Address::from_public_key(&self.from),
+        }
+    }
+
+    /* ---------------- multisig helpers ------------ */
+    pub fn multisig_policy(&self) -> Option<&MultisigPolicy> {
+        match &self.kind {
+            TransactionType::MultisigTransfer { policy, .. } => Some(policy),
+            _ => None,
+        }
+    }
+
+    /// Co-signer `kp`'s signature, produced without modifying the
+    /// transaction so it can be made offline and handed to whoever collects
+    /// the signatures.
+    pub fn multisig_partial_sign(&self, kp: &Dilithium3Keypair) -> Result<MultisigSignature> {
+        let policy = self
+            .multisig_policy()
+            .ok_or_else(|| BlockchainError::InvalidTransaction("not a multisig transaction".into()))?;
+        policy.sign(&self.signing_bytes()?, kp)
+    }
+
+    /// Attach a co-signer's signature after checking it.  A second
+    /// signature from the same co-signer replaces the first.
+    pub fn add_multisig_signature(&mut self, part: MultisigSignature) -> Result<()> {
+        let policy = self
+            .multisig_policy()
+            .ok_or_else(|| BlockchainError::InvalidTransaction("not a multisig transaction".into()))?;
+        if !policy.verify_one(&self.signing_bytes()?, &part)? {
+            return Err(BlockchainError::InvalidSignature("Invalid multisig co-signature".into()));
+        }
+        self.multisig_signatures.retain(|s| s.key_index != part.key_index);
+        self.multisig_signatures.push(part);
+        self.multisig_signatures.sort_by_key(|s| s.key_index);
+        Ok(())
+    }
+
+    /// Co-signatures still needed before the transaction is valid.
+    pub fn multisig_signatures_missing(&self) -> usize {
+        self.multisig_policy()
+            .map(|p| (p.threshold as usize).saturating_sub(self.multisig_signatures.len()))
+            .unwrap_or(0)
+    }
+
     /* ---------------- fee helpers ---------------- */
     fn min_fee(&self) -> FeeInfo {
-        let size = self.signing_bytes().map(|b| b.len()).unwrap_or(0);
-        FeeInfo::for_size(size)
+        let size = self.charged_size().unwrap_or(0);
+        match self.kind {
+            TransactionType::BatchTransfer { .. } => FeeInfo::for_batch_size(size),
+            _ => FeeInfo::for_size(size),
+        }
+    }
+
+    /// Bytes the fee pays for: the signing bytes plus, for a multisig
+    /// transfer, its co-signatures – at least `threshold` of them, so the
+    /// fee set before co-signing still covers them.
+    fn charged_size(&self) -> Result<usize> {
+        let mut size = self.signing_bytes()?.len();
+        if let Some(policy) = self.multisig_policy() {
+            let attached = bincode::serialized_size(&self.multisig_signatures)
+                .map_err(|e| BlockchainError::SerializationError(e.to_string()))? as usize;
+            let missing = (policy.threshold as usize).saturating_sub(self.multisig_signatures.len());
+            size += attached + missing * MULTISIG_SIGNATURE_BYTES;
+        }
+        Ok(size)
     }
 
     /* ---------------- hash & serialization -------- */
     fn signing_bytes(&self) -> Result<Vec<u8>> {
         let view = SigningView {
+            chain_id: &self.chain_id,
             from: &self.from,
             kind: &self.kind,
             nonce: self.nonce,
             fee: self.fee,
             timestamp: self.timestamp,
             valid_until: self.valid_until,
+            lock_until: self.lock_until,
         };
         bincode::serialize(&view)
             .map_err(|e| BlockchainError::SerializationError(e.to_string()))
@@ -133,15 +287,37 @@ impl Transaction {
    Validation & helpers
 ---------------------------------------------------------------------*/
 impl Transaction {
+    /// Full check before a transaction is signed or admitted: the
+    /// consensus rules plus its validity window against the local clock.
     pub fn validate_structure(&self) -> Result<()> {
+        self.validate_consensus()?;
+
+        // timestamp / expiry
+        let now = Utc::now();
+        if self.timestamp > now + chrono::Duration::minutes(5) {
+            return Err(BlockchainError::InvalidTransaction("future ts".into()));
+        }
+        if now > self.valid_until {
+            return Err(BlockchainError::InvalidTransaction("expired".into()));
+        }
+        Ok(())
+    }
+
+    /// Rules every transaction in a block must meet, independent of when
+    /// the block is checked.
+    pub fn validate_consensus(&self) -> Result<()> {
         // pubkey sanity
         if self.from.is_empty() || self.from.len() > 10_000 {
             return Err(BlockchainError::InvalidTransaction("bad sender pk".into()));
         }
 
         // size limit
-        let sz = self.signing_bytes()?.len();
-        if sz > MAX_TX_BYTES {
+        let sz = self.charged_size()?;
+        let max_size = match self.kind {
+            TransactionType::BatchTransfer { .. } => MAX_BATCH_TX_BYTES,
+            _ => MAX_TX_BYTES,
+        };
+        if sz > max_size {
             return Err(BlockchainError::InvalidTransaction("tx too large".into()));
         }
 
@@ -151,26 +327,27 @@ impl Transaction {
             if self.fee < min.total {
                 return Err(BlockchainError::InvalidTransaction("fee too low".into()));
             }
-            if self.fee > MAX_FEE {
+            if self.fee > MAX_FEE.max(min.total) {
                 return Err(BlockchainError::InvalidTransaction("fee too high".into()));
             }
         } else if self.fee != 0 {
             return Err(BlockchainError::InvalidTransaction("reward fee must be 0".into()));
         }
 
-        // timestamp / expiry
-        let now = Utc::now();
-        if self.timestamp > now + chrono::Duration::minutes(5) {
-            return Err(BlockchainError::InvalidTransaction("future ts".into()));
-        }
-        if now > self.valid_until {
-            return Err(BlockchainError::InvalidTransaction("expired".into()));
+        match self.lock_until {
+            Some(_) if self.kind.is_reward() => {
+                return Err(BlockchainError::InvalidTransaction("reward cannot be locked".into()));
+            }
+            Some(LockTime::Time(t)) if t > self.valid_until => {
+                return Err(BlockchainError::InvalidTransaction("expires before unlock".into()));
+            }
+            _ => {}
         }
 
         // kind-specific checks
         match &self.kind {
             TransactionType::Transfer { to, amount, memo } => {
-                if to.is_empty() { return Err(BlockchainError::InvalidTransaction("empty recipient".into())); }
+                to.validate()?;
                 if *amount == 0 { return Err(BlockchainError::InvalidTransaction("zero amount".into())); }
                 if let Some(m) = memo {
                     if m.len() > 256 || !m.is_ascii() {
@@ -183,6 +360,35 @@ impl Transaction {
                     return Err(BlockchainError::InvalidTransaction("zero reward".into()));
                 }
             }
+            TransactionType::BatchTransfer { outputs } => {
+                if outputs.is_empty() || outputs.len() > MAX_BATCH_OUTPUTS {
+                    return Err(BlockchainError::InvalidTransaction("bad output count".into()));
+                }
+                let mut total: u64 = 0;
+                for (to, amount) in outputs {
+                    to.validate()?;
+                    if *amount == 0 { return Err(BlockchainError::InvalidTransaction("zero amount".into())); }
+                    total = total
+                        .checked_add(*amount)
+                        .ok_or_else(|| BlockchainError::InvalidTransaction("amount overflow".into()))?;
+                }
+                total
+                    .checked_add(self.fee)
+                    .ok_or_else(|| BlockchainError::InvalidTransaction("amount overflow".into()))?;
+            }
+            TransactionType::MultisigTransfer { policy, to, amount, memo } => {
+                policy.validate()?;
+                if self.from != policy.address().to_bytes() {
+                    return Err(BlockchainError::InvalidTransaction("sender is not the multisig address".into()));
+                }
+                to.validate()?;
+                if *amount == 0 { return Err(BlockchainError::InvalidTransaction("zero amount".into())); }
+                if let Some(m) = memo {
+                    if m.len() > 256 || !m.is_ascii() {
+                        return Err(BlockchainError::InvalidTransaction("bad memo".into()));
+                    }
+                }
+            }
         }
         Ok(())
     }
@@ -191,6 +397,10 @@ impl Transaction {
         match &self.kind {
             TransactionType::Transfer { amount, .. } => *amount,
             TransactionType::MiningReward { amount, .. } => *amount,
+            TransactionType::MultisigTransfer { amount, .. } => *amount,
+            TransactionType::BatchTransfer { outputs } => {
+                outputs.iter().fold(0u64, |total, (_, amount)| total.saturating_add(*amount))
+            }
         }
     }
 
@@ -212,8 +422,14 @@ struct FeeInfo {
 }
 impl FeeInfo {
     fn for_size(bytes: usize) -> Self {
-        let size_fee = ((bytes as u64 + 1023) / 1024) * FEE_PER_KIB;   // ceil KiB
-        let total = BASE_FEE.saturating_add(size_fee).clamp(BASE_FEE, MAX_FEE);
+        let total = Self::for_batch_size(bytes).total.clamp(BASE_FEE, MAX_FEE);
+        Self { total }
+    }
+
+    /// Batches pay for every KiB they occupy, past `MAX_FEE`.
+    fn for_batch_size(bytes: usize) -> Self {
+        let size_fee = (bytes as u64).div_ceil(1024) * FEE_PER_KIB;   // ceil KiB
+        let total = BASE_FEE.saturating_add(size_fee);
         Self { total }
     }
 }
@@ -224,19 +440,117 @@ impl FeeInfo {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::address::ADDRESS_LEN;
     use crate::crypto::Dilithium3Keypair;
 
     #[test]
     fn fee_calc() {
         let f = FeeInfo::for_size(1500);
         assert_eq!(f.total, BASE_FEE + 2); // 1.5 KiB ⇒ 2 KiB (integer division), so total = 1 + 2 = 3
+        assert_eq!(FeeInfo::for_size(MAX_TX_BYTES * 4).total, MAX_FEE);
+        assert!(FeeInfo::for_batch_size(MAX_TX_BYTES * 4).total > MAX_FEE);
+    }
+
+    #[test]
+    fn batch_fee_follows_size() {
+        let kp = Dilithium3Keypair::new().unwrap();
+        let outputs: Vec<(Address, u64)> = (0..1000u32).map(|i| (Address::from_public_key(&i.to_le_bytes()), 10)).collect();
+        let mut tx = Transaction::new("test", kp.public_key.clone(), TransactionType::BatchTransfer { outputs }, 1);
+        let size = tx.signing_bytes().unwrap().len();
+        assert_eq!(tx.fee, FeeInfo::for_batch_size(size).total);
+        assert!(tx.fee > FeeInfo::for_size(0).total);
+        assert_eq!(tx.amount(), 10_000);
+        tx.sign(&kp).unwrap();
+        assert!(tx.verify_signature().unwrap());
+
+        let empty = Transaction::new("test", kp.public_key.clone(), TransactionType::BatchTransfer { outputs: vec![] }, 1);
+        assert!(empty.validate_structure().is_err());
+    }
+
+    #[test]
+    fn recipient_must_be_a_known_address_version() {
+        let kp = Dilithium3Keypair::new().unwrap();
+        let to = Address::from_public_key(&[1]);
+        let tx = Transaction::new("test", kp.public_key.clone(), TransactionType::Transfer { to, amount: 10, memo: None }, 1);
+
+        // An unknown version byte does not survive decoding.
+        let mut bytes = bincode::serialize(&tx).unwrap();
+        let at = bytes.windows(ADDRESS_LEN).position(|w| w == to.to_bytes().as_slice()).unwrap();
+        bytes[at] = 9;
+        assert!(bincode::deserialize::<Transaction>(&bytes).is_err());
+    }
+
+    #[test]
+    fn only_rewards_and_multisig_name_the_sender_by_address() {
+        let victim = Address::from_public_key(&[3u8; 1952]);
+        let spend = Transaction::new("test", victim.to_bytes(), TransactionType::Transfer { to: Address::from_public_key(&[1]), amount: 10, memo: None }, 1);
+        assert_ne!(spend.sender(), victim);
+
+        let reward = Transaction::new("test", victim.to_bytes(), TransactionType::MiningReward { block_height: 1, amount: 10 }, 0);
+        assert_eq!(reward.sender(), victim);
     }
 
     #[test]
     fn sign_and_verify() {
         let kp = Dilithium3Keypair::new().unwrap();
-        let mut tx = Transaction::new(kp.public_key.clone(), TransactionType::Transfer { to: vec![1], amount: 10, memo: None }, 1);
+        let mut tx = Transaction::new("test", kp.public_key.clone(), TransactionType::Transfer { to: Address::from_public_key(&[1]), amount: 10, memo: None }, 1);
         tx.sign(&kp).unwrap();
         assert!(tx.verify_signature().unwrap());
     }
+
+    #[test]
+    fn signature_is_bound_to_chain_id() {
+        let kp = Dilithium3Keypair::new().unwrap();
+        let mut tx = Transaction::new("testnet", kp.public_key.clone(), TransactionType::Transfer { to: Address::from_public_key(&[1]), amount: 10, memo: None }, 1);
+        tx.sign(&kp).unwrap();
+
+        let mut replayed = tx.clone();
+        replayed.chain_id = "mainnet".to_string();
+        assert!(!replayed.verify_signature().unwrap());
+        assert_ne!(replayed.hash(), tx.id);
+    }
+
+    #[test]
+    fn lock_until_height_and_time() {
+        let kp = Dilithium3Keypair::new().unwrap();
+        let transfer = TransactionType::Transfer { to: Address::from_public_key(&[1]), amount: 10, memo: None };
+        let now = Utc::now();
+
+        let mut by_height = Transaction::new("test", kp.public_key.clone(), transfer.clone(), 1).with_lock_until(LockTime::Height(10));
+        by_height.sign(&kp).unwrap();
+        assert!(by_height.verify_signature().unwrap());
+        assert!(!by_height.is_unlocked_at(9, now));
+        assert!(by_height.is_unlocked_at(10, now));
+
+        let unlock = now + chrono::Duration::minutes(10);
+        let by_time = Transaction::new("test", kp.public_key.clone(), transfer.clone(), 1).with_lock_until(LockTime::Time(unlock));
+        assert!(!by_time.is_unlocked_at(100, now));
+        assert!(by_time.is_unlocked_at(0, unlock));
+
+        // A time lock moves the expiry past the unlock.
+        let later = now + chrono::Duration::hours(2);
+        let waiting = Transaction::new("test", kp.public_key.clone(), transfer.clone(), 1).with_lock_until(LockTime::Time(later));
+        assert!(waiting.valid_until > later);
+        assert!(waiting.validate_structure().is_ok());
+
+        // A lock past an explicit expiry could never be mined.
+        let stuck = Transaction::new("test", kp.public_key.clone(), transfer, 1)
+            .with_lock_until(LockTime::Time(later))
+            .with_valid_until(now + chrono::Duration::hours(1));
+        assert!(stuck.validate_structure().is_err());
+    }
+
+    #[test]
+    fn lock_until_defaults_when_missing() {
+        let kp = Dilithium3Keypair::new().unwrap();
+        let transfer = TransactionType::Transfer { to: Address::from_public_key(&[1]), amount: 10, memo: None };
+        let mut tx = Transaction::new("test", kp.public_key.clone(), transfer, 1);
+        tx.sign(&kp).unwrap();
+
+        let mut json = serde_json::to_value(&tx).unwrap();
+        json.as_object_mut().unwrap().remove("lock_until");
+        let parsed: Transaction = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.lock_until, None);
+        assert!(parsed.verify_signature().unwrap());
+    }
 }
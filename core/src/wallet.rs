@@ -0,0 +1,328 @@
+// src/wallet.rs
+//
+// Encrypted wallet files.
+// --------------------------------------------------------------
+// • A wallet file is a `SecureKeyStore`: Argon2id-derived AES-256-GCM over
+//   the whole file and again over every key.  `DEFAULT_KEY_ID` is the
+//   account `send` and mining use
+// • Passphrases come from a `PassphraseSource`: a passphrase file, the
+//   `NUMI_WALLET_PASSPHRASE` environment variable, or a hidden prompt
+// • Plaintext JSON/PEM key files from older releases are refused until
+//   `migrate_wallet` encrypts them in place
+// • A wallet may hold further named accounts, imported from PEM; any account
+//   can be exported back to PEM
+// • Signed transactions travel between an offline signer and a node as
+//   hex-encoded bincode files, the format `POST /transaction/raw` accepts
+//
+
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use zeroize::Zeroizing;
+
+use crate::{
+    address::Address,
+    crypto::Dilithium3Keypair,
+    error::BlockchainError,
+    secure_storage::{KeyDerivationConfig, SecureKeyStore},
+    transaction::Transaction,
+    Result,
+};
+
+/// Environment variable read before prompting for a passphrase.
+pub const WALLET_PASSPHRASE_ENV: &str = "NUMI_WALLET_PASSPHRASE";
+/// Key id of a wallet's main account.
+pub const DEFAULT_KEY_ID: &str = "default";
+
+/// Where a wallet passphrase comes from.
+#[derive(Clone)]
+pub enum PassphraseSource {
+    /// `NUMI_WALLET_PASSPHRASE` if set, otherwise a hidden prompt
+    Prompt,
+    /// First line of a file, for unattended nodes
+    File(PathBuf),
+    /// Given directly (embedding and tests)
+    Value(Zeroizing<String>),
+}
+
+impl PassphraseSource {
+    /// Read from `file` if one is given, otherwise from the environment or
+    /// a prompt.
+    pub fn from_file_or_prompt(file: Option<PathBuf>) -> Self {
+        file.map(Self::File).unwrap_or(Self::Prompt)
+    }
+
+    pub fn value(passphrase: &str) -> Self {
+        Self::Value(Zeroizing::new(passphrase.to_string()))
+    }
+
+    /// The passphrase.  A new one (`confirm`) is asked for twice when
+    /// prompting.
+    pub fn passphrase(&self, confirm: bool) -> Result<Zeroizing<String>> {
+        let passphrase = match self {
+            Self::Value(passphrase) => passphrase.clone(),
+            Self::File(path) => {
+                let text = Zeroizing::new(fs::read_to_string(path).map_err(|e| {
+                    BlockchainError::IoError(format!("Failed to read passphrase file {}: {e}", path.display()))
+                })?);
+                Zeroizing::new(text.lines().next().unwrap_or_default().to_string())
+            }
+            Self::Prompt => match std::env::var(WALLET_PASSPHRASE_ENV) {
+                Ok(passphrase) if !passphrase.is_empty() => Zeroizing::new(passphrase),
+                _ => {
+                    let passphrase = Zeroizing::new(rpassword::prompt_password("Wallet passphrase: ")?);
+                    if confirm {
+                        let again = Zeroizing::new(rpassword::prompt_password("Repeat passphrase: ")?);
+                        if *again != *passphrase {
+                            return Err(BlockchainError::InvalidArgument("Passphrases do not match".into()));
+                        }
+                    }
+                    passphrase
+                }
+            },
+        };
+        if passphrase.is_empty() {
+            return Err(BlockchainError::InvalidArgument("Wallet passphrase must not be empty".into()));
+        }
+        Ok(passphrase)
+    }
+}
+
+/// Argon2 parameters of wallet files.  Opening a file needs the parameters
+/// it was written with, so these only change together with a file version.
+fn wallet_kdf() -> KeyDerivationConfig {
+    KeyDerivationConfig::default()
+}
+
+/// Is `path` a key file written in plaintext by an older release?
+pub fn is_plaintext_wallet(path: &Path) -> Result<bool> {
+    let content = fs::read(path)?;
+    let text = String::from_utf8_lossy(&content);
+    Ok(serde_json::from_slice::<serde_json::Value>(&content).is_ok() || text.contains("-----BEGIN"))
+}
+
+/// Write a new encrypted wallet holding `keypair`.  Existing files are
+/// never overwritten.
+pub fn create_wallet(path: &Path, keypair: &Dilithium3Keypair, passphrase: &PassphraseSource) -> Result<()> {
+    if path.exists() {
+        return Err(BlockchainError::InvalidArgument(format!("{} already exists", path.display())));
+    }
+    let passphrase = passphrase.passphrase(true)?;
+    write_wallet(path, DEFAULT_KEY_ID, keypair, &passphrase)
+}
+
+fn write_wallet(path: &Path, account: &str, keypair: &Dilithium3Keypair, passphrase: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut store = SecureKeyStore::with_config(path, wallet_kdf())?;
+    store.initialize(passphrase)?;
+    store.store_keypair(account, keypair, passphrase)
+}
+
+/// Keypair of the encrypted wallet at `path`.
+pub fn load_wallet(path: &Path, passphrase: &PassphraseSource) -> Result<Dilithium3Keypair> {
+    load_account(path, DEFAULT_KEY_ID, passphrase)
+}
+
+/// Keypair of `account` in the encrypted wallet at `path`.  Plaintext key
+/// files must be migrated first.
+pub fn load_account(path: &Path, account: &str, passphrase: &PassphraseSource) -> Result<Dilithium3Keypair> {
+    let (mut store, passphrase) = open_wallet(path, passphrase)?;
+    store.get_keypair(account, &passphrase)
+}
+
+fn open_wallet(path: &Path, passphrase: &PassphraseSource) -> Result<(SecureKeyStore, Zeroizing<String>)> {
+    if is_plaintext_wallet(path)? {
+        return Err(BlockchainError::InvalidArgument(format!(
+            "{} is not encrypted; run `numi-core wallet migrate {}` first",
+            path.display(),
+            path.display()
+        )));
+    }
+    let passphrase = passphrase.passphrase(false)?;
+    let mut store = SecureKeyStore::with_config(path, wallet_kdf())?;
+    store.load_from_disk(&passphrase)?;
+    Ok((store, passphrase))
+}
+
+/// Accounts of the wallet at `path` with their addresses, sorted by name.
+pub fn list_accounts(path: &Path, passphrase: &PassphraseSource) -> Result<Vec<(String, Address)>> {
+    let (mut store, passphrase) = open_wallet(path, passphrase)?;
+    let mut accounts = store.list_keys();
+    accounts.sort();
+    accounts
+        .into_iter()
+        .map(|account| {
+            let keypair = store.get_keypair(&account, &passphrase)?;
+            Ok((account, Address::from_public_key(&keypair.public_key)))
+        })
+        .collect()
+}
+
+/// Add `keypair` to the wallet at `path` as `account`, creating the wallet
+/// if there is none.  Existing accounts are never replaced.
+pub fn import_account(path: &Path, account: &str, keypair: &Dilithium3Keypair, passphrase: &PassphraseSource) -> Result<()> {
+    if account.is_empty() {
+        return Err(BlockchainError::InvalidArgument("Account name must not be empty".into()));
+    }
+    if !path.exists() {
+        let passphrase = passphrase.passphrase(true)?;
+        return write_wallet(path, account, keypair, &passphrase);
+    }
+    let (mut store, passphrase) = open_wallet(path, passphrase)?;
+    if store.list_keys().iter().any(|id| id == account) {
+        return Err(BlockchainError::InvalidArgument(format!(
+            "Account '{account}' already exists in {}",
+            path.display()
+        )));
+    }
+    store.store_keypair(account, keypair, &passphrase)
+}
+
+/// Write `keypair` unencrypted as PEM to a new file readable only by its
+/// owner.
+pub fn export_pem(output: &Path, keypair: &Dilithium3Keypair) -> Result<()> {
+    let pem = keypair.to_pem().to_pem_string();
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(output).map_err(|e| {
+        BlockchainError::IoError(format!("Failed to create {}: {e}", output.display()))
+    })?;
+    file.write_all(pem.as_bytes())?;
+    Ok(())
+}
+
+/// Write a signed transaction for `read_signed_transaction` or
+/// `POST /transaction/raw`.  Existing files are never overwritten.
+pub fn write_signed_transaction(path: &Path, tx: &Transaction) -> Result<()> {
+    if tx.signature.is_none() && tx.multisig_signatures.is_empty() {
+        return Err(BlockchainError::InvalidSignature("Transaction is not signed".into()));
+    }
+    let bytes = bincode::serialize(tx).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path).map_err(|e| {
+        BlockchainError::IoError(format!("Failed to create {}: {e}", path.display()))
+    })?;
+    writeln!(file, "{}", hex::encode(bytes))?;
+    Ok(())
+}
+
+/// Signed transaction written by `write_signed_transaction`.  Its
+/// signature is checked here so a damaged file is caught before it is sent.
+pub fn read_signed_transaction(path: &Path) -> Result<Transaction> {
+    let text = fs::read_to_string(path)?;
+    let bytes = hex::decode(text.trim())
+        .map_err(|e| BlockchainError::SerializationError(format!("{} is not a signed transaction: {e}", path.display())))?;
+    let tx: Transaction = bincode::deserialize(&bytes)
+        .map_err(|e| BlockchainError::SerializationError(format!("{} is not a signed transaction: {e}", path.display())))?;
+    if !tx.verify_signature()? {
+        return Err(BlockchainError::InvalidSignature(format!("{} has an invalid signature", path.display())));
+    }
+    Ok(tx)
+}
+
+/// Encrypt the plaintext key file at `path` in place.  The encrypted copy
+/// is read back before it replaces the original.
+pub fn migrate_wallet(path: &Path, passphrase: &PassphraseSource) -> Result<Dilithium3Keypair> {
+    if !is_plaintext_wallet(path)? {
+        return Err(BlockchainError::InvalidArgument(format!("{} is already encrypted", path.display())));
+    }
+    let keypair = Dilithium3Keypair::load_from_file(path)?;
+    let passphrase = passphrase.passphrase(true)?;
+
+    let mut staging = path.as_os_str().to_owned();
+    staging.push(".migrating");
+    let staging = PathBuf::from(staging);
+    if staging.exists() {
+        fs::remove_file(&staging)?;
+    }
+    write_wallet(&staging, DEFAULT_KEY_ID, &keypair, &passphrase)?;
+    let check = load_wallet(&staging, &PassphraseSource::Value(passphrase.clone()))?;
+    if check.public_key != keypair.public_key {
+        fs::remove_file(&staging)?;
+        return Err(BlockchainError::CryptographyError("Encrypted wallet does not match the original".into()));
+    }
+    fs::rename(&staging, path)?;
+    Ok(keypair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wallet_round_trip_and_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        let keypair = Dilithium3Keypair::new().unwrap();
+        let passphrase = PassphraseSource::value("correct horse");
+
+        let path = dir.path().join("wallet.json");
+        create_wallet(&path, &keypair, &passphrase).unwrap();
+        assert!(!is_plaintext_wallet(&path).unwrap());
+        assert_eq!(load_wallet(&path, &passphrase).unwrap().public_key, keypair.public_key);
+        assert!(load_wallet(&path, &PassphraseSource::value("wrong")).is_err());
+        assert!(create_wallet(&path, &keypair, &passphrase).is_err());
+
+        let legacy = dir.path().join("legacy.json");
+        keypair.save_to_file(&legacy).unwrap();
+        assert!(is_plaintext_wallet(&legacy).unwrap());
+        assert!(load_wallet(&legacy, &passphrase).is_err());
+        migrate_wallet(&legacy, &passphrase).unwrap();
+        assert!(!is_plaintext_wallet(&legacy).unwrap());
+        assert_eq!(load_wallet(&legacy, &passphrase).unwrap().public_key, keypair.public_key);
+        assert!(migrate_wallet(&legacy, &passphrase).is_err());
+    }
+
+    #[test]
+    fn test_accounts_and_pem_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let passphrase = PassphraseSource::value("correct horse");
+        let main = Dilithium3Keypair::new().unwrap();
+        let cold = Dilithium3Keypair::new().unwrap();
+
+        let path = dir.path().join("wallet.json");
+        create_wallet(&path, &main, &passphrase).unwrap();
+        let pem = dir.path().join("cold.pem");
+        export_pem(&pem, &cold).unwrap();
+        assert!(export_pem(&pem, &cold).is_err());
+        let imported = Dilithium3Keypair::load_from_file(&pem).unwrap();
+        import_account(&path, "cold", &imported, &passphrase).unwrap();
+        assert!(import_account(&path, "cold", &imported, &passphrase).is_err());
+
+        let accounts = list_accounts(&path, &passphrase).unwrap();
+        assert_eq!(
+            accounts,
+            vec![
+                ("cold".to_string(), Address::from_public_key(&cold.public_key)),
+                (DEFAULT_KEY_ID.to_string(), Address::from_public_key(&main.public_key)),
+            ]
+        );
+        assert_eq!(load_account(&path, "cold", &passphrase).unwrap().public_key, cold.public_key);
+    }
+
+    #[test]
+    fn test_signed_transaction_file() {
+        use crate::transaction::TransactionType;
+
+        let dir = tempfile::tempdir().unwrap();
+        let keypair = Dilithium3Keypair::new().unwrap();
+        let to = Address::from_public_key(&[7; 32]);
+        let mut tx = Transaction::new("test", keypair.public_key.clone(), TransactionType::Transfer { to, amount: 5, memo: None }, 1);
+
+        let path = dir.path().join("tx.hex");
+        assert!(write_signed_transaction(&path, &tx).is_err());
+        tx.sign(&keypair).unwrap();
+        write_signed_transaction(&path, &tx).unwrap();
+        assert_eq!(read_signed_transaction(&path).unwrap().id, tx.id);
+
+        tx.fee += 1;
+        let tampered = dir.path().join("tampered.hex");
+        write_signed_transaction(&tampered, &tx).unwrap();
+        assert!(read_signed_transaction(&tampered).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{extend, mine_on_tip};
+use numi_core::{
+    address::Address,
+    blockchain::NumiBlockchain,
+    config::{ConsensusConfig, DEVELOPMENT_CHAIN_ID},
+    crypto::Dilithium3Keypair,
+    error::{BlockchainError, InvalidBlockError},
+    transaction::{Transaction, TransactionType},
+};
+
+fn batch(from: &Dilithium3Keypair, outputs: Vec<(Address, u64)>, nonce: u64) -> Transaction {
+    let mut tx = Transaction::new(DEVELOPMENT_CHAIN_ID, from.public_key.clone(), TransactionType::BatchTransfer { outputs }, nonce);
+    tx.sign(from).unwrap();
+    tx
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn batch_pays_every_output_from_one_debit() {
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), ConsensusConfig::development()).unwrap();
+    let start = chain.get_balance_by_pubkey(&miner.public_key);
+    let mut recipients: Vec<Address> = (1..=3u8).map(|i| Address::from_public_key(&[i; 32])).collect();
+    recipients.push(Address::from_public_key(&alice.public_key));
+
+    let tx = batch(&miner, recipients.iter().map(|to| (*to, 10)).collect(), 1);
+    let fee = tx.fee;
+    let block = extend(&chain, vec![tx], &miner).await;
+    let reward = block.transactions[0].amount();
+
+    for to in &recipients {
+        assert_eq!(chain.get_balance(to), 10);
+    }
+    let sender = chain.get_account_state_or_default(&miner.public_key);
+    assert_eq!(sender.balance, start + reward - 40 - fee);
+    assert_eq!(sender.nonce, 1);
+
+    // A batch the sender cannot cover credits nobody.  Alice mines nothing,
+    // so no reward in the same block can make up the difference.
+    let too_big = batch(
+        &alice,
+        vec![(Address::from_public_key(&[9; 32]), 1), (Address::from_public_key(&[8; 32]), 10)],
+        1,
+    );
+    assert!(chain.add_block(mine_on_tip(&chain, vec![too_big], &miner)).await.is_err());
+    assert_eq!(chain.get_balance_by_pubkey(&[9; 32]), 0);
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 10);
+    assert_eq!(chain.get_current_height(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn block_with_malformed_batch_is_rejected() {
+    let miner = Dilithium3Keypair::new().unwrap();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), ConsensusConfig::development()).unwrap();
+
+    // Structural rules apply to block transactions, not only at admission.
+    let mut empty = batch(&miner, vec![(Address::from_public_key(&[1; 32]), 10)], 1);
+    empty.kind = TransactionType::BatchTransfer { outputs: vec![] };
+    match chain.add_block(mine_on_tip(&chain, vec![empty], &miner)).await {
+        Err(BlockchainError::InvalidBlock(InvalidBlockError::InvalidTransaction(msg))) => {
+            assert!(msg.contains("output count"), "{msg}")
+        }
+        other => panic!("expected InvalidTransaction, got {other:?}"),
+    }
+    assert_eq!(chain.get_current_height(), 0);
+}
@@ -0,0 +1,109 @@
+//! Block-building helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use numi_core::{
+    block::Block,
+    blockchain::{meets_target, NumiBlockchain},
+    config::ConsensusConfig,
+    crypto::{generate_difficulty_target, Dilithium3Keypair},
+    miner::WalletManager,
+    transaction::{Transaction, TransactionType},
+};
+
+/// `consensus` with a genesis read from a file in `dir` that funds `kp`
+/// with the initial reward.  Unlike the development genesis it is the same
+/// on every chain built from it, so a twin chain can mine the branches fed
+/// to another.
+pub fn shared_genesis(consensus: ConsensusConfig, dir: &Path, kp: &Dilithium3Keypair) -> ConsensusConfig {
+    shared_genesis_at(consensus, dir, kp, 1)
+}
+
+/// `shared_genesis` with a genesis block at `difficulty`, which its child
+/// inherits.
+pub fn shared_genesis_at(consensus: ConsensusConfig, dir: &Path, kp: &Dilithium3Keypair, difficulty: u32) -> ConsensusConfig {
+    let path = dir.join("genesis.toml");
+    let spec = format!(
+        "[genesis]\nchain_id = \"{}\"\ntimestamp = \"2025-01-01T00:00:00Z\"\ndifficulty = {}\n\n\
+         [[genesis.accounts.accounts]]\naddress = \"{}\"\nbalance = {}\n",
+        consensus.chain_id,
+        difficulty,
+        hex::encode(&kp.public_key),
+        consensus.initial_mining_reward
+    );
+    std::fs::write(&path, spec).unwrap();
+    ConsensusConfig { genesis_file: Some(path), ..consensus }
+}
+
+/// Mine a child of the chain tip carrying `txs` and connect it.
+pub async fn extend(chain: &NumiBlockchain, txs: Vec<Transaction>, kp: &Dilithium3Keypair) -> Block {
+    let block = mine_on_tip(chain, txs, kp);
+    assert!(chain.add_block(block.clone()).await.unwrap());
+    block
+}
+
+/// Build a child of the chain tip carrying `txs` and mine it.  Blocks the
+/// chain cannot apply get no state root; they are rejected anyway.
+pub fn mine_on_tip(chain: &NumiBlockchain, txs: Vec<Transaction>, kp: &Dilithium3Keypair) -> Block {
+    let consensus = chain.consensus_params();
+    let mut block = tip_child(chain, txs, kp, &consensus);
+    block.header.state_root = chain.state_root_after(&block.transactions).unwrap_or_default();
+    seal(block, kp, &consensus)
+}
+
+/// Build a child of the chain tip committing to `state_root` and mine it.
+pub fn mine_on_tip_with_root(chain: &NumiBlockchain, txs: Vec<Transaction>, state_root: [u8; 32], kp: &Dilithium3Keypair) -> Block {
+    let consensus = chain.consensus_params();
+    let mut block = tip_child(chain, txs, kp, &consensus);
+    block.header.state_root = state_root;
+    seal(block, kp, &consensus)
+}
+
+fn tip_child(chain: &NumiBlockchain, txs: Vec<Transaction>, kp: &Dilithium3Keypair, consensus: &ConsensusConfig) -> Block {
+    let height = chain.get_current_height() + 1;
+    let txs = with_reward(height, txs, kp, consensus);
+    Block::new(
+        &consensus.chain_id,
+        height,
+        chain.get_latest_block_hash(),
+        txs,
+        chain.get_current_difficulty(),
+        kp.public_key.clone(),
+    )
+}
+
+/// `txs` preceded by a reward paying the subsidy and their fees to `kp`.
+fn with_reward(height: u64, txs: Vec<Transaction>, kp: &Dilithium3Keypair, consensus: &ConsensusConfig) -> Vec<Transaction> {
+    let fees: u64 = txs.iter().map(|t| t.fee).sum();
+    let subsidy = WalletManager::calculate_mining_reward_with_config(height, consensus);
+    let mut reward = Transaction::new(
+        &consensus.chain_id,
+        kp.public_key.clone(),
+        TransactionType::MiningReward { block_height: height, amount: subsidy + fees },
+        0,
+    );
+    reward.sign(kp).unwrap();
+
+    let mut all = vec![reward];
+    all.extend(txs);
+    all
+}
+
+/// `block` moved onto `previous_hash` and mined again at `difficulty`.
+pub fn remine(mut block: Block, previous_hash: [u8; 32], difficulty: u32, kp: &Dilithium3Keypair, consensus: &ConsensusConfig) -> Block {
+    block.header.previous_hash = previous_hash;
+    block.header.difficulty = difficulty;
+    block.header.nonce = 0;
+    seal(block, kp, consensus)
+}
+
+/// Find a nonce meeting the header's difficulty and sign the block.
+fn seal(mut block: Block, kp: &Dilithium3Keypair, consensus: &ConsensusConfig) -> Block {
+    let target = generate_difficulty_target(block.header.difficulty);
+    while !meets_target(&block.header.pow_hash(consensus).unwrap(), &target) {
+        block.header.nonce += 1;
+    }
+    block.sign(kp, None).unwrap();
+    block
+}
@@ -0,0 +1,44 @@
+use numi_core::{blockchain::NumiBlockchain, config::ConsensusConfig, crypto::Dilithium3Keypair};
+use tempfile::tempdir;
+
+const SPEC: &str = r#"
+[genesis]
+chain_id = "genesis-test"
+timestamp = "2025-07-04T13:33:37Z"
+difficulty = 1
+
+[[genesis.accounts.accounts]]
+address = "0a0b0c"
+balance = 500
+
+[[genesis.accounts.accounts]]
+address = "0d0e0f"
+balance = 250
+"#;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn nodes_share_genesis_from_spec_file() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("genesis.toml");
+    std::fs::write(&path, SPEC).unwrap();
+    let consensus = ConsensusConfig {
+        chain_id: "genesis-test".to_string(),
+        genesis_file: Some(path),
+        ..ConsensusConfig::development()
+    };
+
+    // Two nodes with different local keys agree on genesis.
+    let a = NumiBlockchain::new_with_config(consensus.clone(), Dilithium3Keypair::new().unwrap(), None).unwrap();
+    let b = NumiBlockchain::new_with_config(consensus.clone(), Dilithium3Keypair::new().unwrap(), None).unwrap();
+    assert_eq!(a.genesis_hash(), b.genesis_hash());
+    assert_eq!(a.get_balance_by_pubkey(&[0x0a, 0x0b, 0x0c]), 500);
+    assert_eq!(a.get_balance_by_pubkey(&[0x0d, 0x0e, 0x0f]), 250);
+    assert_eq!(a.get_chain_state().total_supply, 750);
+
+    // A node pinned to a different genesis refuses to start.
+    let pinned = ConsensusConfig { genesis_hash: Some(hex::encode([7u8; 32])), ..consensus.clone() };
+    assert!(NumiBlockchain::new_with_config(pinned, Dilithium3Keypair::new().unwrap(), None).is_err());
+
+    let pinned = ConsensusConfig { genesis_hash: Some(hex::encode(a.genesis_hash())), ..consensus };
+    assert!(NumiBlockchain::new_with_config(pinned, Dilithium3Keypair::new().unwrap(), None).is_ok());
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::{extend, mine_on_tip, shared_genesis};
+use numi_core::{
+    address::Address,
+    blockchain::NumiBlockchain,
+    config::{ConsensusConfig, DEVELOPMENT_CHAIN_ID},
+    crypto::Dilithium3Keypair,
+    error::{BlockchainError, InvalidBlockError},
+    mempool::ValidationResult,
+    transaction::{LockTime, Transaction, TransactionType},
+};
+use tempfile::tempdir;
+
+fn locked_transfer(from: &Dilithium3Keypair, to: &Dilithium3Keypair, nonce: u64, lock: LockTime) -> Transaction {
+    let mut tx = Transaction::new(
+        DEVELOPMENT_CHAIN_ID,
+        from.public_key.clone(),
+        TransactionType::Transfer { to: Address::from_public_key(&to.public_key), amount: 100, memo: None },
+        nonce,
+    )
+    .with_lock_until(lock);
+    tx.sign(from).unwrap();
+    tx
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn mempool_admits_locked_transactions_once_they_open() {
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), ConsensusConfig::development()).unwrap();
+
+    let tx = locked_transfer(&miner, &alice, 1, LockTime::Height(2));
+    assert_eq!(
+        chain.add_transaction(tx.clone()).await.unwrap(),
+        ValidationResult::TransactionLocked { lock_until: LockTime::Height(2) }
+    );
+
+    // The next block is #2 once #1 is on chain.
+    extend(&chain, vec![], &miner).await;
+    assert_eq!(chain.add_transaction(tx).await.unwrap(), ValidationResult::Valid);
+
+    let unlock = Utc::now() + Duration::hours(3);
+    let by_time = locked_transfer(&miner, &alice, 2, LockTime::Time(unlock));
+    assert!(by_time.valid_until > unlock);
+    assert_eq!(
+        chain.add_transaction(by_time).await.unwrap(),
+        ValidationResult::TransactionLocked { lock_until: LockTime::Time(unlock) }
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn block_with_immature_locked_transaction_is_rejected() {
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), ConsensusConfig::development()).unwrap();
+
+    let early = locked_transfer(&miner, &alice, 1, LockTime::Height(2));
+    match chain.add_block(mine_on_tip(&chain, vec![early.clone()], &miner)).await {
+        Err(BlockchainError::InvalidBlock(InvalidBlockError::InvalidTransaction(msg))) => assert!(msg.contains("locked")),
+        other => panic!("expected a locked transaction error, got {other:?}"),
+    }
+    assert_eq!(chain.get_current_height(), 0);
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 0);
+
+    extend(&chain, vec![], &miner).await;
+    extend(&chain, vec![early], &miner).await;
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 100);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn mempool_follows_the_tip_height_across_reorgs() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis(ConsensusConfig::development(), dir.path(), &miner);
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+    let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+
+    extend(&chain, vec![], &miner).await;
+    for _ in 0..3 {
+        let block = extend(&twin, vec![], &miner).await;
+        chain.add_block(block).await.unwrap();
+    }
+    assert_eq!(chain.get_current_height(), 3);
+
+    let mut tx = Transaction::new(
+        &consensus.chain_id,
+        miner.public_key.clone(),
+        TransactionType::Transfer { to: Address::from_public_key(&alice.public_key), amount: 100, memo: None },
+        1,
+    )
+    .with_lock_until(LockTime::Height(4));
+    tx.sign(&miner).unwrap();
+    assert_eq!(chain.add_transaction(tx).await.unwrap(), ValidationResult::Valid);
+}
@@ -0,0 +1,296 @@
+mod common;
+
+use common::extend;
+use dashmap::DashMap;
+use numi_core::{
+    address::Address,
+    blockchain::{AccountState, NumiBlockchain},
+    config::{ConsensusConfig, DEVELOPMENT_CHAIN_ID, MAINNET_CHAIN_ID},
+    crypto::Dilithium3Keypair,
+    mempool::{TransactionMempool, ValidationResult, MAX_NONCE_GAP, MEMPOOL_FILE},
+    transaction::{Transaction, TransactionType},
+};
+
+fn transfer(kp: &Dilithium3Keypair, amount: u64, nonce: u64, fee: Option<u64>) -> Transaction {
+    let mut tx = Transaction::new(
+        MAINNET_CHAIN_ID,
+        kp.public_key.clone(),
+        TransactionType::Transfer { to: Address::from_public_key(&[7; 32]), amount, memo: None },
+        nonce,
+    );
+    if let Some(fee) = fee {
+        tx.fee = fee;
+    }
+    tx.sign(kp).unwrap();
+    tx
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn higher_fee_replaces_pending_transaction() {
+    let mempool = TransactionMempool::new();
+    let kp = Dilithium3Keypair::new().unwrap();
+
+    let original = transfer(&kp, 10, 1, None);
+    assert_eq!(mempool.add_transaction(original.clone()).await.unwrap(), ValidationResult::Valid);
+
+    // Same nonce without a fee bump is refused.
+    let same_fee = transfer(&kp, 11, 1, Some(original.fee));
+    match mempool.add_transaction(same_fee).await.unwrap() {
+        ValidationResult::ReplacementFeeTooLow { got, .. } => assert_eq!(got, original.fee),
+        other => panic!("expected ReplacementFeeTooLow, got {other:?}"),
+    }
+
+    let bumped = transfer(&kp, 11, 1, Some(original.fee + 10));
+    assert_eq!(
+        mempool.add_transaction(bumped.clone()).await.unwrap(),
+        ValidationResult::Replaced { previous: original.id }
+    );
+    let pending = mempool.all_transactions();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, bumped.id);
+
+    // Later nonces are unaffected by the replacement.
+    assert_eq!(mempool.add_transaction(transfer(&kp, 5, 2, None)).await.unwrap(), ValidationResult::Valid);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn future_nonce_waits_for_the_gap() {
+    let mempool = TransactionMempool::new();
+    let kp = Dilithium3Keypair::new().unwrap();
+
+    // Nonce 2 arrives first and pays more, but cannot be mined before nonce 1.
+    let first = transfer(&kp, 5, 1, None);
+    let second = transfer(&kp, 5, 2, Some(first.fee + 10));
+    assert_eq!(
+        mempool.add_transaction(second.clone()).await.unwrap(),
+        ValidationResult::Pending { missing_nonce: 1 }
+    );
+    assert!(mempool.select_for_block(&DashMap::new(), 1 << 20, 100).is_empty());
+
+    assert_eq!(mempool.add_transaction(first.clone()).await.unwrap(), ValidationResult::Valid);
+    let ids: Vec<_> = mempool.select_for_block(&DashMap::new(), 1 << 20, 100).iter().map(|tx| tx.id).collect();
+    assert_eq!(ids, vec![first.id, second.id]);
+
+    // Nonces already used, or too far ahead, are refused.
+    assert!(matches!(
+        mempool.add_transaction(transfer(&kp, 5, 0, None)).await.unwrap(),
+        ValidationResult::InvalidNonce { expected: 1, .. }
+    ));
+    assert!(matches!(
+        mempool.add_transaction(transfer(&kp, 5, 1 + MAX_NONCE_GAP, None)).await.unwrap(),
+        ValidationResult::InvalidNonce { .. }
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn fee_estimate_follows_mined_transactions() {
+    let mempool = TransactionMempool::new();
+    assert_eq!(mempool.estimate_fee(1).samples, 0);
+
+    let txs: Vec<Transaction> = (0..10)
+        .map(|_| transfer(&Dilithium3Keypair::new().unwrap(), 5, 1, None))
+        .collect();
+    for tx in &txs {
+        assert_eq!(mempool.add_transaction(tx.clone()).await.unwrap(), ValidationResult::Valid);
+    }
+    mempool.record_mined(1, &txs);
+
+    let estimate = mempool.estimate_fee(1);
+    assert_eq!(estimate.samples, 10);
+    assert!(estimate.fee_per_mib > 0);
+    // Paying what those transactions paid is enough.
+    let size = bincode::serialize(&txs[0]).unwrap().len();
+    assert!(estimate.fee_for_size(size) <= txs[0].fee);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn mempool_survives_restart_with_admission_times() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(MEMPOOL_FILE);
+    let kp = Dilithium3Keypair::new().unwrap();
+
+    let mempool = TransactionMempool::new();
+    let txs = [transfer(&kp, 5, 1, None), transfer(&kp, 6, 2, None), transfer(&kp, 7, 4, None)];
+    for tx in &txs {
+        assert!(mempool.add_transaction(tx.clone()).await.unwrap().is_accepted());
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let age = mempool.transaction_age(&txs[0].id).unwrap();
+    assert_eq!(mempool.save_to_file(&path).await.unwrap(), 3);
+
+    let restarted = TransactionMempool::new();
+    assert_eq!(restarted.load_from_file(&path).await.unwrap(), 3);
+    assert!(restarted.transaction_age(&txs[0].id).unwrap() >= age);
+    // Nonce 3 is still missing, so only the first two are minable.
+    let ids: Vec<_> = restarted.select_for_block(&DashMap::new(), 1 << 20, 100).iter().map(|tx| tx.id).collect();
+    assert_eq!(ids, vec![txs[0].id, txs[1].id]);
+
+    // Nothing saved yet is not an error.
+    assert_eq!(TransactionMempool::new().load_from_file(&dir.path().join("missing")).await.unwrap(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn expired_saved_transactions_are_dropped_on_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(MEMPOOL_FILE);
+    let (short, long) = (Dilithium3Keypair::new().unwrap(), Dilithium3Keypair::new().unwrap());
+
+    let mut expiring = Transaction::new(
+        MAINNET_CHAIN_ID,
+        short.public_key.clone(),
+        TransactionType::Transfer { to: Address::from_public_key(&[7; 32]), amount: 5, memo: None },
+        1,
+    )
+    .with_valid_until(chrono::Utc::now() + chrono::Duration::seconds(1));
+    expiring.sign(&short).unwrap();
+    let kept = transfer(&long, 5, 1, None);
+
+    let mempool = TransactionMempool::new();
+    for tx in [&expiring, &kept] {
+        assert!(mempool.add_transaction(tx.clone()).await.unwrap().is_accepted());
+    }
+    assert_eq!(mempool.save_to_file(&path).await.unwrap(), 2);
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+
+    // The expired entry is dropped; the rest of the file still loads.
+    let restarted = TransactionMempool::new();
+    assert_eq!(restarted.load_from_file(&path).await.unwrap(), 1);
+    assert!(restarted.get_transaction(&expiring.id).is_none());
+    assert!(restarted.get_transaction(&kept.id).is_some());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn evicting_a_transaction_leaves_later_nonces_pending() {
+    let mempool = TransactionMempool::new();
+    let kp = Dilithium3Keypair::new().unwrap();
+    let txs = [transfer(&kp, 5, 1, None), transfer(&kp, 6, 2, None)];
+    for tx in &txs {
+        mempool.add_transaction(tx.clone()).await.unwrap();
+    }
+    assert_eq!(mempool.account_nonces(&Address::from_public_key(&kp.public_key)), (0, vec![1, 2]));
+
+    assert!(mempool.evict(&txs[0].id).await);
+    assert!(!mempool.evict(&txs[0].id).await);
+    assert!(mempool.get_transaction(&txs[0].id).is_none());
+    assert_eq!(mempool.account_nonces(&Address::from_public_key(&kp.public_key)), (0, vec![2]));
+    assert!(mempool.select_for_block(&DashMap::new(), 1 << 20, 100).is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn chain_mempool_checks_balances_and_queued_spends() {
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), ConsensusConfig::development()).unwrap();
+    let pay = |from: &Dilithium3Keypair, amount: u64, nonce: u64| {
+        let mut tx = Transaction::new(
+            DEVELOPMENT_CHAIN_ID,
+            from.public_key.clone(),
+            TransactionType::Transfer { to: Address::from_public_key(&[7; 32]), amount, memo: None },
+            nonce,
+        );
+        tx.sign(from).unwrap();
+        tx
+    };
+
+    // Unknown accounts have nothing to spend.
+    assert!(matches!(
+        chain.add_transaction(pay(&alice, 5, 1)).await.unwrap(),
+        ValidationResult::InsufficientBalance { available: 0, .. }
+    ));
+
+    // A queued transaction's spend counts against the next one.
+    let balance = chain.get_balance_by_pubkey(&miner.public_key);
+    let first = pay(&miner, balance / 2, 1);
+    assert_eq!(chain.add_transaction(first.clone()).await.unwrap(), ValidationResult::Valid);
+    match chain.add_transaction(pay(&miner, balance / 2, 2)).await.unwrap() {
+        ValidationResult::InsufficientBalance { required, available } => {
+            assert_eq!(available, balance);
+            assert!(required > balance);
+        }
+        other => panic!("expected InsufficientBalance, got {other:?}"),
+    }
+
+    // Balances follow the chain.
+    let mut fund = Transaction::new(
+        DEVELOPMENT_CHAIN_ID,
+        miner.public_key.clone(),
+        TransactionType::Transfer { to: Address::from_public_key(&alice.public_key), amount: 50, memo: None },
+        2,
+    );
+    fund.sign(&miner).unwrap();
+    extend(&chain, vec![first, fund], &miner).await;
+    assert_eq!(chain.add_transaction(pay(&alice, 5, 1)).await.unwrap(), ValidationResult::Valid);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn replacement_must_leave_later_nonces_payable() {
+    let miner = Dilithium3Keypair::new().unwrap();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), ConsensusConfig::development()).unwrap();
+    let pay = |amount: u64, nonce: u64, fee: Option<u64>| {
+        let mut tx = Transaction::new(
+            DEVELOPMENT_CHAIN_ID,
+            miner.public_key.clone(),
+            TransactionType::Transfer { to: Address::from_public_key(&[7; 32]), amount, memo: None },
+            nonce,
+        );
+        if let Some(fee) = fee {
+            tx.fee = fee;
+        }
+        tx.sign(&miner).unwrap();
+        tx
+    };
+
+    let balance = chain.get_balance_by_pubkey(&miner.public_key);
+    let first = pay(balance / 4, 1, None);
+    let second = pay(balance / 4, 2, None);
+    for tx in [&first, &second] {
+        assert_eq!(chain.add_transaction(tx.clone()).await.unwrap(), ValidationResult::Valid);
+    }
+
+    // Paying the bumped fee but spending what nonce 2 needs is refused.
+    let greedy = pay(balance * 3 / 4, 1, Some(first.fee * 2));
+    assert!(matches!(
+        chain.add_transaction(greedy).await.unwrap(),
+        ValidationResult::InsufficientBalance { available, .. } if available == balance
+    ));
+    let modest = pay(balance / 4 + 1, 1, Some(first.fee * 2));
+    assert_eq!(
+        chain.add_transaction(modest.clone()).await.unwrap(),
+        ValidationResult::Replaced { previous: first.id }
+    );
+
+    // Both nonces still make a block the chain accepts.
+    let mut block = chain.block_template(&miner, 1 << 20, 100).unwrap();
+    let ids: Vec<_> = block.transactions[1..].iter().map(|tx| tx.id).collect();
+    assert_eq!(ids, vec![modest.id, second.id]);
+    block.mine(&miner, &chain.consensus_params()).unwrap();
+    assert!(chain.add_block(block).await.unwrap());
+    assert!(chain.mempool_handle().all_transactions().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn block_selection_leaves_out_what_the_state_cannot_pay() {
+    let (alice, bob) = (Dilithium3Keypair::new().unwrap(), Dilithium3Keypair::new().unwrap());
+    let funded = |balance: u64| {
+        let accounts = DashMap::new();
+        for kp in [&alice, &bob] {
+            let key = Address::from_public_key(&kp.public_key).to_bytes();
+            accounts.insert(key, AccountState { balance, ..Default::default() });
+        }
+        accounts
+    };
+
+    let mempool = TransactionMempool::new();
+    let accounts = funded(1_000);
+    let keys: Vec<Vec<u8>> = accounts.iter().map(|e| e.key().clone()).collect();
+    mempool.sync_accounts_from_chain(&accounts, &keys, 0);
+    let queued = [transfer(&alice, 400, 1, None), transfer(&alice, 400, 2, None), transfer(&bob, 10, 1, None)];
+    for tx in &queued {
+        assert_eq!(mempool.add_transaction(tx.clone()).await.unwrap(), ValidationResult::Valid);
+    }
+
+    // On a state where Alice cannot pay nonce 1, nonce 2 stays out too.
+    let ids: Vec<_> = mempool.select_for_block(&funded(300), 1 << 20, 100).iter().map(|tx| tx.id).collect();
+    assert_eq!(ids, vec![queued[2].id]);
+    assert_eq!(mempool.select_for_block(&funded(1_000), 1 << 20, 100).len(), 3);
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{extend, mine_on_tip};
+use numi_core::{
+    address::Address,
+    blockchain::NumiBlockchain,
+    config::{ConsensusConfig, DEVELOPMENT_CHAIN_ID},
+    crypto::Dilithium3Keypair,
+    error::{BlockchainError, InvalidBlockError},
+    mempool::ValidationResult,
+    multisig::{MultisigPolicy, MULTISIG_SIGNATURE_BYTES},
+    transaction::{Transaction, TransactionType, FEE_PER_KIB},
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn two_of_three_account_spends_with_collected_signatures() {
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let signers: Vec<Dilithium3Keypair> = (0..3).map(|_| Dilithium3Keypair::new().unwrap()).collect();
+    let policy = MultisigPolicy::new(2, signers.iter().map(|k| k.public_key.clone()).collect()).unwrap();
+    let treasury = policy.address();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), ConsensusConfig::development()).unwrap();
+
+    let mut fund = Transaction::new(
+        DEVELOPMENT_CHAIN_ID,
+        miner.public_key.clone(),
+        TransactionType::Transfer { to: treasury, amount: 500, memo: None },
+        1,
+    );
+    fund.sign(&miner).unwrap();
+    extend(&chain, vec![fund], &miner).await;
+    assert_eq!(chain.get_balance(&treasury), 500);
+
+    let mut spend = Transaction::new(
+        DEVELOPMENT_CHAIN_ID,
+        treasury.to_bytes(),
+        TransactionType::MultisigTransfer {
+            policy: policy.clone(),
+            to: Address::from_public_key(&alice.public_key),
+            amount: 100,
+            memo: None,
+        },
+        1,
+    );
+    // The fee covers the co-signatures, which are not in the signing bytes.
+    assert!(spend.fee >= (2 * MULTISIG_SIGNATURE_BYTES / 1024) as u64 * FEE_PER_KIB);
+
+    // Co-signers sign independently; the coordinator collects the parts.
+    let first = spend.multisig_partial_sign(&signers[0]).unwrap();
+    let second = spend.multisig_partial_sign(&signers[2]).unwrap();
+    spend.add_multisig_signature(first).unwrap();
+    assert_eq!(spend.multisig_signatures_missing(), 1);
+    assert_eq!(chain.add_transaction(spend.clone()).await.unwrap(), ValidationResult::InvalidSignature);
+
+    spend.add_multisig_signature(second).unwrap();
+    assert_eq!(spend.multisig_signatures_missing(), 0);
+    assert!(spend.verify_signature().unwrap());
+    assert_eq!(chain.add_transaction(spend.clone()).await.unwrap(), ValidationResult::Valid);
+
+    let fee = spend.fee;
+    extend(&chain, vec![spend], &miner).await;
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 100);
+    assert_eq!(chain.get_balance(&treasury), 500 - 100 - fee);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn block_spending_with_another_policy_is_rejected() {
+    let miner = Dilithium3Keypair::new().unwrap();
+    let thief = Dilithium3Keypair::new().unwrap();
+    let signers: Vec<Dilithium3Keypair> = (0..2).map(|_| Dilithium3Keypair::new().unwrap()).collect();
+    let treasury = MultisigPolicy::new(2, signers.iter().map(|k| k.public_key.clone()).collect()).unwrap().address();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), ConsensusConfig::development()).unwrap();
+
+    let mut fund = Transaction::new(
+        DEVELOPMENT_CHAIN_ID,
+        miner.public_key.clone(),
+        TransactionType::Transfer { to: treasury, amount: 500, memo: None },
+        1,
+    );
+    fund.sign(&miner).unwrap();
+    extend(&chain, vec![fund], &miner).await;
+
+    // The thief's own 1-of-1 policy signs a spend from the treasury.  The
+    // signatures are valid for that policy; only the binding of `from` to
+    // the policy address stops it.
+    let own = MultisigPolicy::new(1, vec![thief.public_key.clone()]).unwrap();
+    let mut theft = Transaction::new(
+        DEVELOPMENT_CHAIN_ID,
+        treasury.to_bytes(),
+        TransactionType::MultisigTransfer {
+            policy: own,
+            to: Address::from_public_key(&thief.public_key),
+            amount: 400,
+            memo: None,
+        },
+        1,
+    );
+    let part = theft.multisig_partial_sign(&thief).unwrap();
+    theft.add_multisig_signature(part).unwrap();
+    assert!(theft.verify_signature().unwrap());
+
+    match chain.add_block(mine_on_tip(&chain, vec![theft], &miner)).await {
+        Err(BlockchainError::InvalidBlock(InvalidBlockError::InvalidTransaction(_))) => {}
+        other => panic!("expected InvalidTransaction, got {other:?}"),
+    }
+    assert_eq!(chain.get_current_height(), 1);
+    assert_eq!(chain.get_balance(&treasury), 500);
+    assert_eq!(chain.get_balance_by_pubkey(&thief.public_key), 0);
+}
@@ -0,0 +1,218 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{extend, mine_on_tip, shared_genesis};
+use numi_core::{
+    address::Address,
+    blockchain::NumiBlockchain,
+    config::ConsensusConfig,
+    crypto::Dilithium3Keypair,
+    state_tree::StateTree,
+    storage::{BlockchainStorage, TxDirection},
+    transaction::{Transaction, TransactionType},
+};
+use tempfile::tempdir;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn restart_restores_chain_from_storage() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis(ConsensusConfig::development(), dir.path(), &miner);
+
+    let (tip, tip_hash, state) = {
+        let storage = Arc::new(BlockchainStorage::new(dir.path()).unwrap());
+        let chain = NumiBlockchain::new_with_config(consensus.clone(), miner.clone(), Some(storage)).unwrap();
+        let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+
+        let mut tx = Transaction::new(
+            &consensus.chain_id,
+            miner.public_key.clone(),
+            TransactionType::Transfer { to: Address::from_public_key(&alice.public_key), amount: 100, memo: None },
+            1,
+        );
+        tx.sign(&miner).unwrap();
+
+        // Alice is only funded on the branch that gets abandoned, so her
+        // account must be removed from storage again by the reorg.
+        extend(&chain, vec![tx], &miner).await;
+        assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 100);
+
+        let fork1 = extend(&twin, vec![], &miner).await;
+        let fork2 = extend(&twin, vec![], &miner).await;
+        chain.add_block(fork1).await.unwrap();
+        chain.add_block(fork2.clone()).await.unwrap();
+        assert_eq!(chain.get_current_height(), 2);
+
+        (fork2, chain.get_latest_block_hash(), chain.get_chain_state())
+    };
+
+    let storage = Arc::new(BlockchainStorage::new(dir.path()).unwrap());
+    let chain = NumiBlockchain::load_from_storage(&storage, consensus.clone(), miner.clone()).await.unwrap();
+
+    assert_eq!(chain.get_current_height(), 2);
+    assert_eq!(chain.get_latest_block_hash(), tip_hash);
+    assert_eq!(chain.get_block_height(&tip.header.previous_hash), Some(1));
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 0);
+    assert!(chain.get_account_state_or_default(&miner.public_key).balance > 0);
+    let restored = chain.get_chain_state();
+    assert_eq!(restored.total_supply, state.total_supply);
+    assert_eq!(restored.cumulative_difficulty, state.cumulative_difficulty);
+
+    // The restored chain keeps extending from the stored tip.
+    extend(&chain, vec![], &miner).await;
+    assert_eq!(chain.get_current_height(), 3);
+    assert_eq!(storage.load_chain_state().unwrap().unwrap().total_blocks, 4);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn failed_reorg_leaves_storage_on_the_old_chain() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis(ConsensusConfig::development(), dir.path(), &miner);
+
+    let tip_hash = {
+        let storage = Arc::new(BlockchainStorage::new(dir.path()).unwrap());
+        let chain = NumiBlockchain::new_with_config(consensus.clone(), miner.clone(), Some(storage)).unwrap();
+        let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+
+        let mut tx = Transaction::new(
+            &consensus.chain_id,
+            miner.public_key.clone(),
+            TransactionType::Transfer { to: Address::from_public_key(&alice.public_key), amount: 100, memo: None },
+            1,
+        );
+        tx.sign(&miner).unwrap();
+        extend(&chain, vec![tx], &miner).await;
+
+        // Alice has no funds on the fork, so its second block cannot apply.
+        let mut spend = Transaction::new(
+            &consensus.chain_id,
+            alice.public_key.clone(),
+            TransactionType::Transfer { to: Address::from_public_key(&miner.public_key), amount: 50, memo: None },
+            1,
+        );
+        spend.sign(&alice).unwrap();
+        chain.add_block(extend(&twin, vec![], &miner).await).await.unwrap();
+        assert!(chain.add_block(mine_on_tip(&twin, vec![spend], &miner)).await.is_err());
+        chain.get_latest_block_hash()
+    };
+
+    let storage = Arc::new(BlockchainStorage::new(dir.path()).unwrap());
+    let chain = NumiBlockchain::load_from_storage(&storage, consensus, miner.clone()).await.unwrap();
+    assert_eq!(chain.get_current_height(), 1);
+    assert_eq!(chain.get_latest_block_hash(), tip_hash);
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 100);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn restart_replays_blocks_after_checkpoint_snapshot() {
+    let dir = tempdir().unwrap();
+    let consensus = ConsensusConfig { checkpoint_interval: 2, ..ConsensusConfig::development() };
+    let miner = Dilithium3Keypair::new().unwrap();
+
+    let balance = {
+        let storage = Arc::new(BlockchainStorage::new(dir.path()).unwrap());
+        let chain = NumiBlockchain::new_with_config(consensus.clone(), miner.clone(), Some(storage)).unwrap();
+        for _ in 0..7 {
+            extend(&chain, vec![], &miner).await;
+        }
+        chain.get_balance_by_pubkey(&miner.public_key)
+    };
+
+    let storage = Arc::new(BlockchainStorage::new(dir.path()).unwrap());
+    let heights: Vec<u64> = storage.load_checkpoints().unwrap().iter().map(|c| c.block_height).collect();
+    assert_eq!(heights, vec![2, 4, 6]);
+    // Only the newest snapshots are kept.
+    assert!(storage.load_snapshot(2).unwrap().is_none());
+    assert!(storage.load_snapshot(6).unwrap().is_some());
+
+    let chain = NumiBlockchain::load_from_storage(&storage, consensus, miner.clone()).await.unwrap();
+    assert_eq!(chain.get_current_height(), 7);
+    assert_eq!(chain.get_balance_by_pubkey(&miner.public_key), balance);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn snapshot_not_matching_its_header_is_ignored() {
+    let dir = tempdir().unwrap();
+    let consensus = ConsensusConfig { checkpoint_interval: 2, ..ConsensusConfig::development() };
+    let miner = Dilithium3Keypair::new().unwrap();
+
+    let balance = {
+        let storage = Arc::new(BlockchainStorage::new(dir.path()).unwrap());
+        let chain = NumiBlockchain::new_with_config(consensus.clone(), miner.clone(), Some(storage.clone())).unwrap();
+        for _ in 0..5 {
+            extend(&chain, vec![], &miner).await;
+        }
+
+        // Inflate the miner in the newest snapshot and make its checkpoint
+        // record agree, so only the block header can expose it.
+        let mut snapshot = storage.load_snapshot(4).unwrap().unwrap();
+        for (_, account) in snapshot.iter_mut() {
+            account.balance += 1_000_000;
+        }
+        let mut checkpoint = storage.load_checkpoints().unwrap().pop().unwrap();
+        checkpoint.state_root = StateTree::from_accounts(snapshot.iter().map(|(k, a)| (k.as_slice(), a))).root();
+        storage
+            .transaction(|tx| {
+                tx.save_checkpoint(&checkpoint)?;
+                tx.save_snapshot(4, &snapshot)
+            })
+            .unwrap();
+        chain.get_balance_by_pubkey(&miner.public_key)
+    };
+
+    let storage = Arc::new(BlockchainStorage::new(dir.path()).unwrap());
+    let chain = NumiBlockchain::load_from_storage(&storage, consensus, miner.clone()).await.unwrap();
+    assert_eq!(chain.get_current_height(), 5);
+    assert_eq!(chain.get_balance_by_pubkey(&miner.public_key), balance);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn address_history_follows_reorgs_and_rebuilds() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis(ConsensusConfig::development(), dir.path(), &miner);
+    let (miner_address, alice_address) = (Address::from_public_key(&miner.public_key), Address::from_public_key(&alice.public_key));
+
+    {
+        let mut storage = BlockchainStorage::new(dir.path()).unwrap();
+        storage.set_address_index(true).unwrap();
+        let chain = NumiBlockchain::new_with_config(consensus.clone(), miner.clone(), Some(Arc::new(storage))).unwrap();
+        let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+
+        let mut tx = Transaction::new(
+            &consensus.chain_id,
+            miner.public_key.clone(),
+            TransactionType::Transfer { to: alice_address, amount: 100, memo: None },
+            1,
+        );
+        tx.sign(&miner).unwrap();
+        extend(&chain, vec![tx.clone()], &miner).await;
+
+        let history = chain.address_history(&alice_address, 0, 10).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!((history[0].height, history[0].tx_id, history[0].direction, history[0].amount), (1, tx.id, TxDirection::Received, 100));
+        let sent = chain.address_history(&miner_address, 0, 10).unwrap();
+        assert!(sent.iter().any(|e| e.direction == TxDirection::Sent && e.amount == 100 + tx.fee));
+        let account = chain.get_account_state_or_default(&alice.public_key);
+        assert_eq!((account.transaction_count, account.total_received), (1, 100));
+
+        // The transfer is undone by the reorg, and so is its history.
+        chain.add_block(extend(&twin, vec![], &miner).await).await.unwrap();
+        chain.add_block(extend(&twin, vec![], &miner).await).await.unwrap();
+        assert!(chain.address_history(&alice_address, 0, 10).unwrap().is_empty());
+    }
+
+    // Blocks committed while the index was off are picked up on re-enabling.
+    BlockchainStorage::new(dir.path()).unwrap().set_address_index(false).unwrap();
+    let mut storage = BlockchainStorage::new(dir.path()).unwrap();
+    storage.set_address_index(true).unwrap();
+    let chain = NumiBlockchain::load_from_storage(&Arc::new(storage), consensus, miner.clone()).await.unwrap();
+    let heights: Vec<u64> = chain.address_history(&miner_address, 0, 10).unwrap().iter().map(|e| e.height).collect();
+    assert_eq!(heights, vec![2, 1, 0]);
+    assert_eq!(chain.address_history(&miner_address, 1, 1).unwrap()[0].height, 1);
+}
@@ -0,0 +1,230 @@
+mod common;
+
+use common::{extend, mine_on_tip, remine, shared_genesis, shared_genesis_at};
+use numi_core::{
+    address::Address,
+    blockchain::NumiBlockchain,
+    config::{ConsensusConfig, DEVELOPMENT_CHAIN_ID},
+    crypto::Dilithium3Keypair,
+    error::{BlockchainError, InvalidBlockError},
+    transaction::{Transaction, TransactionType},
+};
+use tempfile::tempdir;
+
+fn transfer(from: &Dilithium3Keypair, to: &Dilithium3Keypair, amount: u64, nonce: u64) -> Transaction {
+    let mut tx = Transaction::new(
+        DEVELOPMENT_CHAIN_ID,
+        from.public_key.clone(),
+        TransactionType::Transfer { to: Address::from_public_key(&to.public_key), amount, memo: None },
+        nonce,
+    );
+    tx.sign(from).unwrap();
+    tx
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn heavier_branch_replaces_tip_and_returns_transactions() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis(ConsensusConfig::development(), dir.path(), &miner);
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+    let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+
+    let tx = transfer(&miner, &alice, 100, 1);
+    let main1 = mine_on_tip(&chain, vec![tx.clone()], &miner);
+    assert!(chain.add_block(main1.clone()).await.unwrap());
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 100);
+    assert_eq!(chain.find_transaction(&tx.id).map(|(_, height)| height), Some(1));
+    assert_eq!(chain.get_block_hash(1), Some(main1.calculate_hash(Some(&consensus)).unwrap()));
+
+    // Same work as the tip: kept as a side block, first-seen tip wins.
+    let fork1 = extend(&twin, vec![], &miner).await;
+    assert!(chain.add_block(fork1.clone()).await.unwrap());
+    assert_eq!(chain.get_current_height(), 1);
+    assert_eq!(chain.get_latest_block_hash(), main1.calculate_hash(Some(&consensus)).unwrap());
+
+    // More work: the node switches branches.
+    let fork2 = extend(&twin, vec![], &miner).await;
+    assert!(chain.add_block(fork2.clone()).await.unwrap());
+    assert_eq!(chain.get_current_height(), 2);
+    assert_eq!(chain.get_latest_block_hash(), fork2.calculate_hash(Some(&consensus)).unwrap());
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 0);
+    // Three blocks at difficulty 1, each worth 2^1.
+    assert_eq!(chain.get_chain_state().cumulative_difficulty, 6);
+
+    // The transfer only existed on the abandoned block.
+    let pending = chain.mempool_handle().all_transactions();
+    assert!(pending.iter().any(|t| t.id == tx.id));
+    assert!(chain.find_transaction(&tx.id).is_none());
+
+    // Adding the old tip again is a no-op.
+    assert!(!chain.add_block(main1).await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn reorg_evicts_spends_of_a_reversed_credit() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis(ConsensusConfig::development(), dir.path(), &miner);
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+    let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus).unwrap();
+
+    let credit = transfer(&miner, &alice, 100, 1);
+    extend(&chain, vec![credit.clone()], &miner).await;
+    let spend = transfer(&alice, &miner, 50, 1);
+    assert!(chain.add_transaction(spend.clone()).await.unwrap().is_accepted());
+
+    // The heavier branch never paid Alice, so her queued spend cannot stay.
+    for _ in 0..2 {
+        let fork = extend(&twin, vec![], &miner).await;
+        chain.add_block(fork).await.unwrap();
+    }
+    assert_eq!(chain.get_current_height(), 2);
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 0);
+    let pending: Vec<_> = chain.mempool_handle().all_transactions().iter().map(|t| t.id).collect();
+    assert!(!pending.contains(&spend.id));
+    assert!(pending.contains(&credit.id));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn invalid_branch_restores_original_chain() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis(ConsensusConfig::development(), dir.path(), &miner);
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+    let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+
+    let main1 = extend(&chain, vec![transfer(&miner, &alice, 100, 1)], &miner).await;
+
+    // Alice has no funds on the fork, so its second block cannot apply.
+    let fork1 = extend(&twin, vec![], &miner).await;
+    let fork2 = mine_on_tip(&twin, vec![transfer(&alice, &miner, 50, 1)], &miner);
+    chain.add_block(fork1).await.unwrap();
+    assert!(chain.add_block(fork2).await.is_err());
+
+    assert_eq!(chain.get_current_height(), 1);
+    assert_eq!(chain.get_latest_block_hash(), main1.calculate_hash(Some(&consensus)).unwrap());
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 100);
+    assert_eq!(chain.get_chain_state().cumulative_difficulty, 4);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn reorg_deeper_than_limit_is_rejected() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis(ConsensusConfig { max_reorg_depth: 1, ..ConsensusConfig::development() }, dir.path(), &miner);
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+    let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus).unwrap();
+
+    extend(&chain, vec![], &miner).await;
+    extend(&chain, vec![], &miner).await;
+
+    let fork1 = extend(&twin, vec![], &miner).await;
+    match chain.add_block(fork1).await {
+        Err(BlockchainError::InvalidBlock(InvalidBlockError::ReorgTooDeep { .. })) => {}
+        other => panic!("expected ReorgTooDeep, got {other:?}"),
+    }
+    assert_eq!(chain.get_current_height(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn out_of_order_blocks_connect_from_orphan_pool() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis(ConsensusConfig::development(), dir.path(), &miner);
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+    let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+
+    let b1 = extend(&twin, vec![], &miner).await;
+    let b2 = extend(&twin, vec![], &miner).await;
+    let b3 = extend(&twin, vec![], &miner).await;
+
+    for orphan in [b3.clone(), b2.clone()] {
+        match chain.add_block(orphan).await {
+            Err(BlockchainError::InvalidBlock(InvalidBlockError::OrphanBlock)) => {}
+            other => panic!("expected OrphanBlock, got {other:?}"),
+        }
+    }
+    assert_eq!(chain.get_current_height(), 0);
+    // Already pooled.
+    assert!(!chain.add_block(b2).await.unwrap());
+
+    assert!(chain.add_block(b1).await.unwrap());
+    assert_eq!(chain.get_current_height(), 3);
+    assert_eq!(chain.get_latest_block_hash(), b3.calculate_hash(Some(&consensus)).unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn orphans_below_chain_difficulty_are_not_pooled() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis(ConsensusConfig::development(), dir.path(), &miner);
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+    let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus).unwrap();
+    assert!(chain.get_current_difficulty() > 0);
+
+    // Difficulty 0 needs no work at all.
+    let b1 = extend(&twin, vec![], &miner).await;
+    let mut cheap = mine_on_tip(&twin, vec![], &miner);
+    cheap.header.difficulty = 0;
+    cheap.sign(&miner, None).unwrap();
+
+    match chain.add_block(cheap.clone()).await {
+        Err(BlockchainError::InvalidBlock(InvalidBlockError::DifficultyTooLow { got: 0, .. })) => {}
+        other => panic!("expected DifficultyTooLow, got {other:?}"),
+    }
+    assert!(chain.add_block(b1).await.unwrap());
+    assert_eq!(chain.get_current_height(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn longer_branch_below_the_expected_difficulty_does_not_reorg() {
+    let dir = tempdir().unwrap();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let consensus = shared_genesis_at(ConsensusConfig::development(), dir.path(), &miner, 4);
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+    let twin = NumiBlockchain::new_with_keypair(miner.clone(), consensus.clone()).unwrap();
+
+    let main1 = extend(&chain, vec![], &miner).await;
+    assert_eq!(main1.header.difficulty, 4);
+
+    // The twin's blocks carry valid state roots; remined at difficulty 1
+    // they form a branch whose summed difficulties (4 + 5) exceed the main
+    // chain's (4 + 4).
+    let mut parent = chain.get_block_hash(0).unwrap();
+    let mut cheap = Vec::new();
+    for _ in 0..5 {
+        let block = remine(extend(&twin, vec![], &miner).await, parent, 1, &miner, &consensus);
+        parent = block.calculate_hash(Some(&consensus)).unwrap();
+        cheap.push(block);
+    }
+
+    match chain.add_block(cheap[0].clone()).await {
+        Err(BlockchainError::InvalidBlock(InvalidBlockError::WrongDifficulty { got: 1, expected: 4 })) => {}
+        other => panic!("expected WrongDifficulty, got {other:?}"),
+    }
+    for block in &cheap[1..] {
+        assert!(chain.add_block(block.clone()).await.is_err());
+    }
+    assert_eq!(chain.get_current_height(), 1);
+    assert_eq!(chain.get_latest_block_hash(), main1.calculate_hash(Some(&consensus)).unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn version_1_blocks_are_rejected_after_genesis() {
+    let miner = Dilithium3Keypair::new().unwrap();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), ConsensusConfig::development()).unwrap();
+
+    let mut block = mine_on_tip(&chain, vec![], &miner);
+    block.header.version = 1;
+    block.header.state_root = [0u8; 32];
+    block.sign(&miner, None).unwrap();
+    match chain.add_block(block).await {
+        Err(BlockchainError::InvalidBlock(InvalidBlockError::UnsupportedVersion(1))) => {}
+        other => panic!("expected UnsupportedVersion, got {other:?}"),
+    }
+    assert_eq!(chain.get_current_height(), 0);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::{mine_on_tip, mine_on_tip_with_root};
+use numi_core::{
+    address::Address,
+    blockchain::NumiBlockchain,
+    config::{ConsensusConfig, DEVELOPMENT_CHAIN_ID},
+    crypto::Dilithium3Keypair,
+    error::{BlockchainError, InvalidBlockError},
+    transaction::{Transaction, TransactionType},
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn block_must_commit_to_post_state() {
+    let consensus = ConsensusConfig::development();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), consensus).unwrap();
+    assert_ne!(chain.get_block_by_height(0).unwrap().header.state_root, [0u8; 32]);
+
+    let mut tx = Transaction::new(
+        DEVELOPMENT_CHAIN_ID,
+        miner.public_key.clone(),
+        TransactionType::Transfer { to: Address::from_public_key(&alice.public_key), amount: 100, memo: None },
+        1,
+    );
+    tx.sign(&miner).unwrap();
+
+    let bad = mine_on_tip_with_root(&chain, vec![tx.clone()], [1u8; 32], &miner);
+    match chain.add_block(bad).await {
+        Err(BlockchainError::InvalidBlock(InvalidBlockError::StateRootMismatch)) => {}
+        other => panic!("expected StateRootMismatch, got {other:?}"),
+    }
+    assert_eq!(chain.get_current_height(), 0);
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 0);
+
+    let good = mine_on_tip(&chain, vec![tx], &miner);
+    assert!(chain.add_block(good).await.unwrap());
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 100);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn account_proofs_verify_against_header_state_roots() {
+    let consensus = ConsensusConfig::development();
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), consensus).unwrap();
+    let genesis = chain.get_block_by_height(0).unwrap();
+
+    let mut tx = Transaction::new(
+        DEVELOPMENT_CHAIN_ID,
+        miner.public_key.clone(),
+        TransactionType::Transfer { to: Address::from_public_key(&alice.public_key), amount: 100, memo: None },
+        1,
+    );
+    tx.sign(&miner).unwrap();
+    let block = mine_on_tip(&chain, vec![tx], &miner);
+    chain.add_block(block.clone()).await.unwrap();
+
+    let (alice_address, miner_address) = (Address::from_public_key(&alice.public_key), Address::from_public_key(&miner.public_key));
+    let at_tip = chain.account_proof(&alice_address, None).unwrap();
+    assert_eq!(at_tip.height, 1);
+    assert_eq!(at_tip.state_root, block.header.state_root);
+    assert_eq!(at_tip.account.as_ref().unwrap().balance, 100);
+    assert!(at_tip.proof.verify(&block.header.state_root, &alice_address.to_bytes(), at_tip.account.as_ref()));
+
+    // Before the transfer Alice did not exist.
+    let at_genesis = chain.account_proof(&alice_address, Some(0)).unwrap();
+    assert!(at_genesis.account.is_none());
+    assert!(at_genesis.proof.verify(&genesis.header.state_root, &alice_address.to_bytes(), None));
+    let miner_then = chain.account_proof(&miner_address, Some(0)).unwrap();
+    assert!(miner_then.proof.verify(&genesis.header.state_root, &miner_address.to_bytes(), miner_then.account.as_ref()));
+
+    // Rolling back for the proof leaves the live state untouched.
+    assert_eq!(chain.state_root_after(&[]).unwrap(), block.header.state_root);
+    assert!(chain.account_proof(&alice_address, Some(2)).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn transactions_apply_in_nonce_order() {
+    let miner = Dilithium3Keypair::new().unwrap();
+    let alice = Dilithium3Keypair::new().unwrap();
+    let chain = NumiBlockchain::new_with_keypair(miner.clone(), ConsensusConfig::development()).unwrap();
+
+    let pay = |amount, nonce| {
+        let mut tx = Transaction::new(
+            DEVELOPMENT_CHAIN_ID,
+            miner.public_key.clone(),
+            TransactionType::Transfer { to: Address::from_public_key(&alice.public_key), amount, memo: None },
+            nonce,
+        );
+        tx.sign(&miner).unwrap();
+        tx
+    };
+
+    // Skipping a nonce, or swapping two, is invalid whatever the root.
+    let gap = mine_on_tip_with_root(&chain, vec![pay(10, 2)], [0u8; 32], &miner);
+    assert!(chain.add_block(gap).await.is_err());
+    let swapped = mine_on_tip_with_root(&chain, vec![pay(10, 2), pay(20, 1)], [0u8; 32], &miner);
+    assert!(chain.add_block(swapped).await.is_err());
+    assert_eq!(chain.get_current_height(), 0);
+
+    let ordered = mine_on_tip(&chain, vec![pay(20, 1), pay(10, 2)], &miner);
+    assert!(chain.add_block(ordered).await.unwrap());
+    assert_eq!(chain.get_balance_by_pubkey(&alice.public_key), 30);
+    assert_eq!(chain.get_account_state_or_default(&miner.public_key).nonce, 2);
+
+    // A nonce cannot be spent twice.
+    let replay = mine_on_tip_with_root(&chain, vec![pay(20, 1)], [0u8; 32], &miner);
+    assert!(chain.add_block(replay).await.is_err());
+}
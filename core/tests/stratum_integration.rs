@@ -8,9 +8,11 @@ use numi_core::config::Config;
 use numi_core::mining_service::MiningService;
 use numi_core::miner::Miner;
 use numi_core::blockchain::NumiBlockchain;
+use numi_core::crypto::Dilithium3Keypair;
 use numi_core::storage::BlockchainStorage;
 use numi_core::network::NetworkManager;
 use numi_core::stratum_server::StratumV2Server;
+use numi_core::wallet::PassphraseSource;
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn test_stratum_server_accepts_connection() {
@@ -20,16 +22,17 @@ async fn test_stratum_server_accepts_connection() {
     drop(listener);
 
     // Prepare supporting services
-    let chain = Arc::new(RwLock::new(NumiBlockchain::new(numi_core::config::ConsensusConfig::default()).unwrap()));
+    let chain = Arc::new(RwLock::new(NumiBlockchain::new_with_keypair(Dilithium3Keypair::new().unwrap(), numi_core::config::ConsensusConfig::default()).unwrap()));
     let storage_dir = tempdir().unwrap();
     let _storage = Arc::new(BlockchainStorage::new(storage_dir.path()).unwrap());
 
     // Prepare NetworkManager using current constructor
     let network_cfg = numi_core::config::NetworkConfig::default();
     let (in_tx, _in_rx) = futures::channel::mpsc::unbounded();
-    let (_network_mgr, network_handle) = NetworkManager::new(&network_cfg, in_tx).unwrap();
-    let cfg_default = Config::default();
-    let miner = Arc::new(RwLock::new(Miner::new(&cfg_default).unwrap()));
+    let (_network_mgr, network_handle) = NetworkManager::new(&network_cfg, numi_core::config::MAINNET_CHAIN_ID, &chain.read().genesis_hash(), in_tx).unwrap();
+    let mut cfg_default = Config::default();
+    cfg_default.storage.data_directory = storage_dir.path().to_path_buf();
+    let miner = Arc::new(RwLock::new(Miner::new(&cfg_default, &PassphraseSource::value("test")).unwrap()));
 
     // Configure Stratum bind address and port
     let mut cfg = Config::default();